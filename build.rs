@@ -0,0 +1,36 @@
+use std::path::Path;
+use std::process::Command;
+
+// Exposes the current commit as `GIT_SHA` so the readiness endpoint can report it.
+// CI can set `GIT_SHA` explicitly when the build runs outside a git checkout.
+fn main() {
+    // `migrate::MIGRATIONS` embeds this directory, so new migrations need a rebuild
+    println!("cargo:rerun-if-changed=migrations");
+
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    // HEAD only changes on checkout; a commit moves the branch it points at, which lives in its
+    // own ref file or, after `git pack-refs` or a fresh clone, in `packed-refs`
+    let mut watched = vec![".git/HEAD".to_string(), ".git/packed-refs".to_string()];
+    if let Some(reference) = std::fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+    {
+        watched.push(format!(".git/{}", reference));
+    }
+    // Cargo reruns the script on every build for paths that don't exist
+    for path in watched.iter().filter(|path| Path::new(path).exists()) {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+
+    let git_sha = std::env::var("GIT_SHA").ok().unwrap_or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|sha| sha.trim().to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    });
+    println!("cargo:rustc-env=GIT_SHA={}", git_sha);
+}
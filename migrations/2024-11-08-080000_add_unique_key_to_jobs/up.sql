@@ -0,0 +1,2 @@
+-- Lets producers that may run twice (e.g. outbox sinks) enqueue a job at most once
+ALTER TABLE jobs ADD COLUMN unique_key VARCHAR UNIQUE;
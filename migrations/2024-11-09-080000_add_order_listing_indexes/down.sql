@@ -0,0 +1,4 @@
+DROP INDEX orders_product_created_at_idx;
+DROP INDEX orders_status_created_at_idx;
+DROP INDEX orders_customer_created_at_idx;
+DROP INDEX orders_created_at_id_idx;
@@ -0,0 +1,6 @@
+-- Keyset pagination walks orders by (created_at, id), optionally within one customer,
+-- status or product
+CREATE INDEX orders_created_at_id_idx ON orders (created_at, id);
+CREATE INDEX orders_customer_created_at_idx ON orders (customer_id, created_at, id);
+CREATE INDEX orders_status_created_at_idx ON orders (status, created_at, id);
+CREATE INDEX orders_product_created_at_idx ON orders (product_id, created_at, id);
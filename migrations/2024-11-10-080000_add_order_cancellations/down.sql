@@ -0,0 +1,9 @@
+DROP TABLE order_cancellations;
+
+-- Postgres can't drop an enum value, so the type is rebuilt without it
+UPDATE orders SET status = 'pending' WHERE status = 'cancelled';
+ALTER TYPE order_status RENAME TO order_status_old;
+CREATE TYPE order_status AS ENUM ('pending', 'shipped', 'delivered');
+ALTER TABLE orders
+    ALTER COLUMN status TYPE order_status USING status::text::order_status;
+DROP TYPE order_status_old;
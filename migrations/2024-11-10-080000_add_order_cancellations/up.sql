@@ -0,0 +1,10 @@
+ALTER TYPE order_status ADD VALUE 'cancelled';
+
+-- One row per cancelled order
+CREATE TABLE order_cancellations (
+    order_id UUID PRIMARY KEY REFERENCES orders(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    -- NULL when the customer cancelled the order themselves
+    admin_id UUID REFERENCES admins(id),
+    cancelled_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
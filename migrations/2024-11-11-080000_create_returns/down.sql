@@ -0,0 +1,4 @@
+DROP TABLE refunds;
+DROP TABLE return_items;
+DROP TABLE returns;
+DROP TYPE return_status;
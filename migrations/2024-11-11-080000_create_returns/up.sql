@@ -0,0 +1,33 @@
+CREATE TYPE return_status AS ENUM ('requested', 'approved', 'rejected', 'received', 'accepted', 'declined');
+
+CREATE TABLE returns (
+    id UUID PRIMARY KEY NOT NULL,
+    order_id UUID NOT NULL REFERENCES orders(id),
+    customer_id UUID NOT NULL REFERENCES customers(id),
+    status return_status NOT NULL DEFAULT 'requested',
+    -- The admin's note on the latest step, e.g. why it was rejected or what inspection found
+    note TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- An order can be returned again only after an earlier return was turned down
+CREATE UNIQUE INDEX returns_one_open_per_order_idx ON returns (order_id)
+    WHERE status NOT IN ('rejected', 'declined');
+CREATE INDEX returns_status_created_at_idx ON returns (status, created_at);
+
+CREATE TABLE return_items (
+    return_id UUID NOT NULL REFERENCES returns(id) ON DELETE CASCADE,
+    product_id UUID NOT NULL REFERENCES products(id),
+    reason TEXT NOT NULL,
+    PRIMARY KEY (return_id, product_id)
+);
+
+CREATE TABLE refunds (
+    id UUID PRIMARY KEY NOT NULL,
+    return_id UUID NOT NULL UNIQUE REFERENCES returns(id),
+    order_id UUID NOT NULL REFERENCES orders(id),
+    -- In the same unit as `products.price`
+    amount INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,23 @@
+CREATE TYPE payment_status AS ENUM ('authorized', 'captured', 'partially_refunded', 'refunded', 'voided', 'failed');
+
+CREATE TABLE payments (
+    id UUID PRIMARY KEY NOT NULL,
+    order_id UUID NOT NULL REFERENCES orders(id),
+    -- Which `PaymentProvider` handled it, e.g. 'mock'
+    provider VARCHAR NOT NULL,
+    -- The provider's id for the authorization; absent when it was declined
+    provider_reference VARCHAR,
+    status payment_status NOT NULL,
+    -- In the same unit as `products.price`
+    amount INTEGER NOT NULL,
+    refunded_amount INTEGER NOT NULL DEFAULT 0,
+    -- Why the provider declined or failed the latest step
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX payments_provider_reference_idx ON payments (provider, provider_reference);
+-- An order is paid at most once; it can be paid again only after a decline or a void
+CREATE UNIQUE INDEX payments_one_active_per_order_idx ON payments (order_id)
+    WHERE status NOT IN ('failed', 'voided');
@@ -0,0 +1,13 @@
+-- Callbacks received from payment providers, kept so a redelivered event is applied once
+CREATE TABLE payment_events (
+    provider VARCHAR NOT NULL,
+    -- The provider's event id
+    event_id VARCHAR NOT NULL,
+    event_type VARCHAR NOT NULL,
+    provider_reference VARCHAR NOT NULL,
+    payload JSONB NOT NULL,
+    -- What receiving it did: 'applied' or 'ignored'
+    outcome VARCHAR NOT NULL,
+    received_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (provider, event_id)
+);
@@ -0,0 +1,20 @@
+-- Responses to mutating requests sent with an `Idempotency-Key` header, replayed to retries
+CREATE TABLE idempotency_keys (
+    -- Who sent it (the JWT subject), so one client's keys never match another's
+    owner VARCHAR NOT NULL,
+    idempotency_key VARCHAR NOT NULL,
+    -- SHA-256 of the method, path and body the key was first used with
+    fingerprint VARCHAR NOT NULL,
+    -- Absent while the first request is still being handled
+    status_code INTEGER,
+    content_type VARCHAR,
+    body BYTEA,
+    -- When the request holding the key started; cleared once its response is stored. A
+    -- request still in progress after `idempotency.lease_secs` is taken to have died.
+    locked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (owner, idempotency_key)
+);
+
+CREATE INDEX idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
@@ -0,0 +1,23 @@
+-- What each order cost when it was placed, in the same unit as `products.price`
+CREATE TABLE order_totals (
+    order_id UUID PRIMARY KEY REFERENCES orders(id) ON DELETE CASCADE,
+    -- The product's price at the time; later price changes don't touch it
+    unit_price INTEGER NOT NULL,
+    subtotal INTEGER NOT NULL,
+    discount INTEGER NOT NULL DEFAULT 0,
+    shipping INTEGER NOT NULL DEFAULT 0,
+    tax INTEGER NOT NULL DEFAULT 0,
+    grand_total INTEGER NOT NULL,
+    -- The region the tax was worked out for; NULL when the order named none
+    tax_region VARCHAR,
+    -- In basis points, 725 being 7.25%
+    tax_rate_bps INTEGER NOT NULL DEFAULT 0,
+    CHECK (grand_total = subtotal - discount + shipping + tax)
+);
+
+-- Orders placed before totals were recorded only have their product's current price to go
+-- on; it is the best snapshot left, untaxed and unshipped
+INSERT INTO order_totals (order_id, unit_price, subtotal, grand_total)
+SELECT orders.id, products.price, products.price, products.price
+FROM orders
+JOIN products ON products.id = orders.product_id;
@@ -0,0 +1,2 @@
+-- The sequence is owned by the column and goes with it
+ALTER TABLE outbox_events DROP COLUMN seq;
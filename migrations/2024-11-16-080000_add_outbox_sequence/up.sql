@@ -0,0 +1,18 @@
+-- Commit order of each customer's events; `outbox::record` serializes a customer's writers,
+-- so a higher `seq` for the same customer always committed later. Existing rows are numbered in the order they were created.
+CREATE SEQUENCE outbox_events_seq_seq;
+ALTER TABLE outbox_events ADD COLUMN seq BIGINT;
+UPDATE outbox_events
+SET seq = ordered.position
+FROM (
+    SELECT id, row_number() OVER (ORDER BY created_at, id) AS position
+    FROM outbox_events
+) ordered
+WHERE outbox_events.id = ordered.id;
+SELECT setval('outbox_events_seq_seq', COALESCE((SELECT MAX(seq) FROM outbox_events), 0) + 1, false);
+ALTER TABLE outbox_events
+    ALTER COLUMN seq SET DEFAULT nextval('outbox_events_seq_seq'),
+    ALTER COLUMN seq SET NOT NULL;
+ALTER SEQUENCE outbox_events_seq_seq OWNED BY outbox_events.seq;
+
+CREATE UNIQUE INDEX outbox_events_seq_idx ON outbox_events (seq);
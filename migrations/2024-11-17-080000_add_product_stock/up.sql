@@ -0,0 +1,3 @@
+-- Units on hand; NULL for products whose stock isn't tracked, which never run out.
+-- Each order holds one unit until it is cancelled or returned.
+ALTER TABLE products ADD COLUMN stock INTEGER CHECK (stock >= 0);
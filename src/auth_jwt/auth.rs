@@ -39,7 +39,7 @@ pub fn create_jwt(user_id: &str) -> Result<String, String> {
 pub fn verify_jwt(token: &str) -> Result<Claims, String> {
     let config = configuration::Settings::new().expect("Failed to load configurations");
     let decoding_key = DecodingKey::from_secret(config.jwt.secret.as_ref());
-    let mut validation = Validation::default();
+    let validation = Validation::default();
     let token_data =
         decode::<Claims>(token, &decoding_key, &validation).map_err(|err| err.to_string())?;
 
@@ -1,27 +1,378 @@
 use config::{Config, ConfigError};
 use serde::Deserialize;
+use std::collections::HashMap;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct DatabaseSettings {
     pub url: String,
     pub test_url: String,
+    #[serde(default)]
+    pub migrate_on_start: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct RedisSettings {
     pub uri: String,
 }
 
-#[derive(Debug, Deserialize)]
+// Where `SessionMiddleware` keeps session state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionBackend {
+    #[default]
+    Redis,
+    Cookie,
+    Memory,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SessionSettings {
+    #[serde(default)]
+    pub backend: SessionBackend,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct JwtSettings {
     pub secret: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MetricsSettings {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelemetrySettings {
+    #[serde(default)]
+    pub otlp_enabled: bool,
+    #[serde(default = "default_otlp_endpoint")]
+    pub otlp_endpoint: String,
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            otlp_enabled: false,
+            otlp_endpoint: default_otlp_endpoint(),
+            sample_ratio: default_sample_ratio(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobSettings {
+    // Number of worker tasks polling the `jobs` table; 0 disables them
+    #[serde(default = "default_job_workers")]
+    pub workers: usize,
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    // A job still running this long after it was claimed is assumed abandoned and retried
+    #[serde(default = "default_stale_after_secs")]
+    pub stale_after_secs: u64,
+}
+
+fn default_job_workers() -> usize {
+    2
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_stale_after_secs() -> u64 {
+    300
+}
+
+impl Default for JobSettings {
+    fn default() -> Self {
+        Self {
+            workers: default_job_workers(),
+            poll_interval_ms: default_poll_interval_ms(),
+            stale_after_secs: default_stale_after_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutboxSettings {
+    // Run the dispatcher that hands `outbox_events` to the event sinks
+    #[serde(default = "default_outbox_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    #[serde(default = "default_outbox_batch_size")]
+    pub batch_size: i64,
+}
+
+fn default_outbox_enabled() -> bool {
+    true
+}
+
+fn default_outbox_batch_size() -> i64 {
+    100
+}
+
+impl Default for OutboxSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_outbox_enabled(),
+            poll_interval_ms: default_poll_interval_ms(),
+            batch_size: default_outbox_batch_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LiveSettings {
+    // LISTEN for committed domain events so SSE clients see changes made by any instance
+    #[serde(default = "default_live_enabled")]
+    pub enabled: bool,
+    // Comment frames sent on idle streams so proxies don't cut them
+    #[serde(default = "default_heartbeat_secs")]
+    pub heartbeat_secs: u64,
+}
+
+fn default_live_enabled() -> bool {
+    true
+}
+
+fn default_heartbeat_secs() -> u64 {
+    15
+}
+
+impl Default for LiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_live_enabled(),
+            heartbeat_secs: default_heartbeat_secs(),
+        }
+    }
+}
+
+// Where notification emails go; `file` writes `.eml` files for local development
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailerBackend {
+    Smtp,
+    #[default]
+    File,
+    Memory,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpSettings {
+    #[serde(default = "default_smtp_host")]
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Disable only for local catchers that don't speak TLS
+    #[serde(default = "default_smtp_starttls")]
+    pub starttls: bool,
+}
+
+fn default_smtp_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_smtp_starttls() -> bool {
+    true
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        Self {
+            host: default_smtp_host(),
+            port: default_smtp_port(),
+            username: None,
+            password: None,
+            starttls: default_smtp_starttls(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailSettings {
+    #[serde(default)]
+    pub backend: MailerBackend,
+    #[serde(default = "default_email_from")]
+    pub from: String,
+    #[serde(default = "default_email_file_dir")]
+    pub file_dir: String,
+    #[serde(default)]
+    pub smtp: SmtpSettings,
+}
+
+fn default_email_from() -> String {
+    "ecommerce <no-reply@localhost>".to_string()
+}
+
+fn default_email_file_dir() -> String {
+    "target/mail".to_string()
+}
+
+impl Default for EmailSettings {
+    fn default() -> Self {
+        Self {
+            backend: MailerBackend::default(),
+            from: default_email_from(),
+            file_dir: default_email_file_dir(),
+            smtp: SmtpSettings::default(),
+        }
+    }
+}
+
+// Which `PaymentProvider` takes payments; only the deterministic mock exists so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentBackend {
+    #[default]
+    Mock,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentSettings {
+    #[serde(default)]
+    pub provider: PaymentBackend,
+    // Shared with the provider to sign its callbacks; callbacks are refused while unset
+    #[serde(default)]
+    pub webhook_secret: String,
+    // Callbacks signed longer ago than this (or this far in the future) are replays
+    #[serde(default = "default_webhook_tolerance_secs")]
+    pub webhook_tolerance_secs: i64,
+}
+
+fn default_webhook_tolerance_secs() -> i64 {
+    300
+}
+
+impl Default for PaymentSettings {
+    fn default() -> Self {
+        Self {
+            provider: PaymentBackend::default(),
+            webhook_secret: String::new(),
+            webhook_tolerance_secs: default_webhook_tolerance_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdempotencySettings {
+    // How long a response is replayed to requests reusing its `Idempotency-Key`
+    #[serde(default = "default_idempotency_ttl_secs")]
+    pub ttl_secs: i64,
+    // How long a request may hold its key before a retry, or the sweep, takes it over
+    #[serde(default = "default_idempotency_lease_secs")]
+    pub lease_secs: i64,
+    // How often expired keys are deleted; 0 disables the sweep
+    #[serde(default = "default_idempotency_sweep_interval_secs")]
+    pub sweep_interval_secs: u64,
+}
+
+fn default_idempotency_ttl_secs() -> i64 {
+    24 * 60 * 60
+}
+
+fn default_idempotency_lease_secs() -> i64 {
+    60
+}
+
+fn default_idempotency_sweep_interval_secs() -> u64 {
+    60 * 60
+}
+
+impl Default for IdempotencySettings {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_idempotency_ttl_secs(),
+            lease_secs: default_idempotency_lease_secs(),
+            sweep_interval_secs: default_idempotency_sweep_interval_secs(),
+        }
+    }
+}
+
+// Which `TaxCalculator` prices orders; only the rate table below exists so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaxBackend {
+    #[default]
+    Regional,
+}
+
+// How a tax that falls between two whole units is rounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxRounding {
+    // Halves go up: 12.5 -> 13
+    #[default]
+    HalfUp,
+    // Halves go to the even neighbour: 12.5 -> 12, 13.5 -> 14
+    HalfEven,
+    // Always down, in the customer's favour
+    Down,
+}
+
+// Amounts are in the same unit as `products.price`; rates are in basis points (725 = 7.25%)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PricingSettings {
+    #[serde(default)]
+    pub tax_calculator: TaxBackend,
+    // For orders without a region; regions missing from `tax_rates` are refused
+    #[serde(default)]
+    pub default_tax_rate_bps: u16,
+    // Keyed by region code, e.g. "us-ca"; matched case-insensitively
+    #[serde(default)]
+    pub tax_rates: HashMap<String, u16>,
+    #[serde(default)]
+    pub tax_rounding: TaxRounding,
+    #[serde(default)]
+    pub shipping_fee: i32,
+    // Orders whose discounted subtotal reaches this ship free
+    #[serde(default)]
+    pub free_shipping_over: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub redis: RedisSettings,
     pub jwt: JwtSettings,
+    #[serde(default)]
+    pub session: SessionSettings,
+    #[serde(default)]
+    pub metrics: MetricsSettings,
+    #[serde(default)]
+    pub telemetry: TelemetrySettings,
+    #[serde(default)]
+    pub jobs: JobSettings,
+    #[serde(default)]
+    pub outbox: OutboxSettings,
+    #[serde(default)]
+    pub email: EmailSettings,
+    #[serde(default)]
+    pub live: LiveSettings,
+    #[serde(default)]
+    pub payments: PaymentSettings,
+    #[serde(default)]
+    pub idempotency: IdempotencySettings,
+    #[serde(default)]
+    pub pricing: PricingSettings,
 }
 
 impl Settings {
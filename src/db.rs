@@ -1,4 +1,3 @@
-use crate::config::configuration;
 use diesel::sql_query;
 use diesel_async::pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager};
 use diesel_async::RunQueryDsl;
@@ -14,12 +13,10 @@ pub async fn establish_connection(database_url: &str) -> PgPool {
         AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(database_url);
 
     // Build the pool
-    let pool = Pool::builder(manager)
+    Pool::builder(manager)
         .max_size(16)
         .build()
-        .expect("Failed to create pool");
-
-    pool
+        .expect("Failed to create pool")
 }
 
 /******************************************/
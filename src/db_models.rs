@@ -4,11 +4,12 @@
 #![allow(clippy::all)]
 
 use chrono::NaiveDateTime;
-use diesel::Queryable;
+use diesel::{Queryable, QueryableByName};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Queryable, Debug, Deserialize, Serialize)]
+#[derive(Queryable, Debug, Clone, Deserialize, Serialize)]
 pub struct Customer {
     pub id: Uuid,
     pub username: String,
@@ -17,7 +18,7 @@ pub struct Customer {
     pub created_at: Option<NaiveDateTime>,
 }
 
-#[derive(Queryable, Debug, Deserialize, Serialize)]
+#[derive(Queryable, Debug, Clone, Deserialize, Serialize, ToSchema)]
 pub struct Order {
     pub id: Uuid,
     pub customer_id: Uuid,
@@ -26,17 +27,134 @@ pub struct Order {
     pub product_id: Uuid,
 }
 
-#[derive(Queryable, Debug, Deserialize, Serialize)]
+#[derive(Queryable, Debug, Clone, Deserialize, Serialize)]
 pub struct Product {
     pub id: Uuid,
     pub name: String,
     pub is_available: bool,
     pub price: i32,
+    pub sku: Option<String>,
+    // Units on hand; `None` when stock isn't tracked
+    pub stock: Option<i32>,
 }
 
-#[derive(Queryable, Debug, Deserialize, Serialize)]
+#[derive(Queryable, Debug, Clone, Deserialize, Serialize)]
 pub struct Admin {
     pub id: Uuid,
     pub username: String,
     pub password_hash: String,
 }
+
+#[derive(Queryable, QueryableByName, Debug, Clone, Serialize, ToSchema)]
+#[diesel(table_name = crate::schema::jobs)]
+pub struct BackgroundJob {
+    pub id: Uuid,
+    pub job_type: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: crate::jobs::JobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: NaiveDateTime,
+    pub locked_at: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub unique_key: Option<String>,
+}
+
+#[derive(Queryable, Debug, Clone, Serialize)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    pub event_type: String,
+    pub aggregate_id: Uuid,
+    pub payload: serde_json::Value,
+    pub created_at: NaiveDateTime,
+    pub dispatched_at: Option<NaiveDateTime>,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub seq: i64,
+}
+
+#[derive(Queryable, Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookEndpoint {
+    pub id: Uuid,
+    pub url: String,
+    // Supplied by the admin at registration and never echoed back
+    #[serde(skip)]
+    pub secret: String,
+    pub event_types: Vec<String>,
+    pub is_active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub endpoint_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: crate::webhooks::DeliveryStatus,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReturnRequest {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    pub status: crate::returns::ReturnStatus,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReturnItem {
+    pub return_id: Uuid,
+    pub product_id: Uuid,
+    pub reason: String,
+}
+
+#[derive(Queryable, Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Refund {
+    pub id: Uuid,
+    pub return_id: Uuid,
+    pub order_id: Uuid,
+    pub amount: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Payment {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub provider: String,
+    pub provider_reference: Option<String>,
+    pub status: crate::payments::PaymentStatus,
+    pub amount: i32,
+    pub refunded_amount: i32,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone)]
+pub struct IdempotencyKey {
+    pub owner: String,
+    pub idempotency_key: String,
+    pub fingerprint: String,
+    pub status_code: Option<i32>,
+    pub content_type: Option<String>,
+    pub body: Option<Vec<u8>>,
+    pub locked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
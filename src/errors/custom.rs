@@ -0,0 +1,140 @@
+use crate::middleware::current_request_id;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+#[derive(Debug, Error)]
+pub enum CustomError {
+    #[error("Database Error: {0}")]
+    DatabaseError(#[from] DbError),
+
+    #[error("Blocking Error: {0}")]
+    BlockingError(String),
+
+    #[error("Hashing Error: {0}")]
+    HashingError(String),
+
+    #[error("Validation Error: {0}")]
+    ValidationError(String),
+
+    #[error("Authentication Error: {0}")]
+    AuthenticationError(#[from] AuthError),
+
+    #[error("Fixture Error: {0}")]
+    FixtureError(String),
+
+    #[error("Not Found: {0}")]
+    NotFoundError(String),
+
+    #[error("Conflict: {0}")]
+    ConflictError(String),
+
+    #[error("Unprocessable: {0}")]
+    UnprocessableError(String),
+
+    #[error("Payment Error: {0}")]
+    PaymentError(#[from] PaymentError),
+}
+
+// JSON body returned for every `CustomError`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("Connection Error: {0}")]
+    ConnectionError(String),
+
+    #[error("Query Error: {0}")]
+    QueryBuilderError(String),
+
+    #[error("Insertion Error: {0}")]
+    InsertionError(String),
+
+    #[error("Updation Error: {0}")]
+    UpdationError(String),
+
+    #[error("Migration Error: {0}")]
+    MigrationError(String),
+
+    #[error("Other Database Error: {0}")]
+    Other(String),
+}
+
+// Lets Diesel errors propagate with `?` out of transactions that also return `CustomError`
+impl From<diesel::result::Error> for DbError {
+    fn from(err: diesel::result::Error) -> Self {
+        DbError::QueryBuilderError(err.to_string())
+    }
+}
+
+impl From<diesel::result::Error> for CustomError {
+    fn from(err: diesel::result::Error) -> Self {
+        CustomError::DatabaseError(err.into())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Session Authentication Error: {0}")]
+    SessionAuthenticationError(String),
+
+    #[error("JWT Authentication Error: {0}")]
+    JwtAuthenticationError(String),
+
+    #[error("Other Authentication Error: {0}")]
+    OtherAuthenticationError(String),
+}
+
+// What a `PaymentProvider` reports when it doesn't go through
+#[derive(Debug, Error)]
+pub enum PaymentError {
+    #[error("Declined: {0}")]
+    Declined(String),
+
+    #[error("Provider Unavailable: {0}")]
+    Unavailable(String),
+}
+impl ResponseError for CustomError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CustomError::BlockingError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            CustomError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            CustomError::HashingError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            CustomError::FixtureError(_) => StatusCode::BAD_REQUEST,
+            CustomError::NotFoundError(_) => StatusCode::NOT_FOUND,
+            CustomError::ConflictError(_) => StatusCode::CONFLICT,
+            CustomError::UnprocessableError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            CustomError::DatabaseError(err) => match err {
+                DbError::ConnectionError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                DbError::QueryBuilderError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                DbError::InsertionError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                DbError::UpdationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                DbError::MigrationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                DbError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            CustomError::PaymentError(err) => match err {
+                PaymentError::Declined(_) => StatusCode::PAYMENT_REQUIRED,
+                PaymentError::Unavailable(_) => StatusCode::BAD_GATEWAY,
+            },
+            CustomError::AuthenticationError(err) => match err {
+                AuthError::SessionAuthenticationError(_) => StatusCode::UNAUTHORIZED,
+                AuthError::JwtAuthenticationError(_) => StatusCode::UNAUTHORIZED,
+                AuthError::OtherAuthenticationError(_) => StatusCode::UNAUTHORIZED,
+            },
+        }
+    }
+
+    // The request id lets support find the matching log lines for a reported error
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: self.to_string(),
+            request_id: current_request_id(),
+        })
+    }
+}
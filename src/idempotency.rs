@@ -0,0 +1,379 @@
+use crate::auth_jwt::auth::Claims;
+use crate::config::configuration::IdempotencySettings;
+use crate::db::PgPool;
+use crate::db_models::IdempotencyKey;
+use crate::errors::custom::{AuthError, CustomError, DbError};
+use crate::schema::idempotency_keys::dsl as key_dsl;
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpResponse};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel_async::RunQueryDsl;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+// Set on responses replayed from an earlier request
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/******************************************/
+// Idempotency keys
+/******************************************/
+// A client that retries a mutating request with the same `Idempotency-Key` gets the first
+// response back instead of running it again. Keys belong to the JWT subject and are
+// remembered for `idempotency.ttl_secs`; reusing one for a different request is a 422.
+// While the first request runs its key is leased to it (`locked_at`); a key still in progress
+// after `idempotency.lease_secs` belongs to a request that died, and the next claim takes it.
+
+pub fn validate_key(value: &HeaderValue) -> Result<String, CustomError> {
+    let key = value.to_str().map(str::trim).unwrap_or_default();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH || !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(CustomError::ValidationError(format!(
+            "Idempotency-Key must be 1 to {} visible ASCII characters",
+            MAX_KEY_LENGTH
+        )));
+    }
+    Ok(key.to_string())
+}
+
+// What makes two requests "the same": method, path with query, and body
+pub fn fingerprint(method: &Method, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str().as_bytes());
+    hasher.update(b"\n");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+enum Claim {
+    // First use of the key, leased at the given time; the request runs and its response is
+    // stored
+    Fresh(chrono::NaiveDateTime),
+    // Seen before, possibly still running
+    Seen(IdempotencyKey),
+}
+
+async fn connection(
+    pool: &PgPool,
+) -> Result<
+    diesel_async::pooled_connection::deadpool::Object<diesel_async::AsyncPgConnection>,
+    CustomError,
+> {
+    pool.get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))
+}
+
+// Inserting first means two concurrent requests with one key can't both run
+async fn claim(
+    pool: &PgPool,
+    owner: &str,
+    key: &str,
+    fingerprint: &str,
+    settings: &IdempotencySettings,
+) -> Result<Claim, CustomError> {
+    let mut conn = connection(pool).await?;
+    let now = chrono::Utc::now().naive_utc();
+    // The sweep may not have reached this key yet; an expired or abandoned key is free to reuse
+    diesel::delete(
+        key_dsl::idempotency_keys
+            .find((owner, key))
+            .filter(reusable(now, settings.lease_secs)),
+    )
+    .execute(&mut conn)
+    .await?;
+    let inserted = diesel::insert_into(key_dsl::idempotency_keys)
+        .values((
+            key_dsl::owner.eq(owner),
+            key_dsl::idempotency_key.eq(key),
+            key_dsl::fingerprint.eq(fingerprint),
+            key_dsl::locked_at.eq(now),
+            key_dsl::created_at.eq(now),
+            key_dsl::expires_at.eq(now + chrono::Duration::seconds(settings.ttl_secs)),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await?;
+    if inserted == 1 {
+        return Ok(Claim::Fresh(now));
+    }
+    let stored = key_dsl::idempotency_keys
+        .find((owner, key))
+        .first::<IdempotencyKey>(&mut conn)
+        .await?;
+    Ok(Claim::Seen(stored))
+}
+
+// Keys past their TTL, and keys whose request has held them past the lease
+fn reusable(
+    now: chrono::NaiveDateTime,
+    lease_secs: i64,
+) -> Box<dyn BoxableExpression<key_dsl::idempotency_keys, Pg, SqlType = Bool>> {
+    let lease_start = now - chrono::Duration::seconds(lease_secs);
+    Box::new(
+        key_dsl::expires_at.le(now).or(key_dsl::status_code
+            .is_null()
+            .and(key_dsl::locked_at.le(lease_start))
+            .assume_not_null()),
+    )
+}
+
+// Only while the lease taken at `locked_at` is still ours; once another request has taken
+// the key over, its outcome is the one kept
+async fn store(
+    pool: &PgPool,
+    owner: &str,
+    key: &str,
+    locked_at: chrono::NaiveDateTime,
+    status: StatusCode,
+    content_type: Option<String>,
+    body: &[u8],
+) -> Result<(), CustomError> {
+    let mut conn = connection(pool).await?;
+    diesel::update(
+        key_dsl::idempotency_keys
+            .find((owner, key))
+            .filter(key_dsl::locked_at.eq(locked_at)),
+    )
+    .set((
+        key_dsl::status_code.eq(i32::from(status.as_u16())),
+        key_dsl::content_type.eq(content_type),
+        key_dsl::body.eq(body),
+        key_dsl::locked_at.eq(None::<chrono::NaiveDateTime>),
+    ))
+    .execute(&mut conn)
+    .await?;
+    Ok(())
+}
+
+// Forgets a key whose request failed on our side, so a retry runs it again
+async fn release(
+    pool: &PgPool,
+    owner: &str,
+    key: &str,
+    locked_at: chrono::NaiveDateTime,
+) -> Result<(), CustomError> {
+    let mut conn = connection(pool).await?;
+    diesel::delete(
+        key_dsl::idempotency_keys
+            .find((owner, key))
+            .filter(key_dsl::locked_at.eq(locked_at)),
+    )
+    .execute(&mut conn)
+    .await?;
+    Ok(())
+}
+
+// Deletes every expired or abandoned key and returns how many there were
+pub async fn purge_expired(pool: &PgPool, lease_secs: i64) -> Result<usize, CustomError> {
+    let mut conn = connection(pool).await?;
+    let now = chrono::Utc::now().naive_utc();
+    let purged = diesel::delete(key_dsl::idempotency_keys.filter(reusable(now, lease_secs)))
+        .execute(&mut conn)
+        .await?;
+    Ok(purged)
+}
+
+fn replay(stored: &IdempotencyKey, status: i32) -> HttpResponse {
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::OK);
+    let mut response = HttpResponse::build(status);
+    response.insert_header((REPLAYED_HEADER, "true"));
+    if let Some(content_type) = &stored.content_type {
+        response.insert_header((CONTENT_TYPE, content_type.as_str()));
+    }
+    response.body(stored.body.clone().unwrap_or_default())
+}
+
+/******************************************/
+// Expired key sweep
+/******************************************/
+pub fn spawn_sweeper(pool: PgPool, settings: &IdempotencySettings) -> Option<JoinHandle<()>> {
+    if settings.sweep_interval_secs == 0 {
+        return None;
+    }
+    let sweep_interval = Duration::from_secs(settings.sweep_interval_secs);
+    let lease_secs = settings.lease_secs;
+    Some(tokio::spawn(async move {
+        loop {
+            match purge_expired(&pool, lease_secs).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} expired idempotency keys", purged),
+                Err(err) => tracing::warn!("Failed to purge idempotency keys: {}", err),
+            }
+            tokio::time::sleep(sweep_interval).await;
+        }
+    }))
+}
+
+/******************************************/
+// Middleware
+/******************************************/
+// Wrapped inside `jwt_auth_middleware`, whose claims name the key's owner. Requests without
+// the header, and reads, pass straight through.
+pub async fn idempotency_middleware(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let mutating = matches!(
+        *req.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    let header = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(header) if mutating => header.clone(),
+        _ => {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_boxed_body)
+        }
+    };
+    let key = validate_key(&header)?;
+    let owner = req
+        .extensions()
+        .get::<Claims>()
+        .map(|claims| claims.sub.clone())
+        .ok_or_else(|| {
+            CustomError::AuthenticationError(AuthError::JwtAuthenticationError(
+                "Missing token".to_string(),
+            ))
+        })?;
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .cloned()
+        .ok_or_else(|| CustomError::BlockingError("Database pool unavailable".to_string()))?;
+    let settings = req
+        .app_data::<web::Data<IdempotencySettings>>()
+        .map(|settings| settings.get_ref().clone())
+        .unwrap_or_default();
+
+    // Read to fingerprint it, then handed back for the handler to extract as usual
+    let body = req.extract::<web::Bytes>().await?;
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| req.path().to_string());
+    let fingerprint = fingerprint(req.method(), &path, &body);
+    req.set_payload(Payload::from(body));
+
+    let locked_at = match claim(&pool, &owner, &key, &fingerprint, &settings).await? {
+        Claim::Fresh(locked_at) => locked_at,
+        Claim::Seen(stored) => {
+            if stored.fingerprint != fingerprint {
+                return Err(CustomError::UnprocessableError(format!(
+                    "Idempotency-Key {} was already used for a different request",
+                    key
+                ))
+                .into());
+            }
+            let Some(status) = stored.status_code else {
+                return Err(CustomError::ConflictError(format!(
+                    "A request with Idempotency-Key {} is still in progress",
+                    key
+                ))
+                .into());
+            };
+            return Ok(req.into_response(replay(&stored, status)));
+        }
+    };
+
+    let res = match next.call(req).await {
+        Ok(res) => res,
+        Err(err) => {
+            release(&pool, &owner, &key, locked_at).await?;
+            return Err(err);
+        }
+    };
+    // Server errors aren't remembered, so a retry gets another go
+    if res.status().is_server_error() {
+        release(&pool, &owner, &key, locked_at).await?;
+        return Ok(res.map_into_boxed_body());
+    }
+    let (http_req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = match body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => {
+            release(&pool, &owner, &key, locked_at).await?;
+            return Err(
+                CustomError::BlockingError("Failed to read the response body".to_string()).into(),
+            );
+        }
+    };
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    // The request has taken effect, so the client gets its response either way. Without a
+    // stored response the key would stay "in progress" and every retry would be a 409, so
+    // it is released instead.
+    if let Err(err) = store(
+        &pool,
+        &owner,
+        &key,
+        locked_at,
+        res.status(),
+        content_type,
+        &body,
+    )
+    .await
+    {
+        tracing::error!(%owner, %key, "Failed to store idempotent response: {}", err);
+        if let Err(err) = release(&pool, &owner, &key, locked_at).await {
+            tracing::error!(%owner, %key, "Failed to release idempotency key: {}", err);
+        }
+    }
+    Ok(ServiceResponse::new(
+        http_req,
+        res.set_body(BoxBody::new(body)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fingerprint, validate_key};
+    use actix_web::http::header::HeaderValue;
+    use actix_web::http::Method;
+
+    #[test]
+    fn keys_are_trimmed_and_bounded() {
+        assert_eq!(
+            validate_key(&HeaderValue::from_static(" order-42 ")).unwrap(),
+            "order-42"
+        );
+        assert!(validate_key(&HeaderValue::from_static("")).is_err());
+        assert!(validate_key(&HeaderValue::from_static("has space")).is_err());
+        let long = "k".repeat(256);
+        assert!(validate_key(&HeaderValue::from_str(&long).unwrap()).is_err());
+    }
+
+    #[test]
+    fn fingerprints_cover_method_path_and_body() {
+        let body = br#"{"product_id":"5fcd7d83-7adf-4d4d-931a-68b9678009db"}"#;
+        let original = fingerprint(&Method::POST, "/api/v1/orders", body);
+        assert_eq!(original, fingerprint(&Method::POST, "/api/v1/orders", body));
+        assert_ne!(original, fingerprint(&Method::PUT, "/api/v1/orders", body));
+        assert_ne!(
+            original,
+            fingerprint(&Method::POST, "/protected/orders/new", body)
+        );
+        assert_ne!(
+            original,
+            fingerprint(&Method::POST, "/api/v1/orders", b"{}")
+        );
+    }
+}
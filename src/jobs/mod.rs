@@ -0,0 +1,230 @@
+pub mod worker;
+
+use crate::db::PgPool;
+use crate::db_models::BackgroundJob;
+use crate::errors::custom::{CustomError, DbError};
+use crate::schema::jobs::dsl as job_dsl;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, diesel_derive_enum::DbEnum, Serialize, Deserialize, ToSchema,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::JobStatus"]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    // Out of attempts; stays put until an admin requeues it
+    Dead,
+}
+
+/******************************************/
+// Typed jobs and their handlers
+/******************************************/
+// A job payload; `JOB_TYPE` is stored with the row so a worker can find its handler
+pub trait Job: Serialize + DeserializeOwned + Send + 'static {
+    const JOB_TYPE: &'static str;
+    const MAX_ATTEMPTS: i32 = 5;
+}
+
+#[async_trait]
+pub trait JobHandler<J: Job>: Send + Sync + 'static {
+    // An `Err` schedules a retry with backoff, or dead-letters the job on its last attempt
+    async fn handle(&self, job: J) -> Result<(), String>;
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+type ErasedHandler = Arc<dyn Fn(serde_json::Value) -> JobFuture + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    handlers: HashMap<&'static str, ErasedHandler>,
+}
+
+impl JobRegistry {
+    pub fn register<J: Job, H: JobHandler<J>>(mut self, handler: H) -> Self {
+        let handler = Arc::new(handler);
+        let erased: ErasedHandler = Arc::new(move |payload| {
+            let handler = handler.clone();
+            Box::pin(async move {
+                let job: J = serde_json::from_value(payload)
+                    .map_err(|err| format!("Invalid {} payload: {}", J::JOB_TYPE, err))?;
+                handler.handle(job).await
+            })
+        });
+        self.handlers.insert(J::JOB_TYPE, erased);
+        self
+    }
+
+    fn handler(&self, job_type: &str) -> Option<ErasedHandler> {
+        self.handlers.get(job_type).cloned()
+    }
+}
+
+/******************************************/
+// Enqueueing jobs
+/******************************************/
+pub async fn enqueue<J: Job>(pool: &PgPool, job: &J) -> Result<Uuid, CustomError> {
+    enqueue_at(pool, job, chrono::Utc::now().naive_utc()).await
+}
+
+pub async fn enqueue_at<J: Job>(
+    pool: &PgPool,
+    job: &J,
+    run_at: NaiveDateTime,
+) -> Result<Uuid, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    enqueue_with(&mut conn, job, run_at).await
+}
+
+// Takes a connection so callers can enqueue inside their own transaction
+pub async fn enqueue_with<J: Job>(
+    conn: &mut AsyncPgConnection,
+    job: &J,
+    run_at: NaiveDateTime,
+) -> Result<Uuid, CustomError> {
+    let job_id = insert_job(conn, job, run_at, None).await?;
+    // Without a unique key there is nothing to conflict with
+    job_id.ok_or_else(|| {
+        CustomError::DatabaseError(DbError::InsertionError(
+            "Failed data insertion in db".to_string(),
+        ))
+    })
+}
+
+// Enqueues `job` unless one was already enqueued under `unique_key`, returning None then
+pub async fn enqueue_unique<J: Job>(
+    pool: &PgPool,
+    job: &J,
+    unique_key: &str,
+) -> Result<Option<Uuid>, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    insert_job(
+        &mut conn,
+        job,
+        chrono::Utc::now().naive_utc(),
+        Some(unique_key),
+    )
+    .await
+}
+
+async fn insert_job<J: Job>(
+    conn: &mut AsyncPgConnection,
+    job: &J,
+    run_at: NaiveDateTime,
+    unique_key: Option<&str>,
+) -> Result<Option<Uuid>, CustomError> {
+    let payload = serde_json::to_value(job)
+        .map_err(|err| CustomError::ValidationError(format!("Unserializable job: {}", err)))?;
+    let job_id = Uuid::new_v4();
+    let now = chrono::Utc::now().naive_utc();
+    let inserted = diesel::insert_into(job_dsl::jobs)
+        .values((
+            job_dsl::id.eq(job_id),
+            job_dsl::job_type.eq(J::JOB_TYPE),
+            job_dsl::payload.eq(payload),
+            job_dsl::status.eq(JobStatus::Pending),
+            job_dsl::max_attempts.eq(J::MAX_ATTEMPTS),
+            job_dsl::run_at.eq(run_at),
+            job_dsl::created_at.eq(now),
+            job_dsl::updated_at.eq(now),
+            job_dsl::unique_key.eq(unique_key),
+        ))
+        .on_conflict(job_dsl::unique_key)
+        .do_nothing()
+        .execute(conn)
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::InsertionError(err.to_string())))?;
+    Ok((inserted > 0).then_some(job_id))
+}
+
+/******************************************/
+// Inspecting and requeueing jobs
+/******************************************/
+pub async fn list_jobs(
+    pool: &PgPool,
+    status: Option<JobStatus>,
+    limit: i64,
+) -> Result<Vec<BackgroundJob>, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    let mut query = job_dsl::jobs
+        .order(job_dsl::updated_at.desc())
+        .limit(limit)
+        .into_boxed();
+    if let Some(status) = status {
+        query = query.filter(job_dsl::status.eq(status));
+    }
+    query
+        .load::<BackgroundJob>(&mut conn)
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))
+}
+
+pub async fn find_job(pool: &PgPool, job_id: Uuid) -> Result<Option<BackgroundJob>, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    job_dsl::jobs
+        .find(job_id)
+        .first::<BackgroundJob>(&mut conn)
+        .await
+        .optional()
+        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))
+}
+
+// Gives a dead job a fresh set of attempts; anything else is left alone
+pub async fn requeue_job(pool: &PgPool, job_id: Uuid) -> Result<BackgroundJob, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    let now = chrono::Utc::now().naive_utc();
+    let requeued = diesel::update(
+        job_dsl::jobs
+            .find(job_id)
+            .filter(job_dsl::status.eq(JobStatus::Dead)),
+    )
+    .set((
+        job_dsl::status.eq(JobStatus::Pending),
+        job_dsl::attempts.eq(0),
+        job_dsl::run_at.eq(now),
+        job_dsl::locked_at.eq(None::<NaiveDateTime>),
+        job_dsl::updated_at.eq(now),
+    ))
+    .get_result::<BackgroundJob>(&mut conn)
+    .await
+    .optional()
+    .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
+
+    match requeued {
+        Some(job) => Ok(job),
+        None => match find_job(pool, job_id).await? {
+            Some(job) => Err(CustomError::ConflictError(format!(
+                "Job {} is {:?}, only Dead jobs can be requeued",
+                job.id, job.status
+            ))),
+            None => Err(CustomError::NotFoundError(format!("Job {}", job_id))),
+        },
+    }
+}
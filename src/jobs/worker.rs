@@ -0,0 +1,237 @@
+use super::{JobRegistry, JobStatus};
+use crate::config::configuration::JobSettings;
+use crate::db::PgPool;
+use crate::db_models::BackgroundJob;
+use crate::errors::custom::{CustomError, DbError};
+use crate::schema::jobs::dsl as job_dsl;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Double;
+use diesel_async::RunQueryDsl;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+const BASE_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+// Claims the oldest runnable job in one statement; `SKIP LOCKED` lets concurrent
+// workers (and instances) pass over rows another worker is about to take. A stale running
+// job is one whose worker died; it is retried like a failure, so only while attempts remain.
+const CLAIM_JOB_SQL: &str = r#"
+    UPDATE jobs
+    SET status = 'running',
+        attempts = attempts + 1,
+        locked_at = (now() AT TIME ZONE 'utc'),
+        updated_at = (now() AT TIME ZONE 'utc')
+    WHERE id = (
+        SELECT id FROM jobs
+        WHERE (status = 'pending' AND run_at <= (now() AT TIME ZONE 'utc'))
+           OR (status = 'running'
+               AND locked_at < (now() AT TIME ZONE 'utc') - make_interval(secs => $1)
+               AND attempts < max_attempts)
+        ORDER BY run_at
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+    )
+    RETURNING *
+"#;
+
+// Stale running jobs whose worker died on the last attempt go to the dead-letter queue
+const BURY_STALE_JOBS_SQL: &str = r#"
+    UPDATE jobs
+    SET status = 'dead',
+        locked_at = NULL,
+        last_error = 'Worker stopped during the last attempt',
+        updated_at = (now() AT TIME ZONE 'utc')
+    WHERE status = 'running'
+      AND locked_at < (now() AT TIME ZONE 'utc') - make_interval(secs => $1)
+      AND attempts >= max_attempts
+"#;
+
+/******************************************/
+// Worker tasks
+/******************************************/
+pub fn spawn_workers(
+    pool: PgPool,
+    registry: JobRegistry,
+    settings: &JobSettings,
+) -> Vec<JoinHandle<()>> {
+    let poll_interval = Duration::from_millis(settings.poll_interval_ms);
+    let stale_after = Duration::from_secs(settings.stale_after_secs);
+    (0..settings.workers)
+        .map(|worker| {
+            let pool = pool.clone();
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                tracing::info!("Job worker {} started", worker);
+                loop {
+                    match run_next(&pool, &registry, stale_after).await {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(err) => tracing::warn!("Job worker {} failed to poll: {}", worker, err),
+                    }
+                    tokio::time::sleep(poll_interval).await;
+                }
+            })
+        })
+        .collect()
+}
+
+// Claims and runs at most one job; returns whether there was one
+pub async fn run_next(
+    pool: &PgPool,
+    registry: &JobRegistry,
+    stale_after: Duration,
+) -> Result<bool, CustomError> {
+    let job = match claim_next(pool, stale_after).await? {
+        Some(job) => job,
+        None => return Ok(false),
+    };
+
+    let span = tracing::info_span!(
+        "Run job",
+        job_id = %job.id,
+        job_type = %job.job_type,
+        attempt = job.attempts
+    );
+    async {
+        let outcome = match registry.handler(&job.job_type) {
+            // Run on its own task so a panicking handler counts as a failed attempt
+            Some(handler) => match tokio::spawn(handler(job.payload.clone())).await {
+                Ok(result) => result,
+                Err(err) => Err(format!("Job handler panicked: {}", err)),
+            },
+            None => Err(format!("No handler registered for {}", job.job_type)),
+        };
+
+        match outcome {
+            Ok(()) => mark_completed(pool, &job).await,
+            Err(error) => {
+                tracing::warn!("Job failed: {}", error);
+                mark_failed(pool, &job, &error).await
+            }
+        }
+    }
+    .instrument(span)
+    .await?;
+    Ok(true)
+}
+
+async fn claim_next(
+    pool: &PgPool,
+    stale_after: Duration,
+) -> Result<Option<BackgroundJob>, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    let buried = sql_query(BURY_STALE_JOBS_SQL)
+        .bind::<Double, _>(stale_after.as_secs_f64())
+        .execute(&mut conn)
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::UpdationError(err.to_string())))?;
+    if buried > 0 {
+        tracing::warn!("Moved {} stale jobs out of attempts to dead", buried);
+    }
+    sql_query(CLAIM_JOB_SQL)
+        .bind::<Double, _>(stale_after.as_secs_f64())
+        .get_result::<BackgroundJob>(&mut conn)
+        .await
+        .optional()
+        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))
+}
+
+async fn mark_completed(pool: &PgPool, job: &BackgroundJob) -> Result<(), CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    let updated = diesel::update(
+        job_dsl::jobs
+            .find(job.id)
+            .filter(job_dsl::status.eq(JobStatus::Running))
+            .filter(job_dsl::locked_at.eq(job.locked_at)),
+    )
+    .set((
+        job_dsl::status.eq(JobStatus::Completed),
+        job_dsl::locked_at.eq(None::<NaiveDateTime>),
+        job_dsl::last_error.eq(None::<String>),
+        job_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .execute(&mut conn)
+    .await
+    .map_err(|err| CustomError::DatabaseError(DbError::UpdationError(err.to_string())))?;
+    warn_if_taken_over(updated);
+    Ok(())
+}
+
+async fn mark_failed(pool: &PgPool, job: &BackgroundJob, error: &str) -> Result<(), CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    let now = chrono::Utc::now().naive_utc();
+    let (status, run_at) = if job.attempts >= job.max_attempts {
+        (JobStatus::Dead, job.run_at)
+    } else {
+        let delay = chrono::Duration::from_std(backoff_delay(job.attempts))
+            .unwrap_or_else(|_| chrono::Duration::hours(1));
+        (JobStatus::Pending, now + delay)
+    };
+    let updated = diesel::update(
+        job_dsl::jobs
+            .find(job.id)
+            .filter(job_dsl::status.eq(JobStatus::Running))
+            .filter(job_dsl::locked_at.eq(job.locked_at)),
+    )
+    .set((
+        job_dsl::status.eq(status),
+        job_dsl::run_at.eq(run_at),
+        job_dsl::locked_at.eq(None::<NaiveDateTime>),
+        job_dsl::last_error.eq(error),
+        job_dsl::updated_at.eq(now),
+    ))
+    .execute(&mut conn)
+    .await
+    .map_err(|err| CustomError::DatabaseError(DbError::UpdationError(err.to_string())))?;
+    warn_if_taken_over(updated);
+    Ok(())
+}
+
+// `mark_completed` and `mark_failed` only touch the row while it still carries this worker's
+// claim. A run that outlived `stale_after` may have been claimed again by another worker,
+// whose claim set a new `locked_at`; the outcome is then theirs to record.
+fn warn_if_taken_over(updated: usize) {
+    if updated == 0 {
+        tracing::warn!("Job was claimed again while running; leaving it to the new claim");
+    }
+}
+
+// 5s, 10s, 20s, ... capped at an hour
+pub fn backoff_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff_delay, MAX_BACKOFF};
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_doubles_per_attempt() {
+        assert_eq!(backoff_delay(1), Duration::from_secs(5));
+        assert_eq!(backoff_delay(2), Duration::from_secs(10));
+        assert_eq!(backoff_delay(4), Duration::from_secs(40));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(backoff_delay(30), MAX_BACKOFF);
+        assert_eq!(backoff_delay(i32::MAX), MAX_BACKOFF);
+    }
+}
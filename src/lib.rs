@@ -3,10 +3,25 @@ pub mod config;
 pub mod db;
 pub mod db_models;
 pub mod errors;
+pub mod idempotency;
+pub mod jobs;
+pub mod live;
+pub mod mailer;
+pub mod metrics;
 pub mod middleware;
+pub mod migrate;
+pub mod openapi;
+pub mod operator;
+pub mod outbox;
+pub mod payments;
+pub mod pricing;
+pub mod repository;
+pub mod returns;
 pub mod routes;
 pub mod schema;
 pub mod session_state;
+pub mod session_store;
 pub mod startup;
 pub mod telemetry;
 pub mod validations;
+pub mod webhooks;
@@ -0,0 +1,172 @@
+use crate::db::PgPool;
+use crate::db_models::OutboxEvent;
+use crate::errors::custom::{CustomError, DbError};
+use crate::outbox::{self, EventEnvelope};
+use crate::schema::orders::dsl as order_dsl;
+use crate::schema::outbox_events;
+use crate::schema::outbox_events::dsl as outbox_dsl;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel_async::pooled_connection::deadpool::Object;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_postgres::{AsyncMessage, NoTls};
+use uuid::Uuid;
+
+const CHANNEL_CAPACITY: usize = 256;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// Most events read from the outbox per replay query; longer backlogs are paged
+pub const REPLAY_PAGE: i64 = 500;
+
+/******************************************/
+// In-process fan-out
+/******************************************/
+// Every committed domain event, as received from Postgres. Each instance runs its own
+// listener, so subscribers see changes made through any instance.
+#[derive(Clone)]
+pub struct LiveEvents {
+    sender: broadcast::Sender<EventEnvelope>,
+    heartbeat: Duration,
+}
+
+impl LiveEvents {
+    pub fn new(heartbeat: Duration) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender, heartbeat }
+    }
+
+    pub fn heartbeat(&self) -> Duration {
+        self.heartbeat
+    }
+
+    // Having no subscribers is not an error; nobody is watching yet
+    pub fn publish(&self, envelope: EventEnvelope) {
+        let _ = self.sender.send(envelope);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<EventEnvelope> {
+        self.sender.subscribe()
+    }
+}
+
+/******************************************/
+// Postgres listener
+/******************************************/
+// Keeps a dedicated connection LISTENing on `outbox::NOTIFY_CHANNEL`, reconnecting on
+// failure. Events committed while it is reconnecting are not replayed here; clients catch
+// up with `Last-Event-ID` instead.
+pub fn spawn_listener(database_url: String, live: LiveEvents) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = listen(&database_url, &live).await {
+                tracing::warn!("Live event listener disconnected: {}", err);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn listen(database_url: &str, live: &LiveEvents) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = tokio_postgres::connect(database_url, NoTls).await?;
+    let publisher = live.clone();
+    // Notifications only arrive while the connection is polled, and it must be polled for
+    // `LISTEN` itself to complete
+    let driver = tokio::spawn(async move {
+        loop {
+            match std::future::poll_fn(|cx| connection.poll_message(cx)).await {
+                Some(Ok(AsyncMessage::Notification(notification))) => {
+                    match serde_json::from_str::<EventEnvelope>(notification.payload()) {
+                        Ok(envelope) => publisher.publish(envelope),
+                        Err(err) => tracing::warn!("Unreadable live event: {}", err),
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err),
+                None => return Ok(()),
+            }
+        }
+    });
+    client
+        .batch_execute(&format!("LISTEN {}", outbox::NOTIFY_CHANNEL))
+        .await?;
+    tracing::info!("Listening for live events on {}", outbox::NOTIFY_CHANNEL);
+
+    // The client has to outlive the driver or the connection closes
+    let result = driver.await;
+    drop(client);
+    match result {
+        Ok(result) => result,
+        Err(err) => {
+            tracing::warn!("Live event listener task failed: {}", err);
+            Ok(())
+        }
+    }
+}
+
+/******************************************/
+// Replay
+/******************************************/
+// Where a reconnecting client left off: the `seq` of the event it last received, or `None`
+// for an id that is unknown (or was purged)
+pub async fn sequence_of(pool: &PgPool, event_id: Uuid) -> Result<Option<i64>, CustomError> {
+    let mut conn = connection(pool).await?;
+    let seq = outbox_dsl::outbox_events
+        .find(event_id)
+        .select(outbox_dsl::seq)
+        .first::<i64>(&mut conn)
+        .await
+        .optional()?;
+    Ok(seq)
+}
+
+// The `seq` of the customer's latest committed status change, or 0 if there is none; where
+// a new stream starts watching from
+pub async fn latest_sequence(pool: &PgPool, customer_id: Uuid) -> Result<i64, CustomError> {
+    let mut conn = connection(pool).await?;
+    let seq = status_changes_of(customer_id)
+        .select(diesel::dsl::max(outbox_dsl::seq))
+        .first::<Option<i64>>(&mut conn)
+        .await?;
+    Ok(seq.unwrap_or(0))
+}
+
+// Up to `REPLAY_PAGE` status changes to the customer's orders that committed after
+// `after_seq`, oldest first; a full page means there may be more after its last `seq`
+pub async fn status_changes_since(
+    pool: &PgPool,
+    customer_id: Uuid,
+    after_seq: i64,
+) -> Result<Vec<EventEnvelope>, CustomError> {
+    let mut conn = connection(pool).await?;
+    let rows = status_changes_of(customer_id)
+        .filter(outbox_dsl::seq.gt(after_seq))
+        .order(outbox_dsl::seq.asc())
+        .limit(REPLAY_PAGE)
+        .load::<OutboxEvent>(&mut conn)
+        .await?;
+    rows.iter()
+        .map(|row| {
+            EventEnvelope::from_row(row).map_err(|err| {
+                CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string()))
+            })
+        })
+        .collect()
+}
+
+fn status_changes_of(customer_id: Uuid) -> outbox_events::BoxedQuery<'static, Pg> {
+    let customer_orders = order_dsl::orders
+        .filter(order_dsl::customer_id.eq(customer_id))
+        .select(order_dsl::id);
+    outbox_dsl::outbox_events
+        .filter(outbox_dsl::event_type.eq("OrderStatusChanged"))
+        .filter(outbox_dsl::aggregate_id.eq_any(customer_orders))
+        .into_boxed()
+}
+
+async fn connection(pool: &PgPool) -> Result<Object<AsyncPgConnection>, CustomError> {
+    pool.get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))
+}
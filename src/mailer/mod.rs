@@ -0,0 +1,153 @@
+pub mod notifications;
+pub mod templates;
+
+use crate::config::configuration::{EmailSettings, MailerBackend};
+use async_trait::async_trait;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/******************************************/
+// Mailer trait
+/******************************************/
+// Sends are made from background jobs, so failures are plain strings that end up in
+// `jobs.last_error` like any other job failure.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+pub fn from_settings(settings: &EmailSettings) -> Result<Arc<dyn Mailer>, std::io::Error> {
+    let from = settings.from.parse::<Mailbox>().map_err(|err| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid email.from address: {}", err),
+        )
+    })?;
+    let mailer: Arc<dyn Mailer> = match settings.backend {
+        MailerBackend::Smtp => Arc::new(SmtpMailer::new(settings, from)?),
+        MailerBackend::File => Arc::new(FileMailer::new(&settings.file_dir, from)?),
+        MailerBackend::Memory => Arc::new(InMemoryMailer::default()),
+    };
+    Ok(mailer)
+}
+
+fn build_message(from: &Mailbox, email: &Email) -> Result<Message, String> {
+    let to = email
+        .to
+        .parse::<Mailbox>()
+        .map_err(|err| format!("Invalid recipient {}: {}", email.to, err))?;
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject.clone())
+        .multipart(MultiPart::alternative_plain_html(
+            email.text.clone(),
+            email.html.clone(),
+        ))
+        .map_err(|err| err.to_string())
+}
+
+/******************************************/
+// SMTP
+/******************************************/
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(settings: &EmailSettings, from: Mailbox) -> Result<Self, std::io::Error> {
+        let smtp = &settings.smtp;
+        let mut builder = if smtp.starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host).map_err(|err| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, err.to_string())
+            })?
+        } else {
+            // Plaintext, for local catchers such as MailHog
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host)
+        }
+        .port(smtp.port);
+        if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(Self {
+            from,
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let message = build_message(&self.from, email)?;
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+/******************************************/
+// Files, for development
+/******************************************/
+// Writes each message as an `.eml` file that any mail client can open
+pub struct FileMailer {
+    from: Mailbox,
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str, from: Mailbox) -> Result<Self, std::io::Error> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            from,
+            transport: AsyncFileTransport::new(dir),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let message = build_message(&self.from, email)?;
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+/******************************************/
+// In memory, for tests
+/******************************************/
+#[derive(Default)]
+pub struct InMemoryMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl InMemoryMailer {
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        self.sent.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
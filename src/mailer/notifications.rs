@@ -0,0 +1,168 @@
+use super::templates::Template;
+use super::Mailer;
+use crate::db::PgPool;
+use crate::jobs::{self, Job, JobHandler};
+use crate::outbox::dispatcher::EventSink;
+use crate::outbox::{DomainEvent, EventEnvelope};
+use crate::repository::Repositories;
+use crate::routes::order::order::OrderStatus;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/******************************************/
+// Customer notifications
+/******************************************/
+// Registration, order placement and shipping each record an outbox event; this turns
+// them into email jobs so the requests that caused them never wait on SMTP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Notification {
+    Welcome { customer_id: Uuid },
+    OrderConfirmation { order_id: Uuid },
+    OrderShipped { order_id: Uuid },
+}
+
+impl Job for Notification {
+    const JOB_TYPE: &'static str = "email.notification";
+}
+
+impl Notification {
+    pub fn for_event(event: &DomainEvent) -> Option<Self> {
+        match event {
+            DomainEvent::CustomerRegistered { customer_id, .. } => Some(Notification::Welcome {
+                customer_id: *customer_id,
+            }),
+            DomainEvent::OrderPlaced { order_id, .. } => Some(Notification::OrderConfirmation {
+                order_id: *order_id,
+            }),
+            DomainEvent::OrderStatusChanged {
+                order_id,
+                to: OrderStatus::Shipped,
+                ..
+            } => Some(Notification::OrderShipped {
+                order_id: *order_id,
+            }),
+            DomainEvent::OrderStatusChanged { .. }
+            | DomainEvent::ReturnRequested { .. }
+            | DomainEvent::ReturnStatusChanged { .. }
+            | DomainEvent::RefundCreated { .. }
+            | DomainEvent::PaymentStatusChanged { .. } => None,
+        }
+    }
+}
+
+// Outbox sink; keyed on the event id so a redelivered event never mails twice
+pub struct NotificationSink {
+    pool: PgPool,
+}
+
+impl NotificationSink {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSink for NotificationSink {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn deliver(&self, event: &EventEnvelope) -> Result<(), String> {
+        let Some(notification) = Notification::for_event(&event.event) else {
+            return Ok(());
+        };
+        jobs::enqueue_unique(&self.pool, &notification, &format!("email:{}", event.id))
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+/******************************************/
+// Rendering and sending
+/******************************************/
+pub struct NotificationHandler {
+    repositories: Repositories,
+    mailer: Arc<dyn Mailer>,
+}
+
+impl NotificationHandler {
+    pub fn new(repositories: Repositories, mailer: Arc<dyn Mailer>) -> Self {
+        Self {
+            repositories,
+            mailer,
+        }
+    }
+
+    async fn order_email(&self, template: Template, order_id: Uuid) -> Result<(), String> {
+        let Some(order) = self
+            .repositories
+            .orders
+            .find(order_id)
+            .await
+            .map_err(|err| err.to_string())?
+        else {
+            return Err(format!("Order {} not found", order_id));
+        };
+        let Some(customer) = self
+            .repositories
+            .customers
+            .find(order.customer_id)
+            .await
+            .map_err(|err| err.to_string())?
+        else {
+            return Err(format!("Customer {} not found", order.customer_id));
+        };
+        let product_name = self
+            .repositories
+            .products
+            .find(order.product_id)
+            .await
+            .map_err(|err| err.to_string())?
+            .map(|product| product.name)
+            .unwrap_or_else(|| "your item".to_string());
+
+        let order_id = order.id.to_string();
+        let email = template.render(
+            &customer.email,
+            &[
+                ("username", &customer.username),
+                ("order_id", &order_id),
+                ("product_name", &product_name),
+            ],
+        );
+        self.mailer.send(&email).await
+    }
+}
+
+#[async_trait]
+impl JobHandler<Notification> for NotificationHandler {
+    async fn handle(&self, notification: Notification) -> Result<(), String> {
+        match notification {
+            Notification::Welcome { customer_id } => {
+                let Some(customer) = self
+                    .repositories
+                    .customers
+                    .find(customer_id)
+                    .await
+                    .map_err(|err| err.to_string())?
+                else {
+                    return Err(format!("Customer {} not found", customer_id));
+                };
+                let email =
+                    Template::Welcome.render(&customer.email, &[("username", &customer.username)]);
+                self.mailer.send(&email).await
+            }
+            Notification::OrderConfirmation { order_id } => {
+                self.order_email(Template::OrderConfirmation, order_id)
+                    .await
+            }
+            Notification::OrderShipped { order_id } => {
+                self.order_email(Template::OrderShipped, order_id).await
+            }
+        }
+    }
+}
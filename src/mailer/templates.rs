@@ -0,0 +1,159 @@
+use super::Email;
+
+/******************************************/
+// Email templates
+/******************************************/
+// Bodies live in `templates/email/` and are compiled in. Placeholders are written
+// `{{name}}`; values are HTML-escaped in the HTML body and inserted as-is in the text body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    Welcome,
+    OrderConfirmation,
+    OrderShipped,
+}
+
+impl Template {
+    fn subject(&self) -> &'static str {
+        match self {
+            Template::Welcome => "Welcome to ecommerce, {{username}}",
+            Template::OrderConfirmation => "Order {{order_id}} confirmed",
+            Template::OrderShipped => "Order {{order_id}} has shipped",
+        }
+    }
+
+    fn html(&self) -> &'static str {
+        match self {
+            Template::Welcome => include_str!("../../templates/email/welcome.html"),
+            Template::OrderConfirmation => {
+                include_str!("../../templates/email/order_confirmation.html")
+            }
+            Template::OrderShipped => include_str!("../../templates/email/order_shipped.html"),
+        }
+    }
+
+    fn text(&self) -> &'static str {
+        match self {
+            Template::Welcome => include_str!("../../templates/email/welcome.txt"),
+            Template::OrderConfirmation => {
+                include_str!("../../templates/email/order_confirmation.txt")
+            }
+            Template::OrderShipped => include_str!("../../templates/email/order_shipped.txt"),
+        }
+    }
+
+    pub fn render(&self, to: &str, values: &[(&str, &str)]) -> Email {
+        Email {
+            to: to.to_string(),
+            subject: substitute(self.subject(), values, false),
+            html: substitute(self.html(), values, true),
+            text: substitute(self.text(), values, false),
+        }
+    }
+}
+
+// One pass over the template, so placeholders inside values are never expanded; unknown
+// names are left as they are
+fn substitute(template: &str, values: &[(&str, &str)], escape: bool) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &after[..end];
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) if escape => rendered.push_str(&escape_html(value)),
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Template;
+
+    const ORDER_VALUES: [(&str, &str); 3] = [
+        ("username", "ada"),
+        ("order_id", "5fcd7d83"),
+        ("product_name", "Laptop"),
+    ];
+
+    #[test]
+    fn every_placeholder_is_filled() {
+        for template in [
+            Template::Welcome,
+            Template::OrderConfirmation,
+            Template::OrderShipped,
+        ] {
+            let email = template.render("ada@example.com", &ORDER_VALUES);
+            assert_eq!(email.to, "ada@example.com");
+            for part in [&email.subject, &email.html, &email.text] {
+                assert!(!part.contains("{{"), "{:?} left a placeholder", template);
+            }
+        }
+    }
+
+    #[test]
+    fn values_are_escaped_in_html_only() {
+        let email = Template::OrderShipped.render(
+            "ada@example.com",
+            &[
+                ("username", "<b>ada</b>"),
+                ("order_id", "5fcd7d83"),
+                ("product_name", "Salt & \"Pepper\""),
+            ],
+        );
+        assert!(email.html.contains("&lt;b&gt;ada&lt;/b&gt;"));
+        assert!(email.html.contains("Salt &amp; &quot;Pepper&quot;"));
+        assert!(email.text.contains("<b>ada</b>"));
+        assert!(email.text.contains("Salt & \"Pepper\""));
+        assert_eq!(email.subject, "Order 5fcd7d83 has shipped");
+    }
+
+    #[test]
+    fn placeholders_inside_values_are_not_expanded() {
+        let email = Template::OrderShipped.render(
+            "ada@example.com",
+            &[
+                ("username", "{{order_id}} {{product_name}}"),
+                ("order_id", "5fcd7d83"),
+                ("product_name", "Laptop"),
+            ],
+        );
+        for part in [&email.html, &email.text] {
+            assert!(part.contains("{{order_id}} {{product_name}}"));
+        }
+        assert_eq!(email.subject, "Order 5fcd7d83 has shipped");
+    }
+
+    #[test]
+    fn unknown_and_unclosed_placeholders_are_kept() {
+        let missing = Template::Welcome.render("ada@example.com", &[]);
+        assert_eq!(missing.subject, "Welcome to ecommerce, {{username}}");
+        let unclosed = Template::Welcome.render("ada@example.com", &[("username", "ada {{")]);
+        assert_eq!(unclosed.subject, "Welcome to ecommerce, ada {{");
+        assert!(unclosed.html.contains("Welcome, ada {{!"));
+    }
+}
@@ -1,18 +1,246 @@
-use ecommerce::config::configuration;
+use clap::{Parser, Subcommand, ValueEnum};
+use ecommerce::config::configuration::{self, Settings};
 use ecommerce::db::establish_connection;
+use ecommerce::errors::custom::CustomError;
+use ecommerce::migrate::{migration_status, revert_last_migration, run_migrations};
+use ecommerce::operator::{self, AccountKind};
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use ecommerce::routes::products::seed::{parse_fixtures, seed_products};
 use ecommerce::startup::Application;
-use ecommerce::telemetry::{get_subscriber, init_subscriber};
+use ecommerce::telemetry::{get_subscriber_with_tracer, init_subscriber, init_tracer_provider};
+use opentelemetry::trace::TracerProvider;
+use std::io::BufRead;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(
+    name = "ecommerce",
+    about = "Ecommerce API server and operator tooling"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the HTTP server (default when no subcommand is given)
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// Manage database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Upsert products from a TOML, JSON or CSV fixture file
+    Seed {
+        #[arg(long, default_value = "fixtures/products.toml")]
+        fixture: PathBuf,
+    },
+    /// Create an admin account
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        /// Read from stdin when omitted, so it stays out of shell history
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Set a new password for a customer or admin
+    ResetPassword {
+        #[arg(long, value_enum)]
+        role: Role,
+        #[arg(long)]
+        username: String,
+        /// Read from stdin when omitted, so it stays out of shell history
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Write orders as CSV to a file or stdout
+    ExportOrders {
+        #[arg(long, value_enum)]
+        status: Option<StatusFilter>,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Up,
+    /// Revert the most recently applied migration
+    Down,
+    /// List applied and pending migrations
+    Status,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Role {
+    Customer,
+    Admin,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatusFilter {
+    Pending,
+    Shipped,
+    Delivered,
+    Cancelled,
+}
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let subscriber = get_subscriber("ecommerce".into(), "info".into(), std::io::stdout);
+    let cli = Cli::parse();
+    let config = configuration::Settings::new().expect("Failed to load configurations");
+
+    let tracer_provider = init_tracer_provider("ecommerce", &config.telemetry)
+        .expect("Failed to initialize OTLP exporter");
+    let tracer = tracer_provider
+        .as_ref()
+        .map(|provider| provider.tracer("ecommerce"));
+    let subscriber =
+        get_subscriber_with_tracer("ecommerce".into(), "info".into(), std::io::stdout, tracer);
     init_subscriber(subscriber);
 
-    let config = configuration::Settings::new().expect("Failed to load configurations");
+    let result = match cli.command.unwrap_or(Command::Serve { port: 8080 }) {
+        Command::Serve { port } => serve(config, port).await,
+        Command::Migrate { action } => migrate(action, &config.database.url).await,
+        command => {
+            let pool = establish_connection(&config.database.url).await;
+            run_operator_command(command, &Repositories::postgres(pool)).await
+        }
+    };
+
+    // Flush spans still sitting in the batch exporter
+    opentelemetry::global::shutdown_tracer_provider();
+    result
+}
+
+fn to_io_error(err: CustomError) -> std::io::Error {
+    std::io::Error::other(err.to_string())
+}
+
+async fn serve(config: Settings, port: u16) -> std::io::Result<()> {
+    if config.database.migrate_on_start {
+        let applied = run_migrations(&config.database.url)
+            .await
+            .map_err(to_io_error)?;
+        tracing::info!("Applied {} pending migrations on start", applied.len());
+    }
+
     let pool = establish_connection(&config.database.url).await;
-    let port = 8080;
 
-    let application = Application::build(port, pool, config.redis.uri).await?;
-    application.run_until_stopped().await?;
+    let application = Application::build(port, pool, config).await?;
+    application.run_until_stopped().await
+}
+
+async fn migrate(action: MigrateAction, database_url: &str) -> std::io::Result<()> {
+    match action {
+        MigrateAction::Up => {
+            let applied = run_migrations(database_url).await.map_err(to_io_error)?;
+            if applied.is_empty() {
+                println!("No pending migrations");
+            }
+            for version in applied {
+                println!("Applied {}", version);
+            }
+        }
+        MigrateAction::Down => {
+            let reverted = revert_last_migration(database_url)
+                .await
+                .map_err(to_io_error)?;
+            println!("Reverted {}", reverted);
+        }
+        MigrateAction::Status => {
+            let status = migration_status(database_url).await.map_err(to_io_error)?;
+            for version in status.applied {
+                println!("[applied] {}", version);
+            }
+            for name in status.pending {
+                println!("[pending] {}", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_operator_command(
+    command: Command,
+    repositories: &Repositories,
+) -> std::io::Result<()> {
+    match command {
+        Command::Seed { fixture } => {
+            let fixtures = parse_fixtures(&fixture).map_err(to_io_error)?;
+            let report = seed_products(repositories.products.as_ref(), &fixtures)
+                .await
+                .map_err(to_io_error)?;
+            println!(
+                "Products inserted: {}, updated: {}, skipped: {}",
+                report.inserted, report.updated, report.skipped
+            );
+        }
+        Command::CreateAdmin { username, password } => {
+            let password = password_or_stdin(password)?;
+            let admin_id = operator::create_admin(repositories, username, &password)
+                .await
+                .map_err(to_io_error)?;
+            println!("Created admin {}", admin_id);
+        }
+        Command::ResetPassword {
+            role,
+            username,
+            password,
+        } => {
+            let password = password_or_stdin(password)?;
+            let kind = match role {
+                Role::Customer => AccountKind::Customer,
+                Role::Admin => AccountKind::Admin,
+            };
+            operator::reset_password(repositories, kind, &username, &password)
+                .await
+                .map_err(to_io_error)?;
+            println!("Password updated for {}", username);
+        }
+        Command::ExportOrders { status, output } => {
+            let status = status.map(|status| match status {
+                StatusFilter::Pending => OrderStatus::Pending,
+                StatusFilter::Shipped => OrderStatus::Shipped,
+                StatusFilter::Delivered => OrderStatus::Delivered,
+                StatusFilter::Cancelled => OrderStatus::Cancelled,
+            });
+            let orders = repositories
+                .orders
+                .list(status)
+                .await
+                .map_err(to_io_error)?;
+            match output {
+                Some(path) => {
+                    operator::write_orders_csv(&orders, std::fs::File::create(&path)?)?;
+                    eprintln!("Exported {} orders to {}", orders.len(), path.display());
+                }
+                None => operator::write_orders_csv(&orders, std::io::stdout().lock())?,
+            }
+        }
+        Command::Serve { .. } | Command::Migrate { .. } => unreachable!("handled in main"),
+    }
     Ok(())
 }
+
+fn password_or_stdin(password: Option<String>) -> std::io::Result<String> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Password must not be empty",
+        ));
+    }
+    Ok(password)
+}
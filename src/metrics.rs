@@ -0,0 +1,176 @@
+use crate::db::PgPool;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Instant;
+
+/******************************************/
+// Application metrics
+/******************************************/
+// Counters are always recorded; `/metrics` and the request middleware are only
+// mounted when `metrics.enabled` is set in the configuration.
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests_total: IntCounterVec,
+    pub http_request_duration_seconds: HistogramVec,
+    pub db_pool_max_size: IntGauge,
+    pub db_pool_size: IntGauge,
+    pub db_pool_available: IntGauge,
+    pub db_pool_waiting: IntGauge,
+    pub logins_total: IntCounterVec,
+    pub orders_created_total: IntCounter,
+    pub order_status_transitions_total: IntCounterVec,
+}
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("ecommerce".to_string()), None)
+            .expect("Failed to create metrics registry");
+
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .expect("Failed to create http_requests_total");
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route", "status"],
+        )
+        .expect("Failed to create http_request_duration_seconds");
+        let db_pool_max_size = IntGauge::new("db_pool_max_size", "Maximum size of the db pool")
+            .expect("Failed to create db_pool_max_size");
+        let db_pool_size = IntGauge::new("db_pool_size", "Current number of pooled connections")
+            .expect("Failed to create db_pool_size");
+        let db_pool_available = IntGauge::new("db_pool_available", "Idle pooled connections")
+            .expect("Failed to create db_pool_available");
+        let db_pool_waiting = IntGauge::new("db_pool_waiting", "Tasks waiting for a connection")
+            .expect("Failed to create db_pool_waiting");
+        let logins_total = IntCounterVec::new(
+            Opts::new("logins_total", "Login attempts by role and outcome"),
+            &["role", "outcome"],
+        )
+        .expect("Failed to create logins_total");
+        let orders_created_total =
+            IntCounter::new("orders_created_total", "Number of orders created")
+                .expect("Failed to create orders_created_total");
+        let order_status_transitions_total = IntCounterVec::new(
+            Opts::new(
+                "order_status_transitions_total",
+                "Order status changes by target status",
+            ),
+            &["status"],
+        )
+        .expect("Failed to create order_status_transitions_total");
+
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(http_requests_total.clone()),
+            Box::new(http_request_duration_seconds.clone()),
+            Box::new(db_pool_max_size.clone()),
+            Box::new(db_pool_size.clone()),
+            Box::new(db_pool_available.clone()),
+            Box::new(db_pool_waiting.clone()),
+            Box::new(logins_total.clone()),
+            Box::new(orders_created_total.clone()),
+            Box::new(order_status_transitions_total.clone()),
+        ];
+        for collector in collectors {
+            registry
+                .register(collector)
+                .expect("Failed to register metric");
+        }
+
+        Self {
+            registry,
+            http_requests_total,
+            http_request_duration_seconds,
+            db_pool_max_size,
+            db_pool_size,
+            db_pool_available,
+            db_pool_waiting,
+            logins_total,
+            orders_created_total,
+            order_status_transitions_total,
+        }
+    }
+
+    pub fn record_login(&self, role: &str, success: bool) {
+        let outcome = if success { "success" } else { "failure" };
+        self.logins_total.with_label_values(&[role, outcome]).inc();
+    }
+
+    fn observe_pool(&self, pool: &PgPool) {
+        let status = pool.status();
+        self.db_pool_max_size.set(status.max_size as i64);
+        self.db_pool_size.set(status.size as i64);
+        self.db_pool_available.set(status.available as i64);
+        self.db_pool_waiting.set(status.waiting as i64);
+    }
+
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+/******************************************/
+// Request metrics middleware
+/******************************************/
+pub async fn metrics_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    // Use the route template so `/orders/{id}/view` doesn't explode label cardinality
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(res) => res.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    METRICS.http_requests_total.with_label_values(&labels).inc();
+    METRICS
+        .http_request_duration_seconds
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+/******************************************/
+// Prometheus scrape route
+/******************************************/
+/**
+ * @route   GET /metrics
+ * @access  Public (enabled through `metrics.enabled`)
+ */
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses((status = 200, description = "Prometheus text exposition", body = String, content_type = "text/plain"))
+)]
+pub async fn metrics_endpoint(pool: web::Data<PgPool>) -> HttpResponse {
+    METRICS.observe_pool(&pool);
+    match METRICS.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(body),
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    }
+}
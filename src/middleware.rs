@@ -1,12 +1,15 @@
 use crate::auth_jwt::auth::verify_jwt;
-use actix_web::body::MessageBody;
+use crate::routes::legacy::find_legacy_route;
+use actix_web::body::{BoxBody, MessageBody};
 use actix_web::dev::{ServiceRequest, ServiceResponse};
-use actix_web::error::ErrorUnauthorized;
+use actix_web::error::{ErrorUnauthorized, InternalError};
+use actix_web::http::header::{HeaderName, HeaderValue, LINK};
+use actix_web::middleware::Next;
 use actix_web::{Error, HttpMessage};
-use actix_web_lab::middleware::Next;
+use uuid::Uuid;
 
 pub async fn jwt_auth_middleware(
-    mut req: ServiceRequest,
+    req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, Error> {
     let token = req.headers().get("Authorization");
@@ -22,6 +25,130 @@ pub async fn jwt_auth_middleware(
             req.extensions_mut().insert(claims);
             next.call(req).await
         }
-        Err(_) => return Err(ErrorUnauthorized("Invalid token")),
+        Err(_) => Err(ErrorUnauthorized("Invalid token")),
     }
 }
+
+/******************************************/
+// Request id propagation
+/******************************************/
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Debug, Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    // Accept ids from upstream proxies/clients only if they look sane, otherwise mint one
+    fn from_header(value: Option<&HeaderValue>) -> Self {
+        let incoming = value
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= 128
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            });
+        match incoming {
+            Some(id) => Self(id.to_string()),
+            None => Self(Uuid::new_v4().to_string()),
+        }
+    }
+}
+
+impl AsRef<str> for RequestId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+// Request id of the request being served on this task, used when rendering `CustomError`
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.to_string()).ok()
+}
+
+pub async fn request_id_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let request_id = RequestId::from_header(req.headers().get(REQUEST_ID_HEADER));
+    req.extensions_mut().insert(request_id.clone());
+    let header = HeaderValue::from_str(request_id.as_ref()).ok();
+
+    // Holding a clone of the request while it is routed makes actix panic, so errors raised
+    // by inner middleware (e.g. a missing JWT) are rendered here rather than turned into a
+    // `ServiceResponse`; rendering inside the scope also puts the id in the error body
+    let result = CURRENT_REQUEST_ID
+        .scope(request_id.clone(), async move {
+            next.call(req).await.map_err(|err| {
+                let response = err.error_response();
+                (err, response)
+            })
+        })
+        .await;
+    match result {
+        Ok(mut res) => {
+            if let Some(value) = header {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(res.map_into_boxed_body())
+        }
+        Err((err, mut response)) => {
+            if let Some(value) = header {
+                response
+                    .headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Err(InternalError::from_response(err, response).into())
+        }
+    }
+}
+
+/******************************************/
+// Deprecation headers for legacy routes
+/******************************************/
+pub async fn deprecation_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let legacy_route = req
+        .match_pattern()
+        .and_then(|pattern| find_legacy_route(req.method().as_str(), &pattern));
+
+    let mut res = next.call(req).await?;
+    if let Some(route) = legacy_route {
+        let successor = res
+            .request()
+            .match_info()
+            .iter()
+            .fold(route.successor.to_string(), |successor, (name, value)| {
+                successor.replace(&format!("{{{}}}", name), value)
+            });
+        res.headers_mut().insert(
+            HeaderName::from_static("deprecation"),
+            HeaderValue::from_static("true"),
+        );
+        // A successor still holding a template needs a parameter the legacy path doesn't
+        // carry (e.g. an id sent in the body), so there is no URL to link to
+        if !successor.contains('{') {
+            if let Ok(link) =
+                HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor))
+            {
+                res.headers_mut().insert(LINK, link);
+            }
+        }
+    }
+    Ok(res)
+}
@@ -0,0 +1,98 @@
+use crate::errors::custom::{CustomError, DbError};
+use crate::telemetry::spawn_blocking_with_tracing;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use tracing::instrument;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+// Arbitrary key shared by every instance so only one of them migrates at a time
+const MIGRATION_LOCK_KEY: i64 = 0x6563_6f6d_6d65_7263;
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub applied: Vec<String>,
+    pub pending: Vec<String>,
+}
+
+/******************************************/
+// Applying pending migrations
+/******************************************/
+#[instrument(name = "Run pending migrations", skip(database_url))]
+pub async fn run_migrations(database_url: &str) -> Result<Vec<String>, CustomError> {
+    with_migration_lock(database_url, |conn| {
+        let applied = conn
+            .run_pending_migrations(MIGRATIONS)
+            .map_err(|err| DbError::MigrationError(err.to_string()))?;
+        Ok(applied.iter().map(|version| version.to_string()).collect())
+    })
+    .await
+}
+
+/******************************************/
+// Reverting the latest migration
+/******************************************/
+#[instrument(name = "Revert last migration", skip(database_url))]
+pub async fn revert_last_migration(database_url: &str) -> Result<String, CustomError> {
+    with_migration_lock(database_url, |conn| {
+        let reverted = conn
+            .revert_last_migration(MIGRATIONS)
+            .map_err(|err| DbError::MigrationError(err.to_string()))?;
+        Ok(reverted.to_string())
+    })
+    .await
+}
+
+/******************************************/
+// Listing applied and pending migrations
+/******************************************/
+#[instrument(name = "Migration status", skip(database_url))]
+pub async fn migration_status(database_url: &str) -> Result<MigrationStatus, CustomError> {
+    with_migration_lock(database_url, |conn| {
+        let applied = conn
+            .applied_migrations()
+            .map_err(|err| DbError::MigrationError(err.to_string()))?;
+        let pending = conn
+            .pending_migrations(MIGRATIONS)
+            .map_err(|err| DbError::MigrationError(err.to_string()))?;
+        Ok(MigrationStatus {
+            applied: applied.iter().map(|version| version.to_string()).collect(),
+            pending: pending
+                .iter()
+                .map(|migration| migration.name().to_string())
+                .collect(),
+        })
+    })
+    .await
+}
+
+// Migrations use a blocking connection, so run them off the async runtime while holding a
+// session-level advisory lock; concurrent instances wait here instead of racing.
+async fn with_migration_lock<F, T>(database_url: &str, f: F) -> Result<T, CustomError>
+where
+    F: FnOnce(&mut PgConnection) -> Result<T, DbError> + Send + 'static,
+    T: Send + 'static,
+{
+    let database_url = database_url.to_string();
+    spawn_blocking_with_tracing(move || {
+        let mut conn = PgConnection::establish(&database_url)
+            .map_err(|err| DbError::ConnectionError(err.to_string()))?;
+        sql_query("SELECT pg_advisory_lock($1)")
+            .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+            .execute(&mut conn)
+            .map_err(|err| DbError::MigrationError(err.to_string()))?;
+
+        let result = f(&mut conn);
+
+        sql_query("SELECT pg_advisory_unlock($1)")
+            .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+            .execute(&mut conn)
+            .map_err(|err| DbError::MigrationError(err.to_string()))?;
+        result
+    })
+    .await
+    .map_err(|err| CustomError::BlockingError(err.to_string()))?
+    .map_err(CustomError::DatabaseError)
+}
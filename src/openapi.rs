@@ -0,0 +1,219 @@
+use crate::db_models::{BackgroundJob, Payment, Refund, WebhookDelivery, WebhookEndpoint};
+use crate::errors::custom::ErrorBody;
+use crate::jobs::JobStatus;
+use crate::payments::webhook::{PaymentEventType, PaymentWebhookEvent, WebhookOutcome};
+use crate::payments::PaymentStatus;
+use crate::repository::details::{
+    CustomerSummary, OrderCancellation, OrderDetail, OrderTotals, ProductSummary,
+};
+use crate::repository::pagination::{OrderPage, OrderSort};
+use crate::returns::{
+    AuditEntry, ReturnAction, ReturnDetail, ReturnStatus, ReturnedItem, TimelineEntry, TimelineKind,
+};
+use crate::routes::admin::admin::{
+    CreateAdminBody, LoginAdminBody, OrderStatusBody, UpdateStatusBody,
+};
+use crate::routes::admin::live_orders::{ClientMessage, FeedMessage};
+use crate::routes::admin::payments::RefundPaymentBody;
+use crate::routes::admin::returns::ReturnActionBody;
+use crate::routes::admin::webhooks::RegisterWebhookBody;
+use crate::routes::customer::customer::{
+    CreateCustomerBody, LoginCustomerBody, UpdateCustomerBody,
+};
+use crate::routes::health_check::{DependencyChecks, DependencyStatus, ReadinessReport};
+use crate::routes::legacy::LEGACY_ROUTES;
+use crate::routes::order::events::OrderStatusEvent;
+use crate::routes::order::order::{CancelOrderBody, CreateOrder, OrderStatus};
+use crate::routes::order::payments::PayOrderBody;
+use crate::routes::order::returns::RequestReturnBody;
+use crate::routes::payments::WebhookReceipt;
+use crate::webhooks::DeliveryStatus;
+use utoipa::openapi::path::PathItemType;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::Deprecated;
+use utoipa::{Modify, OpenApi};
+
+/******************************************/
+// OpenAPI document
+/******************************************/
+// Served at `/openapi.json` with Swagger UI under `/swagger-ui/`.
+// Every route in `routes::table` must be listed in `paths` (see tests/api/openapi.rs).
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ecommerce", description = "Customer, admin and order API. Authenticated POST, PUT, PATCH and DELETE requests may carry an `Idempotency-Key` header; a retry with the same key and body gets the original response back."),
+    paths(
+        crate::routes::customer::customer::register_customer,
+        crate::routes::customer::customer::login_customer,
+        crate::routes::customer::customer::logout_customer,
+        crate::routes::customer::customer::update_customer,
+        crate::routes::customer::customer::view_customer,
+        crate::routes::admin::admin::register_admin,
+        crate::routes::admin::admin::login_admin,
+        crate::routes::admin::admin::logout_admin,
+        crate::routes::admin::admin::update_order_status,
+        crate::routes::admin::admin::update_status,
+        crate::routes::admin::admin::fetch_all_orders,
+        crate::routes::admin::admin::fetch_order,
+        crate::routes::admin::admin::admin_cancel_order,
+        crate::routes::admin::live_orders::live_orders,
+        crate::routes::admin::returns::list_returns,
+        crate::routes::admin::returns::get_return,
+        crate::routes::admin::returns::update_return,
+        crate::routes::admin::returns::admin_order_timeline,
+        crate::routes::admin::payments::admin_order_payments,
+        crate::routes::admin::payments::capture_payment,
+        crate::routes::admin::payments::void_payment,
+        crate::routes::admin::payments::refund_payment,
+        crate::routes::admin::jobs::list_jobs,
+        crate::routes::admin::jobs::get_job,
+        crate::routes::admin::jobs::requeue_job,
+        crate::routes::admin::webhooks::register_webhook,
+        crate::routes::admin::webhooks::list_webhooks,
+        crate::routes::admin::webhooks::delete_webhook,
+        crate::routes::admin::webhooks::list_webhook_deliveries,
+        crate::routes::order::order::create_order,
+        crate::routes::order::order::get_order,
+        crate::routes::order::order::list_orders,
+        crate::routes::order::order::cancel_order,
+        crate::routes::order::returns::request_return,
+        crate::routes::order::returns::list_order_returns,
+        crate::routes::order::returns::order_timeline,
+        crate::routes::order::payments::pay_for_order,
+        crate::routes::order::payments::list_order_payments,
+        crate::routes::order::events::order_events,
+        crate::routes::payments::payment_webhook,
+        crate::routes::health_check::health_check,
+        crate::routes::health_check::readiness_check,
+        crate::metrics::metrics_endpoint,
+    ),
+    components(schemas(
+        CreateCustomerBody,
+        UpdateCustomerBody,
+        LoginCustomerBody,
+        CreateAdminBody,
+        LoginAdminBody,
+        UpdateStatusBody,
+        OrderStatusBody,
+        CreateOrder,
+        CancelOrderBody,
+        OrderStatus,
+        OrderStatusEvent,
+        OrderDetail,
+        ProductSummary,
+        CustomerSummary,
+        OrderTotals,
+        OrderCancellation,
+        OrderPage,
+        OrderSort,
+        BackgroundJob,
+        JobStatus,
+        RequestReturnBody,
+        ReturnActionBody,
+        ReturnAction,
+        ReturnStatus,
+        ReturnedItem,
+        ReturnDetail,
+        Refund,
+        TimelineEntry,
+        TimelineKind,
+        AuditEntry,
+        PayOrderBody,
+        RefundPaymentBody,
+        Payment,
+        PaymentStatus,
+        PaymentWebhookEvent,
+        PaymentEventType,
+        WebhookOutcome,
+        WebhookReceipt,
+        ClientMessage,
+        FeedMessage,
+        RegisterWebhookBody,
+        WebhookEndpoint,
+        WebhookDelivery,
+        DeliveryStatus,
+        ReadinessReport,
+        DependencyChecks,
+        DependencyStatus,
+        ErrorBody,
+    )),
+    modifiers(&BearerAuth, &LegacyAliases),
+    tags(
+        (name = "customer", description = "Customer accounts"),
+        (name = "admin", description = "Admin accounts, order, payment and return management, background jobs and webhooks"),
+        (name = "order", description = "Customer orders"),
+        (name = "payments", description = "Callbacks from the payment provider"),
+        (name = "health", description = "Probes and metrics"),
+    )
+)]
+pub struct ApiDoc;
+
+// Registers the JWT scheme referenced by `security(("bearer_auth" = []))`
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+// Documents every `routes::legacy` alias as a deprecated copy of its successor, unless the
+// alias has its own annotated handler (e.g. `update_status`, whose body differs).
+struct LegacyAliases;
+
+impl Modify for LegacyAliases {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for route in LEGACY_ROUTES {
+            let method = path_item_type(route.method);
+            let documented = openapi
+                .paths
+                .paths
+                .get(route.path)
+                .and_then(|item| item.operations.get(&method))
+                .cloned();
+            let successor = openapi
+                .paths
+                .paths
+                .get(route.successor)
+                .and_then(|item| item.operations.get(&path_item_type(route.successor_method)))
+                .cloned();
+
+            let Some(mut operation) = documented.or(successor) else {
+                continue;
+            };
+            operation.deprecated = Some(Deprecated::True);
+            operation.operation_id = operation
+                .operation_id
+                .map(|operation_id| format!("{}_legacy", operation_id));
+            operation.description = Some(format!(
+                "Deprecated alias of `{} {}`.",
+                route.successor_method, route.successor
+            ));
+            openapi
+                .paths
+                .paths
+                .entry(route.path.to_string())
+                .or_default()
+                .operations
+                .insert(method, operation);
+        }
+    }
+}
+
+fn path_item_type(method: &str) -> PathItemType {
+    match method {
+        "POST" => PathItemType::Post,
+        "PUT" => PathItemType::Put,
+        "PATCH" => PathItemType::Patch,
+        "DELETE" => PathItemType::Delete,
+        _ => PathItemType::Get,
+    }
+}
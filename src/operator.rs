@@ -0,0 +1,101 @@
+use crate::db_models::{Admin, Order};
+use crate::errors::custom::{CustomError, DbError};
+use crate::repository::Repositories;
+use crate::validations::name_email::UserName;
+use argon2::{self, password_hash::SaltString, Argon2, PasswordHasher};
+use std::io::Write;
+use tracing::instrument;
+use uuid::Uuid;
+
+// Operator tasks behind the `ecommerce` subcommands; they share the server's repositories
+#[derive(Debug, Clone, Copy)]
+pub enum AccountKind {
+    Customer,
+    Admin,
+}
+
+fn hash_password(password: &str) -> Result<String, CustomError> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hashed = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| CustomError::HashingError(err.to_string()))?;
+    Ok(password_hashed.to_string())
+}
+
+/******************************************/
+// Creating an admin account
+/******************************************/
+#[instrument(name = "Create admin from CLI", skip(repositories, password))]
+pub async fn create_admin(
+    repositories: &Repositories,
+    username: String,
+    password: &str,
+) -> Result<Uuid, CustomError> {
+    let validated_name =
+        UserName::parse(username).map_err(|err| CustomError::ValidationError(err.to_string()))?;
+    let admin_id = Uuid::new_v4();
+    repositories
+        .admins
+        .insert(Admin {
+            id: admin_id,
+            username: validated_name.as_ref().to_string(),
+            password_hash: hash_password(password)?,
+        })
+        .await?;
+    Ok(admin_id)
+}
+
+/******************************************/
+// Resetting a customer or admin password
+/******************************************/
+#[instrument(name = "Reset password from CLI", skip(repositories, password))]
+pub async fn reset_password(
+    repositories: &Repositories,
+    kind: AccountKind,
+    username: &str,
+    password: &str,
+) -> Result<(), CustomError> {
+    let password_hashed = hash_password(password)?;
+    let updated = match kind {
+        AccountKind::Customer => {
+            repositories
+                .customers
+                .update_password_hash(username, &password_hashed)
+                .await?
+        }
+        AccountKind::Admin => {
+            repositories
+                .admins
+                .update_password_hash(username, &password_hashed)
+                .await?
+        }
+    };
+
+    if !updated {
+        return Err(CustomError::DatabaseError(DbError::UpdationError(format!(
+            "No {:?} account named {}",
+            kind, username
+        ))));
+    }
+    Ok(())
+}
+
+/******************************************/
+// Exporting orders
+/******************************************/
+// Every column is a uuid, enum or timestamp, so no field ever needs CSV quoting
+pub fn write_orders_csv<W: Write>(orders: &[Order], mut writer: W) -> std::io::Result<()> {
+    writeln!(writer, "id,customer_id,product_id,status,created_at")?;
+    for order in orders {
+        writeln!(
+            writer,
+            "{},{},{},{:?},{}",
+            order.id,
+            order.customer_id,
+            order.product_id,
+            order.status,
+            order.created_at.format("%Y-%m-%dT%H:%M:%S%.f")
+        )?;
+    }
+    writer.flush()
+}
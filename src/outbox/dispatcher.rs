@@ -0,0 +1,163 @@
+use super::EventEnvelope;
+use crate::config::configuration::OutboxSettings;
+use crate::db::PgPool;
+use crate::db_models::OutboxEvent;
+use crate::errors::custom::{CustomError, DbError};
+use crate::jobs::worker::backoff_delay;
+use crate::schema::outbox_events::dsl as outbox_dsl;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+// How long a claimed batch is left to one dispatcher before others may pick it up again
+const CLAIM_TIMEOUT: chrono::Duration = chrono::Duration::minutes(5);
+
+/******************************************/
+// Sinks
+/******************************************/
+// Where dispatched events go. Delivery is at-least-once: an event is retried for every
+// sink whenever any sink fails, so implementations should deduplicate on `EventEnvelope::id`.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn deliver(&self, event: &EventEnvelope) -> Result<(), String>;
+}
+
+// Writes every event to the application log
+pub struct LogSink;
+
+#[async_trait]
+impl EventSink for LogSink {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn deliver(&self, event: &EventEnvelope) -> Result<(), String> {
+        tracing::info!(
+            event_id = %event.id,
+            event_type = event.event.event_type(),
+            aggregate_id = %event.event.aggregate_id(),
+            "Domain event"
+        );
+        Ok(())
+    }
+}
+
+/******************************************/
+// Dispatcher task
+/******************************************/
+pub fn spawn_dispatcher(
+    pool: PgPool,
+    sinks: Vec<Arc<dyn EventSink>>,
+    settings: &OutboxSettings,
+) -> Option<JoinHandle<()>> {
+    if !settings.enabled {
+        return None;
+    }
+    let poll_interval = Duration::from_millis(settings.poll_interval_ms);
+    let batch_size = settings.batch_size;
+    Some(tokio::spawn(async move {
+        tracing::info!("Outbox dispatcher started with {} sinks", sinks.len());
+        loop {
+            match dispatch_pending(&pool, &sinks, batch_size).await {
+                // A full batch means there is probably more waiting
+                Ok(handled) if handled as i64 >= batch_size => continue,
+                Ok(_) => {}
+                Err(err) => tracing::warn!("Outbox dispatcher failed to poll: {}", err),
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }))
+}
+
+// Delivers up to `batch_size` due events, oldest first, and returns how many were attempted.
+// The batch is claimed by pushing `next_attempt_at` past `CLAIM_TIMEOUT` in a short
+// transaction, so concurrent dispatchers skip it without a transaction being held open
+// while the sinks run; a dispatcher that dies mid-batch leaves its events due again later.
+pub async fn dispatch_pending(
+    pool: &PgPool,
+    sinks: &[Arc<dyn EventSink>],
+    batch_size: i64,
+) -> Result<usize, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    let events = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                let now = chrono::Utc::now().naive_utc();
+                let events = outbox_dsl::outbox_events
+                    .filter(outbox_dsl::dispatched_at.is_null())
+                    .filter(outbox_dsl::next_attempt_at.le(now))
+                    .order(outbox_dsl::seq.asc())
+                    .limit(batch_size)
+                    .for_update()
+                    .skip_locked()
+                    .load::<OutboxEvent>(conn)
+                    .await?;
+                let ids: Vec<Uuid> = events.iter().map(|event| event.id).collect();
+                diesel::update(outbox_dsl::outbox_events.filter(outbox_dsl::id.eq_any(&ids)))
+                    .set(outbox_dsl::next_attempt_at.eq(now + CLAIM_TIMEOUT))
+                    .execute(conn)
+                    .await?;
+                Ok(events)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    for event in &events {
+        let attempts = event.attempts + 1;
+        let now = chrono::Utc::now().naive_utc();
+        let target = outbox_dsl::outbox_events.find(event.id);
+        match deliver(sinks, event).await {
+            Ok(()) => {
+                diesel::update(target)
+                    .set((
+                        outbox_dsl::dispatched_at.eq(now),
+                        outbox_dsl::attempts.eq(attempts),
+                        outbox_dsl::last_error.eq(None::<String>),
+                    ))
+                    .execute(&mut conn)
+                    .await?;
+            }
+            Err(error) => {
+                tracing::warn!(event_id = %event.id, "Outbox delivery failed: {}", error);
+                let delay = chrono::Duration::from_std(backoff_delay(attempts))
+                    .unwrap_or_else(|_| chrono::Duration::hours(1));
+                diesel::update(target)
+                    .set((
+                        outbox_dsl::attempts.eq(attempts),
+                        outbox_dsl::next_attempt_at.eq(now + delay),
+                        outbox_dsl::last_error.eq(error),
+                    ))
+                    .execute(&mut conn)
+                    .await?;
+            }
+        }
+    }
+    Ok(events.len())
+}
+
+async fn deliver(sinks: &[Arc<dyn EventSink>], event: &OutboxEvent) -> Result<(), String> {
+    let envelope = EventEnvelope::from_row(event)
+        .map_err(|err| format!("Unreadable {} payload: {}", event.event_type, err))?;
+
+    let mut errors = Vec::new();
+    for sink in sinks {
+        if let Err(err) = sink.deliver(&envelope).await {
+            errors.push(format!("{}: {}", sink.name(), err));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
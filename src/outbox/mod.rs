@@ -0,0 +1,204 @@
+pub mod dispatcher;
+
+use crate::db_models::OutboxEvent;
+use crate::payments::PaymentStatus;
+use crate::returns::{ReturnStatus, ReturnedItem};
+use crate::routes::order::order::OrderStatus;
+use crate::schema::outbox_events::dsl as outbox_dsl;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/******************************************/
+// Domain events
+/******************************************/
+// Written to `outbox_events` by the Postgres repositories in the same transaction as the
+// change they describe, then handed to the sinks by `dispatcher`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum DomainEvent {
+    CustomerRegistered {
+        customer_id: Uuid,
+        username: String,
+        email: String,
+    },
+    OrderPlaced {
+        order_id: Uuid,
+        customer_id: Uuid,
+        product_id: Uuid,
+        status: OrderStatus,
+        created_at: NaiveDateTime,
+    },
+    OrderStatusChanged {
+        order_id: Uuid,
+        customer_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+    },
+    ReturnRequested {
+        return_id: Uuid,
+        order_id: Uuid,
+        customer_id: Uuid,
+        items: Vec<ReturnedItem>,
+    },
+    ReturnStatusChanged {
+        return_id: Uuid,
+        order_id: Uuid,
+        customer_id: Uuid,
+        from: ReturnStatus,
+        to: ReturnStatus,
+        admin_id: Uuid,
+        note: Option<String>,
+    },
+    RefundCreated {
+        refund_id: Uuid,
+        return_id: Uuid,
+        order_id: Uuid,
+        customer_id: Uuid,
+        amount: i32,
+    },
+    // `from` is absent for a new authorization; `amount` is what the step moved, e.g. the
+    // part refunded
+    PaymentStatusChanged {
+        payment_id: Uuid,
+        order_id: Uuid,
+        customer_id: Uuid,
+        from: Option<PaymentStatus>,
+        to: PaymentStatus,
+        amount: i32,
+    },
+}
+
+impl DomainEvent {
+    // Every value `event_type` can return, e.g. for validating subscriptions
+    pub const EVENT_TYPES: [&'static str; 7] = [
+        "CustomerRegistered",
+        "OrderPlaced",
+        "OrderStatusChanged",
+        "ReturnRequested",
+        "ReturnStatusChanged",
+        "RefundCreated",
+        "PaymentStatusChanged",
+    ];
+
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            DomainEvent::CustomerRegistered { .. } => "CustomerRegistered",
+            DomainEvent::OrderPlaced { .. } => "OrderPlaced",
+            DomainEvent::OrderStatusChanged { .. } => "OrderStatusChanged",
+            DomainEvent::ReturnRequested { .. } => "ReturnRequested",
+            DomainEvent::ReturnStatusChanged { .. } => "ReturnStatusChanged",
+            DomainEvent::RefundCreated { .. } => "RefundCreated",
+            DomainEvent::PaymentStatusChanged { .. } => "PaymentStatusChanged",
+        }
+    }
+
+    // The customer or order the event is about; return, refund and payment events belong to
+    // their order, which makes them part of its timeline
+    pub fn aggregate_id(&self) -> Uuid {
+        match self {
+            DomainEvent::CustomerRegistered { customer_id, .. } => *customer_id,
+            DomainEvent::OrderPlaced { order_id, .. } => *order_id,
+            DomainEvent::OrderStatusChanged { order_id, .. } => *order_id,
+            DomainEvent::ReturnRequested { order_id, .. } => *order_id,
+            DomainEvent::ReturnStatusChanged { order_id, .. } => *order_id,
+            DomainEvent::RefundCreated { order_id, .. } => *order_id,
+            DomainEvent::PaymentStatusChanged { order_id, .. } => *order_id,
+        }
+    }
+
+    // Whose event it is; every event belongs to exactly one customer
+    pub fn customer_id(&self) -> Uuid {
+        match self {
+            DomainEvent::CustomerRegistered { customer_id, .. }
+            | DomainEvent::OrderPlaced { customer_id, .. }
+            | DomainEvent::OrderStatusChanged { customer_id, .. }
+            | DomainEvent::ReturnRequested { customer_id, .. }
+            | DomainEvent::ReturnStatusChanged { customer_id, .. }
+            | DomainEvent::RefundCreated { customer_id, .. }
+            | DomainEvent::PaymentStatusChanged { customer_id, .. } => *customer_id,
+        }
+    }
+}
+
+// What sinks receive; `id` is stable across redeliveries so consumers can deduplicate, and
+// `seq` orders each customer's events by when they committed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub id: Uuid,
+    pub seq: i64,
+    pub occurred_at: NaiveDateTime,
+    pub event: DomainEvent,
+}
+
+impl EventEnvelope {
+    pub fn from_row(row: &OutboxEvent) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            id: row.id,
+            seq: row.seq,
+            occurred_at: row.created_at,
+            event: serde_json::from_value(row.payload.clone())?,
+        })
+    }
+}
+
+/******************************************/
+// Recording events
+/******************************************/
+// Postgres channel every recorded envelope is sent on once its transaction commits
+pub const NOTIFY_CHANNEL: &str = "domain_events";
+// First key of the transaction-scoped advisory lock taken per customer before numbering
+// an event; the second is a hash of the customer id
+const SEQUENCE_LOCK_SPACE: i32 = 0x6f75_7462;
+
+// Must be called with the connection of the transaction that makes the change
+pub async fn record(
+    conn: &mut AsyncPgConnection,
+    event: &DomainEvent,
+) -> QueryResult<EventEnvelope> {
+    let payload = serde_json::to_value(event)
+        .map_err(|err| diesel::result::Error::SerializationError(Box::new(err)))?;
+    // Held until this transaction ends, so none of the customer's other events is numbered
+    // until this one has committed or rolled back: within a customer `seq` order is commit
+    // order, and replaying their events after a `seq` can't skip one that committed late.
+    // Writes for different customers don't wait on each other.
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+        .bind::<Integer, _>(SEQUENCE_LOCK_SPACE)
+        .bind::<Text, _>(event.customer_id().to_string())
+        .execute(conn)
+        .await?;
+    let id = Uuid::new_v4();
+    let occurred_at = chrono::Utc::now().naive_utc();
+    let seq = diesel::insert_into(outbox_dsl::outbox_events)
+        .values((
+            outbox_dsl::id.eq(id),
+            outbox_dsl::event_type.eq(event.event_type()),
+            outbox_dsl::aggregate_id.eq(event.aggregate_id()),
+            outbox_dsl::payload.eq(payload),
+            outbox_dsl::created_at.eq(occurred_at),
+            outbox_dsl::next_attempt_at.eq(occurred_at),
+        ))
+        .returning(outbox_dsl::seq)
+        .get_result::<i64>(conn)
+        .await?;
+    let envelope = EventEnvelope {
+        id,
+        seq,
+        occurred_at,
+        event: event.clone(),
+    };
+
+    // Held by Postgres until commit and dropped on rollback, so listeners only ever see
+    // events that are durable. Nothing relies on it arriving; the outbox row is the record.
+    let message = serde_json::to_string(&envelope)
+        .map_err(|err| diesel::result::Error::SerializationError(Box::new(err)))?;
+    diesel::sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(NOTIFY_CHANNEL)
+        .bind::<Text, _>(message)
+        .execute(conn)
+        .await?;
+    Ok(envelope)
+}
@@ -0,0 +1,111 @@
+use super::{AuthorizeRequest, PaymentProvider};
+use crate::errors::custom::PaymentError;
+use async_trait::async_trait;
+
+// Payment methods with a fixed outcome; every other method is approved
+pub const DECLINED_METHOD: &str = "tok_decline";
+pub const UNAVAILABLE_METHOD: &str = "tok_unavailable";
+
+const REFERENCE_PREFIX: &str = "mock_";
+
+/******************************************/
+// Mock provider
+/******************************************/
+// Deterministic and offline, for local development and tests: the outcome depends only on
+// the payment method, and references are derived from our payment id.
+#[derive(Debug, Default)]
+pub struct MockPaymentProvider;
+
+impl MockPaymentProvider {
+    fn check_reference(reference: &str) -> Result<(), PaymentError> {
+        if reference.starts_with(REFERENCE_PREFIX) {
+            Ok(())
+        } else {
+            Err(PaymentError::Declined(format!(
+                "Unknown payment reference {}",
+                reference
+            )))
+        }
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for MockPaymentProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn authorize(&self, request: &AuthorizeRequest) -> Result<String, PaymentError> {
+        match request.payment_method.as_str() {
+            DECLINED_METHOD => Err(PaymentError::Declined("Card declined".to_string())),
+            UNAVAILABLE_METHOD => Err(PaymentError::Unavailable(
+                "Mock provider is unavailable".to_string(),
+            )),
+            _ => Ok(format!(
+                "{}{}",
+                REFERENCE_PREFIX,
+                request.payment_id.simple()
+            )),
+        }
+    }
+
+    async fn capture(&self, reference: &str, _amount: i32) -> Result<(), PaymentError> {
+        Self::check_reference(reference)
+    }
+
+    async fn refund(&self, reference: &str, _amount: i32) -> Result<(), PaymentError> {
+        Self::check_reference(reference)
+    }
+
+    async fn void(&self, reference: &str) -> Result<(), PaymentError> {
+        Self::check_reference(reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockPaymentProvider, DECLINED_METHOD, UNAVAILABLE_METHOD};
+    use crate::errors::custom::PaymentError;
+    use crate::payments::{AuthorizeRequest, PaymentProvider};
+    use uuid::Uuid;
+
+    fn request(payment_method: &str) -> AuthorizeRequest {
+        AuthorizeRequest {
+            payment_id: Uuid::parse_str("5fcd7d83-7adf-4d4d-931a-68b9678009db").unwrap(),
+            order_id: Uuid::new_v4(),
+            amount: 50000,
+            payment_method: payment_method.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn approvals_are_referenced_by_payment_id() {
+        let provider = MockPaymentProvider;
+        let reference = provider.authorize(&request("tok_visa")).await.unwrap();
+        assert_eq!(reference, "mock_5fcd7d837adf4d4d931a68b9678009db");
+        assert_eq!(
+            reference,
+            provider.authorize(&request("tok_visa")).await.unwrap()
+        );
+        assert!(provider.capture(&reference, 50000).await.is_ok());
+        assert!(provider.refund(&reference, 100).await.is_ok());
+        assert!(provider.void(&reference).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn special_methods_fail_the_same_way_every_time() {
+        let provider = MockPaymentProvider;
+        assert!(matches!(
+            provider.authorize(&request(DECLINED_METHOD)).await,
+            Err(PaymentError::Declined(_))
+        ));
+        assert!(matches!(
+            provider.authorize(&request(UNAVAILABLE_METHOD)).await,
+            Err(PaymentError::Unavailable(_))
+        ));
+        assert!(matches!(
+            provider.capture("ch_elsewhere", 50000).await,
+            Err(PaymentError::Declined(_))
+        ));
+    }
+}
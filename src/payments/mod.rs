@@ -0,0 +1,521 @@
+pub mod mock;
+pub mod webhook;
+
+use crate::config::configuration::{PaymentBackend, PaymentSettings};
+use crate::db::PgPool;
+use crate::db_models::{Order, Payment};
+use crate::errors::custom::{CustomError, DbError, PaymentError};
+use crate::outbox::{self, DomainEvent};
+use crate::repository::postgres::return_to_stock;
+use crate::routes::order::order::OrderStatus;
+use crate::schema::order_cancellations::dsl as cancellation_dsl;
+use crate::schema::order_totals::dsl as totals_dsl;
+use crate::schema::orders::dsl as order_dsl;
+use crate::schema::payments::dsl as payment_dsl;
+use crate::schema::products::dsl as product_dsl;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// Longest payment method token accepted
+const MAX_METHOD_LENGTH: usize = 255;
+// Cancellation reason recorded when a pending order's payment is refunded in full
+const REFUNDED_REASON: &str = "Payment refunded";
+
+/******************************************/
+// Payment states
+/******************************************/
+// Authorized -> Captured -> PartiallyRefunded -> Refunded; Authorized -> Voided or Failed.
+// Declined authorizations are kept as Failed. An order is fulfillable once captured.
+// Admins move payments through the provider (below); the provider also reports changes made
+// on its side through `webhook`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, diesel_derive_enum::DbEnum, Serialize, Deserialize, ToSchema,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::PaymentStatus"]
+pub enum PaymentStatus {
+    Authorized,
+    Captured,
+    PartiallyRefunded,
+    Refunded,
+    Voided,
+    Failed,
+}
+
+impl PaymentStatus {
+    // Whether the money has been taken and not all of it given back
+    pub fn is_captured(self) -> bool {
+        matches!(
+            self,
+            PaymentStatus::Captured | PaymentStatus::PartiallyRefunded
+        )
+    }
+
+    // Whether money is held or taken, so the order can't be cancelled without settling it
+    pub fn is_live(self) -> bool {
+        self == PaymentStatus::Authorized || self.is_captured()
+    }
+}
+
+/******************************************/
+// Provider trait
+/******************************************/
+pub struct AuthorizeRequest {
+    // Our id for the payment, so providers can deduplicate retried authorizations
+    pub payment_id: Uuid,
+    pub order_id: Uuid,
+    pub amount: i32,
+    // An opaque token from the provider's client-side SDK, never card details
+    pub payment_method: String,
+}
+
+// A payment gateway. `authorize` returns the provider's reference for the authorization,
+// which the other calls act on.
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    // Stored with each payment, so changing provider doesn't orphan older payments
+    fn name(&self) -> &'static str;
+    async fn authorize(&self, request: &AuthorizeRequest) -> Result<String, PaymentError>;
+    async fn capture(&self, reference: &str, amount: i32) -> Result<(), PaymentError>;
+    async fn refund(&self, reference: &str, amount: i32) -> Result<(), PaymentError>;
+    async fn void(&self, reference: &str) -> Result<(), PaymentError>;
+}
+
+pub fn from_settings(settings: &PaymentSettings) -> Arc<dyn PaymentProvider> {
+    match settings.provider {
+        PaymentBackend::Mock => Arc::new(mock::MockPaymentProvider),
+    }
+}
+
+async fn connection(
+    pool: &PgPool,
+) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, CustomError> {
+    pool.get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))
+}
+
+fn reference(payment: &Payment) -> Result<&str, CustomError> {
+    payment.provider_reference.as_deref().ok_or_else(|| {
+        CustomError::ConflictError(format!("Payment {} was never authorized", payment.id))
+    })
+}
+
+/******************************************/
+// Authorizing
+/******************************************/
+// The customer's own pending order, for its grand total; orders without a price snapshot are
+// charged their product's current price. The provider is called
+// outside any transaction; the partial unique index on `payments` stops a concurrent second
+// authorization from being recorded, and an order cancelled meanwhile gets its authorization
+// voided rather than left holding the customer's money.
+pub async fn authorize(
+    pool: &PgPool,
+    provider: &dyn PaymentProvider,
+    customer_id: Uuid,
+    order_id: Uuid,
+    payment_method: &str,
+) -> Result<Payment, CustomError> {
+    let payment_method = payment_method.trim();
+    if payment_method.is_empty() || payment_method.len() > MAX_METHOD_LENGTH {
+        return Err(CustomError::ValidationError(format!(
+            "Payment method must be 1 to {} characters",
+            MAX_METHOD_LENGTH
+        )));
+    }
+
+    let mut conn = connection(pool).await?;
+    let (status, price, grand_total) = order_dsl::orders
+        .inner_join(product_dsl::products)
+        .left_join(totals_dsl::order_totals)
+        .filter(order_dsl::id.eq(order_id))
+        .filter(order_dsl::customer_id.eq(customer_id))
+        .select((
+            order_dsl::status,
+            product_dsl::price,
+            totals_dsl::grand_total.nullable(),
+        ))
+        .first::<(OrderStatus, i32, Option<i32>)>(&mut conn)
+        .await
+        .optional()?
+        .ok_or_else(|| CustomError::NotFoundError(format!("Order {}", order_id)))?;
+    let amount = grand_total.unwrap_or(price);
+    if status != OrderStatus::Pending {
+        return Err(CustomError::ConflictError(format!(
+            "Order {} is {:?}; only pending orders can be paid for",
+            order_id, status
+        )));
+    }
+    let active = payment_dsl::payments
+        .filter(payment_dsl::order_id.eq(order_id))
+        .filter(payment_dsl::status.ne_all(vec![PaymentStatus::Failed, PaymentStatus::Voided]))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await?;
+    if active > 0 {
+        return Err(CustomError::ConflictError(format!(
+            "Order {} is already paid for",
+            order_id
+        )));
+    }
+
+    let payment_id = Uuid::new_v4();
+    let request = AuthorizeRequest {
+        payment_id,
+        order_id,
+        amount,
+        payment_method: payment_method.to_string(),
+    };
+    let outcome = provider.authorize(&request).await;
+    let (provider_reference, status, last_error, failure) = match outcome {
+        Ok(reference) => (Some(reference), PaymentStatus::Authorized, None, None),
+        // Declines are kept so the order shows every attempt
+        Err(PaymentError::Declined(reason)) => (
+            None,
+            PaymentStatus::Failed,
+            Some(reason.clone()),
+            Some(PaymentError::Declined(reason)),
+        ),
+        Err(err) => return Err(err.into()),
+    };
+    let authorized_reference = provider_reference.clone();
+
+    let recorded = conn
+        .transaction::<_, CustomError, _>(|conn| {
+            async move {
+                // Locked so a cancellation either lands first and is seen here, or waits and
+                // then finds the authorization
+                let order_status = order_dsl::orders
+                    .find(order_id)
+                    .select(order_dsl::status)
+                    .for_update()
+                    .first::<OrderStatus>(conn)
+                    .await?;
+                if order_status != OrderStatus::Pending {
+                    return Err(CustomError::ConflictError(format!(
+                        "Order {} is {:?}; only pending orders can be paid for",
+                        order_id, order_status
+                    )));
+                }
+                let now = chrono::Utc::now().naive_utc();
+                let payment = diesel::insert_into(payment_dsl::payments)
+                    .values((
+                        payment_dsl::id.eq(payment_id),
+                        payment_dsl::order_id.eq(order_id),
+                        payment_dsl::provider.eq(provider.name()),
+                        payment_dsl::provider_reference.eq(provider_reference),
+                        payment_dsl::status.eq(status),
+                        payment_dsl::amount.eq(amount),
+                        payment_dsl::last_error.eq(last_error),
+                        payment_dsl::created_at.eq(now),
+                        payment_dsl::updated_at.eq(now),
+                    ))
+                    .get_result::<Payment>(conn)
+                    .await?;
+                let event = DomainEvent::PaymentStatusChanged {
+                    payment_id,
+                    order_id,
+                    customer_id,
+                    from: None,
+                    to: status,
+                    amount,
+                };
+                outbox::record(conn, &event).await?;
+                Ok(payment)
+            }
+            .scope_boxed()
+        })
+        .await;
+    let payment = match (recorded, authorized_reference) {
+        (Ok(payment), _) => payment,
+        (Err(err), Some(reference)) => {
+            if let Err(void_err) = provider.void(&reference).await {
+                tracing::error!(%order_id, %reference, "Failed to void authorization: {}", void_err);
+            }
+            return Err(err);
+        }
+        (Err(err), None) => return Err(err),
+    };
+    match failure {
+        Some(err) => Err(err.into()),
+        None => Ok(payment),
+    }
+}
+
+/******************************************/
+// Capturing, voiding and refunding
+/******************************************/
+// Admin steps on an existing payment. Each checks the current status, asks the provider,
+// then records the new status only if nobody moved the payment in between.
+pub async fn capture(
+    pool: &PgPool,
+    provider: &dyn PaymentProvider,
+    payment_id: Uuid,
+) -> Result<Payment, CustomError> {
+    let current = require_payment(pool, payment_id).await?;
+    if current.status != PaymentStatus::Authorized {
+        return Err(step_conflict("capture", &current));
+    }
+    let mut conn = connection(pool).await?;
+    let order_status = order_dsl::orders
+        .find(current.order_id)
+        .select(order_dsl::status)
+        .first::<OrderStatus>(&mut conn)
+        .await?;
+    if order_status == OrderStatus::Cancelled {
+        return Err(CustomError::ConflictError(format!(
+            "Order {} is cancelled; void payment {} instead",
+            current.order_id, payment_id
+        )));
+    }
+    let result = provider.capture(reference(&current)?, current.amount).await;
+    settle(
+        pool,
+        &current,
+        result,
+        PaymentStatus::Captured,
+        0,
+        current.amount,
+    )
+    .await
+}
+
+pub async fn void(
+    pool: &PgPool,
+    provider: &dyn PaymentProvider,
+    payment_id: Uuid,
+) -> Result<Payment, CustomError> {
+    let current = require_payment(pool, payment_id).await?;
+    if current.status != PaymentStatus::Authorized {
+        return Err(step_conflict("void", &current));
+    }
+    let result = provider.void(reference(&current)?).await;
+    settle(
+        pool,
+        &current,
+        result,
+        PaymentStatus::Voided,
+        0,
+        current.amount,
+    )
+    .await
+}
+
+// The rest of the captured amount unless `amount` says otherwise
+pub async fn refund(
+    pool: &PgPool,
+    provider: &dyn PaymentProvider,
+    payment_id: Uuid,
+    amount: Option<i32>,
+) -> Result<Payment, CustomError> {
+    let current = require_payment(pool, payment_id).await?;
+    if !current.status.is_captured() {
+        return Err(step_conflict("refund", &current));
+    }
+    let remaining = current.amount - current.refunded_amount;
+    let amount = amount.unwrap_or(remaining);
+    if amount <= 0 || amount > remaining {
+        return Err(CustomError::ValidationError(format!(
+            "Refund amount must be between 1 and {}",
+            remaining
+        )));
+    }
+    let refunded_amount = current.refunded_amount + amount;
+    let next = if refunded_amount == current.amount {
+        PaymentStatus::Refunded
+    } else {
+        PaymentStatus::PartiallyRefunded
+    };
+    let result = provider.refund(reference(&current)?, amount).await;
+    settle(pool, &current, result, next, refunded_amount, amount).await
+}
+
+async fn require_payment(pool: &PgPool, payment_id: Uuid) -> Result<Payment, CustomError> {
+    find_payment(pool, payment_id)
+        .await?
+        .ok_or_else(|| CustomError::NotFoundError(format!("Payment {}", payment_id)))
+}
+
+fn step_conflict(step: &str, payment: &Payment) -> CustomError {
+    CustomError::ConflictError(format!(
+        "Cannot {} payment {}; it is {:?}",
+        step, payment.id, payment.status
+    ))
+}
+
+// Records what the provider said: the failure on the payment as it was, or the new status
+// together with its outbox event
+async fn settle(
+    pool: &PgPool,
+    current: &Payment,
+    result: Result<(), PaymentError>,
+    next: PaymentStatus,
+    refunded_amount: i32,
+    moved: i32,
+) -> Result<Payment, CustomError> {
+    let mut conn = connection(pool).await?;
+    if let Err(err) = result {
+        diesel::update(payment_dsl::payments.find(current.id))
+            .set((
+                payment_dsl::last_error.eq(err.to_string()),
+                payment_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(&mut conn)
+            .await?;
+        return Err(err.into());
+    }
+
+    let current = current.clone();
+    conn.transaction::<_, CustomError, _>(|conn| {
+        async move { transition(conn, &current, next, refunded_amount, moved).await }.scope_boxed()
+    })
+    .await
+}
+
+// Moves the payment on from `current`, failing if it is no longer there, and records the
+// step. A full refund of an order that hasn't shipped also cancels the order. Must be called
+// inside a transaction.
+pub(crate) async fn transition(
+    conn: &mut AsyncPgConnection,
+    current: &Payment,
+    next: PaymentStatus,
+    refunded_amount: i32,
+    moved: i32,
+) -> Result<Payment, CustomError> {
+    let updated = diesel::update(
+        payment_dsl::payments
+            .filter(payment_dsl::id.eq(current.id))
+            .filter(payment_dsl::status.eq(current.status))
+            .filter(payment_dsl::refunded_amount.eq(current.refunded_amount)),
+    )
+    .set((
+        payment_dsl::status.eq(next),
+        payment_dsl::refunded_amount.eq(refunded_amount),
+        payment_dsl::last_error.eq(None::<String>),
+        payment_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .get_result::<Payment>(conn)
+    .await
+    .optional()?
+    .ok_or_else(|| {
+        CustomError::ConflictError(format!(
+            "Payment {} changed while it was being updated",
+            current.id
+        ))
+    })?;
+    let order = order_dsl::orders
+        .find(current.order_id)
+        .for_update()
+        .first::<Order>(conn)
+        .await?;
+
+    // Cancelled the way `PgOrderRepository::cancel` does it, unit back in stock included;
+    // the product is locked before any event is recorded
+    let cancels = next == PaymentStatus::Refunded && order.status == OrderStatus::Pending;
+    if cancels {
+        diesel::update(order_dsl::orders.find(order.id))
+            .set(order_dsl::status.eq(OrderStatus::Cancelled))
+            .execute(conn)
+            .await?;
+        return_to_stock(conn, order.product_id, 1).await?;
+        diesel::insert_into(cancellation_dsl::order_cancellations)
+            .values((
+                cancellation_dsl::order_id.eq(order.id),
+                cancellation_dsl::reason.eq(REFUNDED_REASON),
+                cancellation_dsl::admin_id.eq(None::<Uuid>),
+                cancellation_dsl::cancelled_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+            .await?;
+    }
+
+    let event = DomainEvent::PaymentStatusChanged {
+        payment_id: current.id,
+        order_id: order.id,
+        customer_id: order.customer_id,
+        from: Some(current.status),
+        to: next,
+        amount: moved,
+    };
+    outbox::record(conn, &event).await?;
+    if cancels {
+        let event = DomainEvent::OrderStatusChanged {
+            order_id: order.id,
+            customer_id: order.customer_id,
+            from: order.status,
+            to: OrderStatus::Cancelled,
+        };
+        outbox::record(conn, &event).await?;
+    }
+    Ok(updated)
+}
+
+/******************************************/
+// Reading payments
+/******************************************/
+pub async fn find_payment(pool: &PgPool, payment_id: Uuid) -> Result<Option<Payment>, CustomError> {
+    let mut conn = connection(pool).await?;
+    Ok(payment_dsl::payments
+        .find(payment_id)
+        .first::<Payment>(&mut conn)
+        .await
+        .optional()?)
+}
+
+// Every attempt, oldest first
+pub async fn list_for_order(pool: &PgPool, order_id: Uuid) -> Result<Vec<Payment>, CustomError> {
+    let mut conn = connection(pool).await?;
+    Ok(payment_dsl::payments
+        .filter(payment_dsl::order_id.eq(order_id))
+        .order(payment_dsl::created_at.asc())
+        .load::<Payment>(&mut conn)
+        .await?)
+}
+
+// Statuses of the order's payments that hold or have taken money; run inside the caller's
+// transaction, after it has locked the order
+pub(crate) async fn live_payments(
+    conn: &mut AsyncPgConnection,
+    order_id: Uuid,
+) -> QueryResult<Vec<PaymentStatus>> {
+    payment_dsl::payments
+        .filter(payment_dsl::order_id.eq(order_id))
+        .filter(payment_dsl::status.eq_any(vec![
+            PaymentStatus::Authorized,
+            PaymentStatus::Captured,
+            PaymentStatus::PartiallyRefunded,
+        ]))
+        .select(payment_dsl::status)
+        .load::<PaymentStatus>(conn)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PaymentStatus;
+
+    #[test]
+    fn only_captured_money_makes_an_order_fulfillable() {
+        assert!(PaymentStatus::Captured.is_captured());
+        assert!(PaymentStatus::PartiallyRefunded.is_captured());
+        assert!(!PaymentStatus::Authorized.is_captured());
+        assert!(!PaymentStatus::Refunded.is_captured());
+        assert!(!PaymentStatus::Voided.is_captured());
+        assert!(!PaymentStatus::Failed.is_captured());
+    }
+
+    #[test]
+    fn authorized_and_captured_payments_are_live() {
+        assert!(PaymentStatus::Authorized.is_live());
+        assert!(PaymentStatus::Captured.is_live());
+        assert!(PaymentStatus::PartiallyRefunded.is_live());
+        assert!(!PaymentStatus::Refunded.is_live());
+        assert!(!PaymentStatus::Voided.is_live());
+        assert!(!PaymentStatus::Failed.is_live());
+    }
+}
@@ -0,0 +1,317 @@
+use super::{connection, transition, PaymentStatus};
+use crate::config::configuration::PaymentSettings;
+use crate::db::PgPool;
+use crate::db_models::Payment;
+use crate::errors::custom::{AuthError, CustomError};
+use crate::schema::payment_events::dsl as event_dsl;
+use crate::schema::payments::dsl as payment_dsl;
+use crate::webhooks::signature;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/******************************************/
+// Provider callbacks
+/******************************************/
+// Signed like our own outgoing webhooks (see `webhooks::signature`), with the provider
+// holding `payments.webhook_secret`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum PaymentEventType {
+    #[serde(rename = "payment.captured")]
+    Captured,
+    #[serde(rename = "payment.refunded")]
+    Refunded,
+    #[serde(rename = "payment.voided")]
+    Voided,
+    #[serde(rename = "payment.failed")]
+    Failed,
+}
+
+/// A payment event sent by the provider
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PaymentWebhookEvent {
+    /// The provider's event id; redeliveries reuse it
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: PaymentEventType,
+    /// The `provider_reference` of the payment it is about
+    pub reference: String,
+    /// For `payment.refunded`, everything refunded so far; defaults to the full amount
+    pub amount_refunded: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookOutcome {
+    /// The payment moved
+    Applied,
+    /// The payment was already there, or the event arrived after a later one
+    Ignored,
+    /// This event id was received before
+    Duplicate,
+}
+
+impl WebhookOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            WebhookOutcome::Applied => "applied",
+            WebhookOutcome::Ignored => "ignored",
+            WebhookOutcome::Duplicate => "duplicate",
+        }
+    }
+}
+
+fn rejected(reason: &str) -> CustomError {
+    CustomError::AuthenticationError(AuthError::OtherAuthenticationError(reason.to_string()))
+}
+
+// Refuses callbacks that aren't signed with our secret, and replays of old ones; `now` is
+// in Unix seconds
+pub fn check_signature(
+    settings: &PaymentSettings,
+    timestamp: Option<&str>,
+    signature: Option<&str>,
+    body: &[u8],
+    now: i64,
+) -> Result<(), CustomError> {
+    if settings.webhook_secret.is_empty() {
+        return Err(rejected("Payment webhooks are not configured"));
+    }
+    let timestamp = timestamp
+        .and_then(|timestamp| timestamp.parse::<i64>().ok())
+        .ok_or_else(|| rejected("Missing or malformed webhook timestamp"))?;
+    if (now - timestamp).abs() > settings.webhook_tolerance_secs {
+        return Err(rejected("Webhook timestamp is outside the tolerance"));
+    }
+    let signature = signature.ok_or_else(|| rejected("Missing webhook signature"))?;
+    if !signature::verify(&settings.webhook_secret, timestamp, body, signature) {
+        return Err(rejected("Invalid webhook signature"));
+    }
+    Ok(())
+}
+
+// Where the event takes the payment, as its status and refunded amount; `None` when there is
+// nothing to do because it's already there or has moved past it
+pub fn next_state(payment: &Payment, event: &PaymentWebhookEvent) -> Option<(PaymentStatus, i32)> {
+    let authorized = payment.status == PaymentStatus::Authorized;
+    match event.event_type {
+        PaymentEventType::Captured if authorized => {
+            Some((PaymentStatus::Captured, payment.refunded_amount))
+        }
+        PaymentEventType::Voided if authorized => {
+            Some((PaymentStatus::Voided, payment.refunded_amount))
+        }
+        PaymentEventType::Failed if authorized => {
+            Some((PaymentStatus::Failed, payment.refunded_amount))
+        }
+        PaymentEventType::Refunded if payment.status.is_captured() => {
+            // A running total, so a late partial refund never undoes a later one
+            let total = event
+                .amount_refunded
+                .unwrap_or(payment.amount)
+                .min(payment.amount);
+            if total <= payment.refunded_amount {
+                return None;
+            }
+            let status = if total == payment.amount {
+                PaymentStatus::Refunded
+            } else {
+                PaymentStatus::PartiallyRefunded
+            };
+            Some((status, total))
+        }
+        _ => None,
+    }
+}
+
+// Applies a verified event once. The payment row is locked first, so concurrent deliveries
+// of the same event queue up behind each other and all but the first find it recorded.
+pub async fn receive(
+    pool: &PgPool,
+    provider: &str,
+    event: PaymentWebhookEvent,
+    payload: serde_json::Value,
+) -> Result<WebhookOutcome, CustomError> {
+    let mut conn = connection(pool).await?;
+    let provider = provider.to_string();
+    conn.transaction::<_, CustomError, _>(|conn| {
+        async move {
+            let current = payment_dsl::payments
+                .filter(payment_dsl::provider.eq(&provider))
+                .filter(payment_dsl::provider_reference.eq(&event.reference))
+                .for_update()
+                .first::<Payment>(conn)
+                .await
+                .optional()?
+                .ok_or_else(|| {
+                    CustomError::NotFoundError(format!("Payment {}", event.reference))
+                })?;
+            let seen = event_dsl::payment_events
+                .filter(event_dsl::provider.eq(&provider))
+                .filter(event_dsl::event_id.eq(&event.id))
+                .count()
+                .get_result::<i64>(conn)
+                .await?;
+            if seen > 0 {
+                return Ok(WebhookOutcome::Duplicate);
+            }
+
+            let next = next_state(&current, &event);
+            let outcome = match next {
+                Some(_) => WebhookOutcome::Applied,
+                None => WebhookOutcome::Ignored,
+            };
+            diesel::insert_into(event_dsl::payment_events)
+                .values((
+                    event_dsl::provider.eq(&provider),
+                    event_dsl::event_id.eq(&event.id),
+                    event_dsl::event_type.eq(payload["type"].as_str().unwrap_or_default()),
+                    event_dsl::provider_reference.eq(&event.reference),
+                    event_dsl::payload.eq(&payload),
+                    event_dsl::outcome.eq(outcome.as_str()),
+                    event_dsl::received_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .execute(conn)
+                .await?;
+            if let Some((status, refunded_amount)) = next {
+                let moved = match status {
+                    PaymentStatus::Refunded | PaymentStatus::PartiallyRefunded => {
+                        refunded_amount - current.refunded_amount
+                    }
+                    _ => current.amount,
+                };
+                transition(conn, &current, status, refunded_amount, moved).await?;
+            }
+            Ok(outcome)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_signature, next_state, PaymentEventType, PaymentWebhookEvent};
+    use crate::config::configuration::PaymentSettings;
+    use crate::db_models::Payment;
+    use crate::payments::PaymentStatus;
+    use crate::webhooks::signature::sign;
+    use uuid::Uuid;
+
+    const NOW: i64 = 1_700_000_000;
+    const BODY: &[u8] = br#"{"id":"evt_1"}"#;
+
+    fn settings() -> PaymentSettings {
+        PaymentSettings {
+            webhook_secret: "whsec_payments".to_string(),
+            ..PaymentSettings::default()
+        }
+    }
+
+    fn payment(status: PaymentStatus, refunded_amount: i32) -> Payment {
+        let now = chrono::Utc::now().naive_utc();
+        Payment {
+            id: Uuid::new_v4(),
+            order_id: Uuid::new_v4(),
+            provider: "mock".to_string(),
+            provider_reference: Some("mock_1".to_string()),
+            status,
+            amount: 50000,
+            refunded_amount,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn event(event_type: PaymentEventType, amount_refunded: Option<i32>) -> PaymentWebhookEvent {
+        PaymentWebhookEvent {
+            id: "evt_1".to_string(),
+            event_type,
+            reference: "mock_1".to_string(),
+            amount_refunded,
+        }
+    }
+
+    #[test]
+    fn signed_recent_callbacks_are_accepted() {
+        let signature = sign("whsec_payments", NOW - 60, BODY);
+        let timestamp = (NOW - 60).to_string();
+        assert!(
+            check_signature(&settings(), Some(&timestamp), Some(&signature), BODY, NOW).is_ok()
+        );
+    }
+
+    #[test]
+    fn replays_forgeries_and_unconfigured_secrets_are_refused() {
+        let old = NOW - 301;
+        let replayed = sign("whsec_payments", old, BODY);
+        assert!(check_signature(
+            &settings(),
+            Some(&old.to_string()),
+            Some(&replayed),
+            BODY,
+            NOW
+        )
+        .is_err());
+
+        let forged = sign("another-secret", NOW, BODY);
+        let timestamp = NOW.to_string();
+        assert!(check_signature(&settings(), Some(&timestamp), Some(&forged), BODY, NOW).is_err());
+        assert!(check_signature(&settings(), Some(&timestamp), None, BODY, NOW).is_err());
+        assert!(check_signature(&settings(), Some("soon"), Some(&forged), BODY, NOW).is_err());
+
+        let signature = sign("", NOW, BODY);
+        let unconfigured = PaymentSettings::default();
+        assert!(
+            check_signature(&unconfigured, Some(&timestamp), Some(&signature), BODY, NOW).is_err()
+        );
+    }
+
+    #[test]
+    fn events_only_move_payments_forward() {
+        let authorized = payment(PaymentStatus::Authorized, 0);
+        assert_eq!(
+            next_state(&authorized, &event(PaymentEventType::Captured, None)),
+            Some((PaymentStatus::Captured, 0))
+        );
+        let captured = payment(PaymentStatus::Captured, 0);
+        assert_eq!(
+            next_state(&captured, &event(PaymentEventType::Captured, None)),
+            None
+        );
+        assert_eq!(
+            next_state(&captured, &event(PaymentEventType::Voided, None)),
+            None
+        );
+        assert_eq!(
+            next_state(&authorized, &event(PaymentEventType::Refunded, None)),
+            None
+        );
+    }
+
+    #[test]
+    fn refunds_are_running_totals() {
+        let captured = payment(PaymentStatus::Captured, 0);
+        assert_eq!(
+            next_state(&captured, &event(PaymentEventType::Refunded, Some(10000))),
+            Some((PaymentStatus::PartiallyRefunded, 10000))
+        );
+        let partial = payment(PaymentStatus::PartiallyRefunded, 20000);
+        assert_eq!(
+            next_state(&partial, &event(PaymentEventType::Refunded, Some(10000))),
+            None
+        );
+        assert_eq!(
+            next_state(&partial, &event(PaymentEventType::Refunded, None)),
+            Some((PaymentStatus::Refunded, 50000))
+        );
+        assert_eq!(
+            next_state(&partial, &event(PaymentEventType::Refunded, Some(90000))),
+            Some((PaymentStatus::Refunded, 50000))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+pub mod tax;
+
+use crate::config::configuration::PricingSettings;
+use crate::errors::custom::CustomError;
+use crate::repository::details::OrderTotals;
+use std::sync::Arc;
+use tax::TaxCalculator;
+
+// Longest region code accepted, e.g. "US-CA"
+const MAX_REGION_LENGTH: usize = 16;
+
+/******************************************/
+// Order totals
+/******************************************/
+// An order is one unit of one product. The discount comes off the subtotal, tax is charged on
+// what is left of it (shipping isn't taxed), and shipping is added on top. The result is
+// stored with the order, so it never changes with the product's price.
+#[derive(Clone)]
+pub struct Pricing {
+    tax: Arc<dyn TaxCalculator>,
+    shipping_fee: i32,
+    free_shipping_over: Option<i32>,
+}
+
+fn too_large() -> CustomError {
+    CustomError::ValidationError("Order total is too large".to_string())
+}
+
+// Trimmed and upper-cased; `None` when no region was given
+pub fn normalize_region(region: Option<&str>) -> Result<Option<String>, CustomError> {
+    let Some(region) = region.map(str::trim).filter(|region| !region.is_empty()) else {
+        return Ok(None);
+    };
+    if region.len() > MAX_REGION_LENGTH
+        || !region
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(CustomError::ValidationError(format!(
+            "Region must be up to {} letters, digits or dashes",
+            MAX_REGION_LENGTH
+        )));
+    }
+    Ok(Some(region.to_ascii_uppercase()))
+}
+
+impl Pricing {
+    pub fn new(tax: Arc<dyn TaxCalculator>, settings: &PricingSettings) -> Self {
+        Self {
+            tax,
+            shipping_fee: settings.shipping_fee,
+            free_shipping_over: settings.free_shipping_over,
+        }
+    }
+
+    pub fn from_settings(settings: &PricingSettings) -> Self {
+        Self::new(tax::from_settings(settings), settings)
+    }
+
+    // For goods worth `goods` after the discount
+    pub fn shipping(&self, goods: i32) -> i32 {
+        match self.free_shipping_over {
+            Some(threshold) if goods >= threshold => 0,
+            _ => self.shipping_fee.max(0),
+        }
+    }
+
+    // The discount is capped at the subtotal, so an order never costs less than its shipping
+    pub async fn quote(
+        &self,
+        unit_price: i32,
+        discount: i32,
+        region: Option<&str>,
+    ) -> Result<OrderTotals, CustomError> {
+        if unit_price < 0 {
+            return Err(CustomError::ValidationError(
+                "Product has a negative price".to_string(),
+            ));
+        }
+        let tax_region = normalize_region(region)?;
+        let subtotal = unit_price;
+        let discount = discount.clamp(0, subtotal);
+        let goods = subtotal - discount;
+        let shipping = self.shipping(goods);
+        let tax = self.tax.tax(tax_region.as_deref(), goods).await?;
+        let grand_total = goods
+            .checked_add(shipping)
+            .and_then(|total| total.checked_add(tax.amount))
+            .ok_or_else(too_large)?;
+        Ok(OrderTotals {
+            unit_price,
+            subtotal,
+            discount,
+            shipping,
+            tax: tax.amount,
+            grand_total,
+            tax_region,
+            tax_rate_bps: i32::from(tax.rate_bps),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_region, Pricing};
+    use crate::config::configuration::PricingSettings;
+
+    fn pricing() -> Pricing {
+        let mut settings = PricingSettings {
+            shipping_fee: 500,
+            free_shipping_over: Some(10000),
+            ..PricingSettings::default()
+        };
+        settings.tax_rates.insert("us-ca".to_string(), 725);
+        Pricing::from_settings(&settings)
+    }
+
+    #[tokio::test]
+    async fn totals_add_up_with_tax_on_the_discounted_goods() {
+        let totals = pricing().quote(4999, 1000, Some(" us-ca ")).await.unwrap();
+        assert_eq!(totals.unit_price, 4999);
+        assert_eq!(totals.subtotal, 4999);
+        assert_eq!(totals.discount, 1000);
+        assert_eq!(totals.shipping, 500);
+        // 7.25% of 3999 is 289.9275
+        assert_eq!(totals.tax, 290);
+        assert_eq!(totals.grand_total, 3999 + 500 + 290);
+        assert_eq!(totals.tax_region.as_deref(), Some("US-CA"));
+        assert_eq!(totals.tax_rate_bps, 725);
+    }
+
+    #[tokio::test]
+    async fn large_orders_ship_free_and_discounts_are_capped() {
+        let free = pricing().quote(50000, 0, None).await.unwrap();
+        assert_eq!(free.shipping, 0);
+        assert_eq!(free.tax, 0);
+        assert_eq!(free.grand_total, 50000);
+
+        let capped = pricing().quote(4999, 9999, None).await.unwrap();
+        assert_eq!(capped.discount, 4999);
+        assert_eq!(capped.grand_total, 500);
+    }
+
+    #[tokio::test]
+    async fn overflowing_totals_are_refused() {
+        assert!(pricing().quote(i32::MAX, 0, Some("us-ca")).await.is_err());
+        assert!(pricing().quote(-1, 0, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn regions_without_a_rate_are_refused() {
+        assert!(pricing().quote(4999, 0, Some("fr")).await.is_err());
+    }
+
+    #[test]
+    fn regions_are_normalized_or_refused() {
+        assert_eq!(normalize_region(None).unwrap(), None);
+        assert_eq!(normalize_region(Some("  ")).unwrap(), None);
+        assert_eq!(normalize_region(Some("de")).unwrap().as_deref(), Some("DE"));
+        assert!(normalize_region(Some("US CA")).is_err());
+        assert!(normalize_region(Some(&"X".repeat(17))).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::config::configuration::{PricingSettings, TaxBackend, TaxRounding};
+use crate::errors::custom::CustomError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// 10_000 basis points make the whole amount
+const BASIS_POINTS: i64 = 10_000;
+
+/******************************************/
+// Tax calculators
+/******************************************/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaxQuote {
+    pub rate_bps: u16,
+    pub amount: i32,
+}
+
+// Works out the tax on an order's goods. Async so a calculator backed by a tax service can be
+// plugged in alongside the local rate table.
+#[async_trait]
+pub trait TaxCalculator: Send + Sync {
+    async fn tax(&self, region: Option<&str>, taxable: i32) -> Result<TaxQuote, CustomError>;
+}
+
+pub fn from_settings(settings: &PricingSettings) -> Arc<dyn TaxCalculator> {
+    match settings.tax_calculator {
+        TaxBackend::Regional => Arc::new(RegionalTaxCalculator::new(settings)),
+    }
+}
+
+// `amount` at `rate_bps` basis points, rounded to a whole unit; `amount` is never negative
+pub fn apply_rate(amount: i32, rate_bps: u16, rounding: TaxRounding) -> i64 {
+    let scaled = i64::from(amount) * i64::from(rate_bps);
+    let whole = scaled / BASIS_POINTS;
+    // Twice the remainder against the whole, so halves compare exactly
+    let twice_remainder = (scaled % BASIS_POINTS) * 2;
+    let round_up = match rounding {
+        TaxRounding::HalfUp => twice_remainder >= BASIS_POINTS,
+        TaxRounding::HalfEven => {
+            twice_remainder > BASIS_POINTS || (twice_remainder == BASIS_POINTS && whole % 2 == 1)
+        }
+        TaxRounding::Down => false,
+    };
+    whole + i64::from(round_up)
+}
+
+/******************************************/
+// Per-region rate table
+/******************************************/
+// `pricing.tax_rates`, with `pricing.default_tax_rate_bps` for orders without a region. The
+// customer picks the region, so one the table doesn't list is refused rather than quietly
+// taxed at the default rate.
+pub struct RegionalTaxCalculator {
+    default_rate_bps: u16,
+    // Keys lowercased, as the config loader already does for its own keys
+    rates: HashMap<String, u16>,
+    rounding: TaxRounding,
+}
+
+impl RegionalTaxCalculator {
+    pub fn new(settings: &PricingSettings) -> Self {
+        Self {
+            default_rate_bps: settings.default_tax_rate_bps,
+            rates: settings
+                .tax_rates
+                .iter()
+                .map(|(region, rate)| (region.to_ascii_lowercase(), *rate))
+                .collect(),
+            rounding: settings.tax_rounding,
+        }
+    }
+
+    pub fn rate(&self, region: Option<&str>) -> Result<u16, CustomError> {
+        let Some(region) = region else {
+            return Ok(self.default_rate_bps);
+        };
+        self.rates
+            .get(&region.to_ascii_lowercase())
+            .copied()
+            .ok_or_else(|| {
+                CustomError::ValidationError(format!("Tax region {} isn't supported", region))
+            })
+    }
+}
+
+#[async_trait]
+impl TaxCalculator for RegionalTaxCalculator {
+    async fn tax(&self, region: Option<&str>, taxable: i32) -> Result<TaxQuote, CustomError> {
+        let rate_bps = self.rate(region)?;
+        let amount = i32::try_from(apply_rate(taxable, rate_bps, self.rounding))
+            .map_err(|_| CustomError::ValidationError("Order total is too large".to_string()))?;
+        Ok(TaxQuote { rate_bps, amount })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_rate, RegionalTaxCalculator, TaxCalculator, TaxQuote};
+    use crate::config::configuration::{PricingSettings, TaxRounding};
+
+    #[test]
+    fn half_up_rounds_halves_away_from_zero() {
+        // 7.25% of 50000 is exactly 3625
+        assert_eq!(apply_rate(50000, 725, TaxRounding::HalfUp), 3625);
+        // 10% of 125 is 12.5, of 135 is 13.5
+        assert_eq!(apply_rate(125, 1000, TaxRounding::HalfUp), 13);
+        assert_eq!(apply_rate(135, 1000, TaxRounding::HalfUp), 14);
+        // 7.25% of 999 is 72.4275
+        assert_eq!(apply_rate(999, 725, TaxRounding::HalfUp), 72);
+        // 7.25% of 1001 is 72.5725
+        assert_eq!(apply_rate(1001, 725, TaxRounding::HalfUp), 73);
+    }
+
+    #[test]
+    fn half_even_rounds_halves_to_the_even_neighbour() {
+        assert_eq!(apply_rate(125, 1000, TaxRounding::HalfEven), 12);
+        assert_eq!(apply_rate(135, 1000, TaxRounding::HalfEven), 14);
+        // Only exact halves are special; 72.5725 still rounds up
+        assert_eq!(apply_rate(1001, 725, TaxRounding::HalfEven), 73);
+        assert_eq!(apply_rate(999, 725, TaxRounding::HalfEven), 72);
+    }
+
+    #[test]
+    fn down_truncates_every_fraction() {
+        assert_eq!(apply_rate(135, 1000, TaxRounding::Down), 13);
+        assert_eq!(apply_rate(1001, 725, TaxRounding::Down), 72);
+        assert_eq!(apply_rate(50000, 725, TaxRounding::Down), 3625);
+    }
+
+    #[test]
+    fn nothing_and_zero_rates_are_untaxed() {
+        assert_eq!(apply_rate(0, 1900, TaxRounding::HalfUp), 0);
+        assert_eq!(apply_rate(50000, 0, TaxRounding::HalfUp), 0);
+        // Less than half a unit of tax rounds to nothing
+        assert_eq!(apply_rate(4, 1000, TaxRounding::HalfUp), 0);
+        assert_eq!(apply_rate(5, 1000, TaxRounding::HalfUp), 1);
+    }
+
+    #[tokio::test]
+    async fn regions_use_their_own_rate_and_unknown_ones_are_refused() {
+        let mut settings = PricingSettings {
+            default_tax_rate_bps: 500,
+            ..PricingSettings::default()
+        };
+        settings.tax_rates.insert("US-CA".to_string(), 725);
+        settings.tax_rates.insert("de".to_string(), 1900);
+        let calculator = RegionalTaxCalculator::new(&settings);
+
+        assert_eq!(
+            calculator.tax(Some("us-ca"), 50000).await.unwrap(),
+            TaxQuote {
+                rate_bps: 725,
+                amount: 3625
+            }
+        );
+        assert_eq!(
+            calculator.tax(Some("DE"), 50000).await.unwrap(),
+            TaxQuote {
+                rate_bps: 1900,
+                amount: 9500
+            }
+        );
+        assert!(calculator.tax(Some("FR"), 50000).await.is_err());
+        assert_eq!(calculator.tax(None, 50000).await.unwrap().rate_bps, 500);
+    }
+}
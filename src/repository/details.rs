@@ -0,0 +1,108 @@
+use crate::db_models::Order;
+use crate::routes::order::order::OrderStatus;
+use chrono::NaiveDateTime;
+use diesel::Queryable;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/******************************************/
+// Order detail responses
+/******************************************/
+// Loaded from `orders` joined with `products` and `customers`, and left joined with
+// `order_totals` and `order_cancellations`; see the `joinable!` declarations in the schema.
+
+#[derive(Queryable, Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct ProductSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub price: i32,
+}
+
+#[derive(Queryable, Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct CustomerSummary {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+}
+
+/// What the order cost when it was placed, in the same unit as the product price
+#[derive(Queryable, Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct OrderTotals {
+    /// The product's price at the time; later price changes don't touch it
+    pub unit_price: i32,
+    pub subtotal: i32,
+    pub discount: i32,
+    pub shipping: i32,
+    pub tax: i32,
+    /// `subtotal - discount + shipping + tax`, what the customer pays
+    pub grand_total: i32,
+    /// The region the tax was worked out for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tax_region: Option<String>,
+    /// In basis points, 725 being 7.25%
+    pub tax_rate_bps: i32,
+}
+
+#[derive(Queryable, Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct OrderCancellation {
+    pub reason: String,
+    pub cancelled_at: NaiveDateTime,
+    /// The admin who cancelled the order; absent when the customer did, and never shown to
+    /// customers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct OrderDetail {
+    pub id: Uuid,
+    pub status: OrderStatus,
+    pub created_at: NaiveDateTime,
+    pub product: ProductSummary,
+    /// Only shown to admins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer: Option<CustomerSummary>,
+    /// Absent for orders inserted without a price snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totals: Option<OrderTotals>,
+    /// Present once the order is `Cancelled`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancellation: Option<OrderCancellation>,
+}
+
+impl OrderDetail {
+    pub fn from_parts(
+        order: Order,
+        product: ProductSummary,
+        customer: CustomerSummary,
+        totals: Option<OrderTotals>,
+        cancellation: Option<OrderCancellation>,
+    ) -> Self {
+        Self {
+            id: order.id,
+            status: order.status,
+            created_at: order.created_at,
+            product,
+            customer: Some(customer),
+            totals,
+            cancellation,
+        }
+    }
+
+    // What a customer sees of their own order
+    pub fn without_customer(self) -> Self {
+        Self {
+            customer: None,
+            cancellation: self.cancellation.map(|cancellation| OrderCancellation {
+                admin_id: None,
+                ..cancellation
+            }),
+            ..self
+        }
+    }
+
+    pub fn customer_id(&self) -> Option<Uuid> {
+        self.customer.as_ref().map(|customer| customer.id)
+    }
+}
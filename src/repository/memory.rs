@@ -0,0 +1,431 @@
+use super::details::{
+    CustomerSummary, OrderCancellation, OrderDetail, OrderTotals, ProductSummary,
+};
+use super::pagination::{OrderPage, OrderPageRequest};
+use super::{
+    check_transition, not_captured, AdminRepository, CancelOutcome, CustomerRepository,
+    OrderRepository, ProductRepository,
+};
+use crate::db_models::{Admin, Customer, Order, Product};
+use crate::errors::custom::{CustomError, DbError};
+use crate::payments::PaymentStatus;
+use crate::routes::order::order::OrderStatus;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+// Mirrors the unique constraints Postgres would enforce
+fn duplicate(constraint: &str) -> CustomError {
+    CustomError::DatabaseError(DbError::QueryBuilderError(format!(
+        "duplicate key value violates unique constraint \"{}\"",
+        constraint
+    )))
+}
+
+fn missing_reference(constraint: &str) -> CustomError {
+    CustomError::DatabaseError(DbError::QueryBuilderError(format!(
+        "insert or update on table \"orders\" violates foreign key constraint \"{}\"",
+        constraint
+    )))
+}
+
+/******************************************/
+// Customers
+/******************************************/
+#[derive(Default)]
+pub struct InMemoryCustomerRepository {
+    customers: RwLock<HashMap<Uuid, Customer>>,
+}
+
+#[async_trait]
+impl CustomerRepository for InMemoryCustomerRepository {
+    async fn insert(&self, customer: Customer) -> Result<(), CustomError> {
+        let mut customers = self.customers.write().unwrap();
+        if customers.contains_key(&customer.id) {
+            return Err(duplicate("customers_pkey"));
+        }
+        if customers.values().any(|c| c.username == customer.username) {
+            return Err(duplicate("customers_username_key"));
+        }
+        if customers.values().any(|c| c.email == customer.email) {
+            return Err(duplicate("customers_email_key"));
+        }
+        customers.insert(customer.id, customer);
+        Ok(())
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Customer>, CustomError> {
+        Ok(self.customers.read().unwrap().get(&id).cloned())
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<Customer>, CustomError> {
+        Ok(self
+            .customers
+            .read()
+            .unwrap()
+            .values()
+            .find(|c| c.username == username)
+            .cloned())
+    }
+
+    async fn update_profile(
+        &self,
+        id: Uuid,
+        username: &str,
+        email: &str,
+    ) -> Result<bool, CustomError> {
+        let mut customers = self.customers.write().unwrap();
+        if customers
+            .values()
+            .any(|c| c.id != id && c.username == username)
+        {
+            return Err(duplicate("customers_username_key"));
+        }
+        if customers.values().any(|c| c.id != id && c.email == email) {
+            return Err(duplicate("customers_email_key"));
+        }
+        match customers.get_mut(&id) {
+            Some(customer) => {
+                customer.username = username.to_string();
+                customer.email = email.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn update_password_hash(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<bool, CustomError> {
+        let mut customers = self.customers.write().unwrap();
+        match customers.values_mut().find(|c| c.username == username) {
+            Some(customer) => {
+                customer.password_hash = password_hash.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/******************************************/
+// Admins
+/******************************************/
+#[derive(Default)]
+pub struct InMemoryAdminRepository {
+    admins: RwLock<HashMap<Uuid, Admin>>,
+}
+
+#[async_trait]
+impl AdminRepository for InMemoryAdminRepository {
+    async fn insert(&self, admin: Admin) -> Result<(), CustomError> {
+        let mut admins = self.admins.write().unwrap();
+        if admins.contains_key(&admin.id) {
+            return Err(duplicate("admins_pkey"));
+        }
+        if admins.values().any(|a| a.username == admin.username) {
+            return Err(duplicate("admins_username_key"));
+        }
+        admins.insert(admin.id, admin);
+        Ok(())
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<Admin>, CustomError> {
+        Ok(self
+            .admins
+            .read()
+            .unwrap()
+            .values()
+            .find(|a| a.username == username)
+            .cloned())
+    }
+
+    async fn update_password_hash(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<bool, CustomError> {
+        let mut admins = self.admins.write().unwrap();
+        match admins.values_mut().find(|a| a.username == username) {
+            Some(admin) => {
+                admin.password_hash = password_hash.to_string();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/******************************************/
+// Orders
+/******************************************/
+// Shares the customer and product stores so orders can reference and be joined with them
+pub struct InMemoryOrderRepository {
+    orders: RwLock<Vec<Order>>,
+    totals: RwLock<HashMap<Uuid, OrderTotals>>,
+    cancellations: RwLock<HashMap<Uuid, OrderCancellation>>,
+    // Latest payment status per order, standing in for the `payments` table
+    payments: RwLock<HashMap<Uuid, PaymentStatus>>,
+    customers: Arc<InMemoryCustomerRepository>,
+    products: Arc<InMemoryProductRepository>,
+}
+
+impl InMemoryOrderRepository {
+    pub fn new(
+        customers: Arc<InMemoryCustomerRepository>,
+        products: Arc<InMemoryProductRepository>,
+    ) -> Self {
+        Self {
+            orders: RwLock::new(Vec::new()),
+            totals: RwLock::new(HashMap::new()),
+            cancellations: RwLock::new(HashMap::new()),
+            payments: RwLock::new(HashMap::new()),
+            customers,
+            products,
+        }
+    }
+
+    pub fn record_payment(&self, order_id: Uuid, status: PaymentStatus) {
+        self.payments.write().unwrap().insert(order_id, status);
+    }
+
+    fn payment(&self, order_id: Uuid) -> Option<PaymentStatus> {
+        self.payments.read().unwrap().get(&order_id).copied()
+    }
+
+    // The inner join of the Postgres queries; `insert` keeps both sides present
+    fn detail(&self, order: &Order) -> Option<OrderDetail> {
+        let product = self
+            .products
+            .products
+            .read()
+            .unwrap()
+            .get(&order.product_id)
+            .map(|p| ProductSummary {
+                id: p.id,
+                name: p.name.clone(),
+                price: p.price,
+            })?;
+        let customer = self
+            .customers
+            .customers
+            .read()
+            .unwrap()
+            .get(&order.customer_id)
+            .map(|c| CustomerSummary {
+                id: c.id,
+                username: c.username.clone(),
+                email: c.email.clone(),
+            })?;
+        let totals = self.totals.read().unwrap().get(&order.id).cloned();
+        let cancellation = self.cancellations.read().unwrap().get(&order.id).cloned();
+        Some(OrderDetail::from_parts(
+            order.clone(),
+            product,
+            customer,
+            totals,
+            cancellation,
+        ))
+    }
+}
+
+#[async_trait]
+impl OrderRepository for InMemoryOrderRepository {
+    async fn insert(&self, order: Order) -> Result<(), CustomError> {
+        let mut orders = self.orders.write().unwrap();
+        if orders.iter().any(|o| o.id == order.id) {
+            return Err(duplicate("orders_pkey"));
+        }
+        if !self
+            .customers
+            .customers
+            .read()
+            .unwrap()
+            .contains_key(&order.customer_id)
+        {
+            return Err(missing_reference("orders_customer_id_fkey"));
+        }
+        let mut products = self.products.products.write().unwrap();
+        let Some(product) = products.get_mut(&order.product_id) else {
+            return Err(missing_reference("fk_product"));
+        };
+        match product.stock {
+            Some(0) => {
+                return Err(CustomError::ConflictError(format!(
+                    "Product {} is out of stock",
+                    product.id
+                )))
+            }
+            Some(stock) => product.stock = Some(stock - 1),
+            None => {}
+        }
+        orders.push(order);
+        Ok(())
+    }
+
+    async fn insert_with_totals(
+        &self,
+        order: Order,
+        totals: OrderTotals,
+    ) -> Result<(), CustomError> {
+        let order_id = order.id;
+        self.insert(order).await?;
+        self.totals.write().unwrap().insert(order_id, totals);
+        Ok(())
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Order>, CustomError> {
+        Ok(self
+            .orders
+            .read()
+            .unwrap()
+            .iter()
+            .find(|o| o.id == id)
+            .cloned())
+    }
+
+    async fn find_detail(&self, id: Uuid) -> Result<Option<OrderDetail>, CustomError> {
+        let order = self.find(id).await?;
+        Ok(order.and_then(|order| self.detail(&order)))
+    }
+
+    async fn list_page(&self, request: &OrderPageRequest) -> Result<OrderPage, CustomError> {
+        let mut orders: Vec<OrderDetail> = self
+            .orders
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|o| request.filter.matches(o))
+            .filter(|o| match request.cursor {
+                None => true,
+                Some(cursor) if request.scans_ascending() => {
+                    (o.created_at, o.id) > (cursor.created_at, cursor.id)
+                }
+                Some(cursor) => (o.created_at, o.id) < (cursor.created_at, cursor.id),
+            })
+            .filter_map(|o| self.detail(o))
+            .collect();
+        orders.sort_by_key(|o| (o.created_at, o.id));
+        if !request.scans_ascending() {
+            orders.reverse();
+        }
+        orders.truncate(request.limit.max(0) as usize + 1);
+        Ok(request.into_page(orders))
+    }
+
+    async fn list(&self, status: Option<OrderStatus>) -> Result<Vec<Order>, CustomError> {
+        let mut orders: Vec<Order> = self
+            .orders
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|o| status.is_none_or(|status| o.status == status))
+            .cloned()
+            .collect();
+        orders.sort_by_key(|o| o.created_at);
+        Ok(orders)
+    }
+
+    async fn update_status(&self, id: Uuid, status: OrderStatus) -> Result<bool, CustomError> {
+        let mut orders = self.orders.write().unwrap();
+        let Some(order) = orders.iter_mut().find(|o| o.id == id) else {
+            return Ok(false);
+        };
+        check_transition(id, order.status, status)?;
+        if matches!(status, OrderStatus::Shipped | OrderStatus::Delivered)
+            && !self.payment(id).is_some_and(PaymentStatus::is_captured)
+        {
+            return Err(not_captured(id));
+        }
+        order.status = status;
+        Ok(true)
+    }
+
+    async fn cancel(
+        &self,
+        id: Uuid,
+        reason: &str,
+        admin_id: Option<Uuid>,
+    ) -> Result<CancelOutcome, CustomError> {
+        let mut orders = self.orders.write().unwrap();
+        let Some(order) = orders.iter_mut().find(|o| o.id == id) else {
+            return Ok(CancelOutcome::NotFound);
+        };
+        if order.status != OrderStatus::Pending {
+            return Ok(CancelOutcome::NotCancellable(order.status));
+        }
+        if let Some(payment) = self.payment(id).filter(|payment| payment.is_live()) {
+            return Ok(CancelOutcome::PaymentOutstanding(payment));
+        }
+        order.status = OrderStatus::Cancelled;
+        if let Some(product) = self
+            .products
+            .products
+            .write()
+            .unwrap()
+            .get_mut(&order.product_id)
+        {
+            product.stock = product.stock.map(|stock| stock + 1);
+        }
+        self.cancellations.write().unwrap().insert(
+            id,
+            OrderCancellation {
+                reason: reason.to_string(),
+                cancelled_at: chrono::Utc::now().naive_utc(),
+                admin_id,
+            },
+        );
+        Ok(CancelOutcome::Cancelled)
+    }
+}
+
+/******************************************/
+// Products
+/******************************************/
+#[derive(Default)]
+pub struct InMemoryProductRepository {
+    products: RwLock<HashMap<Uuid, Product>>,
+}
+
+#[async_trait]
+impl ProductRepository for InMemoryProductRepository {
+    async fn insert(&self, product: Product) -> Result<(), CustomError> {
+        let mut products = self.products.write().unwrap();
+        if products.contains_key(&product.id) {
+            return Err(duplicate("products_pkey"));
+        }
+        if product.sku.is_some() && products.values().any(|p| p.sku == product.sku) {
+            return Err(duplicate("products_sku_key"));
+        }
+        products.insert(product.id, product);
+        Ok(())
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Product>, CustomError> {
+        Ok(self.products.read().unwrap().get(&id).cloned())
+    }
+
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, CustomError> {
+        Ok(self
+            .products
+            .read()
+            .unwrap()
+            .values()
+            .find(|p| p.sku.as_deref() == Some(sku))
+            .cloned())
+    }
+
+    async fn update(&self, product: Product) -> Result<bool, CustomError> {
+        let mut products = self.products.write().unwrap();
+        match products.get_mut(&product.id) {
+            Some(existing) => {
+                *existing = product;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
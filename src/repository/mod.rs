@@ -0,0 +1,177 @@
+pub mod details;
+pub mod memory;
+pub mod pagination;
+pub mod postgres;
+
+use crate::db::PgPool;
+use crate::db_models::{Admin, Customer, Order, Product};
+use crate::errors::custom::CustomError;
+use crate::payments::PaymentStatus;
+use crate::repository::details::{OrderDetail, OrderTotals};
+use crate::repository::pagination::{OrderPage, OrderPageRequest};
+use crate::routes::order::order::OrderStatus;
+use actix_web::web;
+use async_trait::async_trait;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/******************************************/
+// Repository traits
+/******************************************/
+// Handlers depend on these instead of Diesel; `postgres` backs the server and
+// `memory` lets business rules be tested without a database. Registering a customer,
+// placing an order and changing its status also record an `outbox::DomainEvent` in the
+// same transaction on Postgres; the in-memory repositories have no outbox.
+#[async_trait]
+pub trait CustomerRepository: Send + Sync {
+    async fn insert(&self, customer: Customer) -> Result<(), CustomError>;
+    async fn find(&self, id: Uuid) -> Result<Option<Customer>, CustomError>;
+    async fn find_by_username(&self, username: &str) -> Result<Option<Customer>, CustomError>;
+    // Returns false when no customer has that id
+    async fn update_profile(
+        &self,
+        id: Uuid,
+        username: &str,
+        email: &str,
+    ) -> Result<bool, CustomError>;
+    async fn update_password_hash(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<bool, CustomError>;
+}
+
+#[async_trait]
+pub trait AdminRepository: Send + Sync {
+    async fn insert(&self, admin: Admin) -> Result<(), CustomError>;
+    async fn find_by_username(&self, username: &str) -> Result<Option<Admin>, CustomError>;
+    async fn update_password_hash(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<bool, CustomError>;
+}
+
+#[async_trait]
+pub trait OrderRepository: Send + Sync {
+    // Without a price snapshot; orders customers place go through `insert_with_totals`
+    async fn insert(&self, order: Order) -> Result<(), CustomError>;
+    // The order and what it cost, in one transaction
+    async fn insert_with_totals(
+        &self,
+        order: Order,
+        totals: OrderTotals,
+    ) -> Result<(), CustomError>;
+    async fn find(&self, id: Uuid) -> Result<Option<Order>, CustomError>;
+    // The order joined with its product and customer
+    async fn find_detail(&self, id: Uuid) -> Result<Option<OrderDetail>, CustomError>;
+    // One page of matching orders, keyset-paginated on `(created_at, id)`
+    async fn list_page(&self, request: &OrderPageRequest) -> Result<OrderPage, CustomError>;
+    // Oldest first, optionally restricted to one status; for exports, not request handlers
+    async fn list(&self, status: Option<OrderStatus>) -> Result<Vec<Order>, CustomError>;
+    // `false` if there is no such order. Refuses moves outside `STATUS_TRANSITIONS`, and to
+    // ship or deliver an order without a captured payment.
+    async fn update_status(&self, id: Uuid, status: OrderStatus) -> Result<bool, CustomError>;
+    // Moves a `Pending` order to `Cancelled` and records why; `admin_id` is `None` when the
+    // customer cancels. Orders holding a payment stay as they are until it is voided or
+    // refunded.
+    async fn cancel(
+        &self,
+        id: Uuid,
+        reason: &str,
+        admin_id: Option<Uuid>,
+    ) -> Result<CancelOutcome, CustomError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelOutcome {
+    Cancelled,
+    NotFound,
+    // Only pending orders can be cancelled; carries the status the order is in
+    NotCancellable(OrderStatus),
+    // An authorized or captured payment has to be settled first; carries its status
+    PaymentOutstanding(PaymentStatus),
+}
+
+// Every move `update_status` makes; cancelling goes through `cancel`, and setting the status
+// an order already has changes nothing
+const STATUS_TRANSITIONS: [(OrderStatus, OrderStatus); 3] = [
+    (OrderStatus::Pending, OrderStatus::Shipped),
+    (OrderStatus::Pending, OrderStatus::Delivered),
+    (OrderStatus::Shipped, OrderStatus::Delivered),
+];
+
+// Both repositories refuse any other move the same way
+fn check_transition(order_id: Uuid, from: OrderStatus, to: OrderStatus) -> Result<(), CustomError> {
+    if from == to || STATUS_TRANSITIONS.contains(&(from, to)) {
+        return Ok(());
+    }
+    Err(CustomError::ConflictError(format!(
+        "Order {} can't go from {:?} to {:?}",
+        order_id, from, to
+    )))
+}
+
+// Both repositories refuse the same way to ship an order nobody has paid for
+fn not_captured(order_id: Uuid) -> CustomError {
+    CustomError::ConflictError(format!("Order {} has no captured payment", order_id))
+}
+
+#[async_trait]
+pub trait ProductRepository: Send + Sync {
+    async fn insert(&self, product: Product) -> Result<(), CustomError>;
+    async fn find(&self, id: Uuid) -> Result<Option<Product>, CustomError>;
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, CustomError>;
+    async fn update(&self, product: Product) -> Result<bool, CustomError>;
+}
+
+/******************************************/
+// Bundle registered as app data
+/******************************************/
+#[derive(Clone)]
+pub struct Repositories {
+    pub customers: Arc<dyn CustomerRepository>,
+    pub admins: Arc<dyn AdminRepository>,
+    pub orders: Arc<dyn OrderRepository>,
+    pub products: Arc<dyn ProductRepository>,
+}
+
+impl Repositories {
+    pub fn postgres(pool: PgPool) -> Self {
+        Self {
+            customers: Arc::new(postgres::PgCustomerRepository::new(pool.clone())),
+            admins: Arc::new(postgres::PgAdminRepository::new(pool.clone())),
+            orders: Arc::new(postgres::PgOrderRepository::new(pool.clone())),
+            products: Arc::new(postgres::PgProductRepository::new(pool)),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self::in_memory_with_orders().0
+    }
+
+    // Also hands back the order store, so tests can record the payments it has no routes for
+    pub fn in_memory_with_orders() -> (Self, Arc<memory::InMemoryOrderRepository>) {
+        let customers = Arc::new(memory::InMemoryCustomerRepository::default());
+        let products = Arc::new(memory::InMemoryProductRepository::default());
+        let orders = Arc::new(memory::InMemoryOrderRepository::new(
+            customers.clone(),
+            products.clone(),
+        ));
+        let repositories = Self {
+            orders: orders.clone(),
+            customers,
+            admins: Arc::new(memory::InMemoryAdminRepository::default()),
+            products,
+        };
+        (repositories, orders)
+    }
+
+    // Handlers extract each repository as `web::Data<dyn ...Repository>`
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::from(self.customers.clone()))
+            .app_data(web::Data::from(self.admins.clone()))
+            .app_data(web::Data::from(self.orders.clone()))
+            .app_data(web::Data::from(self.products.clone()));
+    }
+}
@@ -0,0 +1,376 @@
+use crate::db_models::Order;
+use crate::repository::details::OrderDetail;
+use crate::routes::order::order::OrderStatus;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 50;
+pub const MAX_PAGE_LIMIT: i64 = 200;
+
+/******************************************/
+// Listing requests
+/******************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderFilter {
+    pub status: Option<OrderStatus>,
+    pub customer_id: Option<Uuid>,
+    pub product_id: Option<Uuid>,
+    // Inclusive
+    pub created_from: Option<NaiveDateTime>,
+    // Exclusive
+    pub created_to: Option<NaiveDateTime>,
+}
+
+impl OrderFilter {
+    pub fn matches(&self, order: &Order) -> bool {
+        self.status.is_none_or(|status| order.status == status)
+            && self.customer_id.is_none_or(|id| order.customer_id == id)
+            && self.product_id.is_none_or(|id| order.product_id == id)
+            && self
+                .created_from
+                .is_none_or(|from| order.created_at >= from)
+            && self.created_to.is_none_or(|to| order.created_at < to)
+    }
+}
+
+/// Orders are kept in `created_at` order, ties broken by id
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSort {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Next,
+    Prev,
+}
+
+// Position of the last (or first) order on a page. Opaque to clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderCursor {
+    pub direction: Direction,
+    pub sort: OrderSort,
+    pub created_at: NaiveDateTime,
+    pub id: Uuid,
+}
+
+impl OrderCursor {
+    fn at(order: &OrderDetail, direction: Direction, sort: OrderSort) -> Self {
+        Self {
+            direction,
+            sort,
+            created_at: order.created_at,
+            id: order.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            Direction::Next => "n",
+            Direction::Prev => "p",
+        };
+        let sort = match self.sort {
+            OrderSort::Newest => "d",
+            OrderSort::Oldest => "a",
+        };
+        let created_at = self.created_at.and_utc();
+        hex::encode(format!(
+            "{}:{}:{}.{}:{}",
+            direction,
+            sort,
+            created_at.timestamp(),
+            created_at.timestamp_subsec_nanos(),
+            self.id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        let invalid = || "Invalid cursor".to_string();
+        let raw = hex::decode(cursor).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let mut parts = raw.splitn(4, ':');
+        let direction = match parts.next() {
+            Some("n") => Direction::Next,
+            Some("p") => Direction::Prev,
+            _ => return Err(invalid()),
+        };
+        let sort = match parts.next() {
+            Some("d") => OrderSort::Newest,
+            Some("a") => OrderSort::Oldest,
+            _ => return Err(invalid()),
+        };
+        let (secs, nanos) = parts
+            .next()
+            .and_then(|timestamp| timestamp.split_once('.'))
+            .ok_or_else(invalid)?;
+        let created_at = chrono::DateTime::from_timestamp(
+            secs.parse().map_err(|_| invalid())?,
+            nanos.parse().map_err(|_| invalid())?,
+        )
+        .ok_or_else(invalid)?
+        .naive_utc();
+        let id = parts
+            .next()
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            direction,
+            sort,
+            created_at,
+            id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderPageRequest {
+    pub filter: OrderFilter,
+    pub sort: OrderSort,
+    pub limit: i64,
+    pub cursor: Option<OrderCursor>,
+}
+
+impl OrderPageRequest {
+    // From query parameters: clamps `limit` and rejects cursors from a different sort
+    pub fn parse(
+        filter: OrderFilter,
+        sort: Option<OrderSort>,
+        limit: Option<i64>,
+        cursor: Option<&str>,
+    ) -> Result<Self, String> {
+        let cursor = cursor.map(OrderCursor::decode).transpose()?;
+        let sort = match (sort, cursor) {
+            (Some(sort), Some(cursor)) if sort != cursor.sort => {
+                return Err("Cursor was issued for a different sort".to_string())
+            }
+            (Some(sort), _) => sort,
+            (None, Some(cursor)) => cursor.sort,
+            (None, None) => OrderSort::default(),
+        };
+        Ok(Self {
+            filter,
+            sort,
+            limit: limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT),
+            cursor,
+        })
+    }
+
+    // A `Prev` cursor reads against the sort order
+    fn backwards(&self) -> bool {
+        matches!(
+            self.cursor,
+            Some(OrderCursor {
+                direction: Direction::Prev,
+                ..
+            })
+        )
+    }
+
+    // Whether rows are read oldest first
+    pub fn scans_ascending(&self) -> bool {
+        (self.sort == OrderSort::Oldest) != self.backwards()
+    }
+
+    /// Builds the page from up to `limit + 1` rows read in scan order; the extra row only
+    /// tells whether there is more in that direction
+    pub fn into_page(&self, mut rows: Vec<OrderDetail>) -> OrderPage {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit.max(0) as usize);
+        let backwards = self.backwards();
+        if backwards {
+            rows.reverse();
+        }
+        let cursor = |order: Option<&OrderDetail>, direction| {
+            order.map(|order| OrderCursor::at(order, direction, self.sort).encode())
+        };
+        // Coming from a cursor means there is a page on the side we came from
+        let (more_before, more_after) = match self.cursor {
+            None => (false, has_more),
+            Some(_) if backwards => (has_more, true),
+            Some(_) => (true, has_more),
+        };
+        OrderPage {
+            next_cursor: if more_after {
+                cursor(rows.last(), Direction::Next)
+            } else {
+                None
+            },
+            prev_cursor: if more_before {
+                cursor(rows.first(), Direction::Prev)
+            } else {
+                None
+            },
+            orders: rows,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OrderPage {
+    pub orders: Vec<OrderDetail>,
+    /// Pass as `cursor` for the following page; absent on the last page
+    pub next_cursor: Option<String>,
+    /// Pass as `cursor` for the preceding page; absent on the first page
+    pub prev_cursor: Option<String>,
+}
+
+impl OrderPage {
+    // Customers listing their own orders don't get the customer summary
+    pub fn without_customers(self) -> Self {
+        Self {
+            orders: self
+                .orders
+                .into_iter()
+                .map(OrderDetail::without_customer)
+                .collect(),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, OrderCursor, OrderFilter, OrderPageRequest, OrderSort};
+    use crate::db_models::{Customer, Order, Product};
+    use crate::payments::PaymentStatus;
+    use crate::repository::memory::{
+        InMemoryCustomerRepository, InMemoryOrderRepository, InMemoryProductRepository,
+    };
+    use crate::repository::{CustomerRepository, OrderRepository, ProductRepository};
+    use crate::routes::order::order::OrderStatus;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    async fn repository_with(count: i64) -> (InMemoryOrderRepository, Vec<Uuid>) {
+        let customers = Arc::new(InMemoryCustomerRepository::default());
+        let products = Arc::new(InMemoryProductRepository::default());
+        let customer = Customer {
+            id: Uuid::new_v4(),
+            username: "ada".to_string(),
+            password_hash: "not-a-real-hash".to_string(),
+            email: "ada@example.com".to_string(),
+            created_at: None,
+        };
+        let product = Product {
+            id: Uuid::new_v4(),
+            name: "Keyboard".to_string(),
+            is_available: true,
+            price: 4999,
+            sku: None,
+            stock: None,
+        };
+        customers.insert(customer.clone()).await.unwrap();
+        products.insert(product.clone()).await.unwrap();
+        let repository = InMemoryOrderRepository::new(customers, products);
+        let start = chrono::Utc::now().naive_utc();
+        let mut ids = Vec::new();
+        for minute in 0..count {
+            let order = Order {
+                id: Uuid::new_v4(),
+                customer_id: customer.id,
+                status: OrderStatus::Pending,
+                created_at: start + chrono::Duration::minutes(minute),
+                product_id: product.id,
+            };
+            ids.push(order.id);
+            repository.insert(order).await.unwrap();
+        }
+        (repository, ids)
+    }
+
+    fn request(sort: OrderSort, cursor: Option<&str>) -> OrderPageRequest {
+        OrderPageRequest {
+            filter: OrderFilter::default(),
+            sort,
+            limit: 2,
+            cursor: cursor.map(|cursor| OrderCursor::decode(cursor).unwrap()),
+        }
+    }
+
+    fn ids(page: &super::OrderPage) -> Vec<Uuid> {
+        page.orders.iter().map(|order| order.id).collect()
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = OrderCursor {
+            direction: Direction::Prev,
+            sort: OrderSort::Oldest,
+            created_at: chrono::Utc::now().naive_utc(),
+            id: Uuid::new_v4(),
+        };
+        assert_eq!(OrderCursor::decode(&cursor.encode()), Ok(cursor));
+        assert!(OrderCursor::decode("not-a-cursor").is_err());
+        assert!(OrderCursor::decode(&hex::encode("n:d:1.0:nope")).is_err());
+    }
+
+    #[tokio::test]
+    async fn pages_forward_and_back() {
+        let (repository, ids_oldest_first) = repository_with(5).await;
+
+        let first = repository
+            .list_page(&request(OrderSort::Oldest, None))
+            .await
+            .unwrap();
+        assert_eq!(ids(&first), ids_oldest_first[0..2]);
+        assert!(first.prev_cursor.is_none());
+
+        let second = repository
+            .list_page(&request(OrderSort::Oldest, first.next_cursor.as_deref()))
+            .await
+            .unwrap();
+        assert_eq!(ids(&second), ids_oldest_first[2..4]);
+
+        let last = repository
+            .list_page(&request(OrderSort::Oldest, second.next_cursor.as_deref()))
+            .await
+            .unwrap();
+        assert_eq!(ids(&last), ids_oldest_first[4..5]);
+        assert!(last.next_cursor.is_none());
+
+        let back = repository
+            .list_page(&request(OrderSort::Oldest, last.prev_cursor.as_deref()))
+            .await
+            .unwrap();
+        assert_eq!(ids(&back), ids_oldest_first[2..4]);
+        let start = repository
+            .list_page(&request(OrderSort::Oldest, back.prev_cursor.as_deref()))
+            .await
+            .unwrap();
+        assert_eq!(ids(&start), ids_oldest_first[0..2]);
+        assert!(start.prev_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn newest_first_and_filters() {
+        let (repository, ids_oldest_first) = repository_with(3).await;
+        let page = repository
+            .list_page(&request(OrderSort::Newest, None))
+            .await
+            .unwrap();
+        assert_eq!(ids(&page), vec![ids_oldest_first[2], ids_oldest_first[1]]);
+        assert_eq!(page.orders[0].product.name, "Keyboard");
+        assert!(page.orders[0].customer.is_some());
+        assert!(page
+            .without_customers()
+            .orders
+            .iter()
+            .all(|order| order.customer.is_none()));
+
+        repository.record_payment(ids_oldest_first[0], PaymentStatus::Captured);
+        repository
+            .update_status(ids_oldest_first[0], OrderStatus::Shipped)
+            .await
+            .unwrap();
+        let mut shipped = request(OrderSort::Newest, None);
+        shipped.filter.status = Some(OrderStatus::Shipped);
+        let page = repository.list_page(&shipped).await.unwrap();
+        assert_eq!(ids(&page), vec![ids_oldest_first[0]]);
+        assert!(page.next_cursor.is_none() && page.prev_cursor.is_none());
+    }
+}
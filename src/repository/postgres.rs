@@ -0,0 +1,659 @@
+use super::details::{
+    CustomerSummary, OrderCancellation, OrderDetail, OrderTotals, ProductSummary,
+};
+use super::pagination::{OrderPage, OrderPageRequest};
+use super::{
+    check_transition, not_captured, AdminRepository, CancelOutcome, CustomerRepository,
+    OrderRepository, ProductRepository,
+};
+use crate::db::PgPool;
+use crate::db_models::{Admin, Customer, Order, Product};
+use crate::errors::custom::{CustomError, DbError};
+use crate::outbox::{self, DomainEvent};
+use crate::payments;
+use crate::routes::order::order::OrderStatus;
+use crate::schema::admins::dsl as admin_dsl;
+use crate::schema::customers::dsl as customer_dsl;
+use crate::schema::order_cancellations::dsl as cancellation_dsl;
+use crate::schema::order_totals::dsl as totals_dsl;
+use crate::schema::orders;
+use crate::schema::orders::dsl as order_dsl;
+use crate::schema::products::dsl as product_dsl;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::pooled_connection::deadpool::Object;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use uuid::Uuid;
+
+async fn connection(pool: &PgPool) -> Result<Object<AsyncPgConnection>, CustomError> {
+    pool.get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))
+}
+
+fn query_error(err: diesel::result::Error) -> CustomError {
+    CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string()))
+}
+
+// What the order detail queries select; see `OrderDetail::from_parts`
+type DetailRow = (
+    Order,
+    ProductSummary,
+    CustomerSummary,
+    Option<OrderTotals>,
+    Option<OrderCancellation>,
+);
+
+// Inserts must touch exactly one row
+fn expect_inserted(result: usize) -> Result<(), CustomError> {
+    if result == 0 {
+        return Err(CustomError::DatabaseError(DbError::InsertionError(
+            "Failed data insertion in db".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/******************************************/
+// Customers
+/******************************************/
+pub struct PgCustomerRepository {
+    pool: PgPool,
+}
+
+impl PgCustomerRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl CustomerRepository for PgCustomerRepository {
+    async fn insert(&self, customer: Customer) -> Result<(), CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let event = DomainEvent::CustomerRegistered {
+            customer_id: customer.id,
+            username: customer.username.clone(),
+            email: customer.email.clone(),
+        };
+        let result = conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    let result = diesel::insert_into(customer_dsl::customers)
+                        .values((
+                            customer_dsl::id.eq(customer.id),
+                            customer_dsl::username.eq(customer.username),
+                            customer_dsl::password_hash.eq(customer.password_hash),
+                            customer_dsl::email.eq(customer.email),
+                        ))
+                        .execute(conn)
+                        .await?;
+                    outbox::record(conn, &event).await?;
+                    Ok(result)
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(query_error)?;
+        expect_inserted(result)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Customer>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        customer_dsl::customers
+            .find(id)
+            .first::<Customer>(&mut conn)
+            .await
+            .optional()
+            .map_err(query_error)
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<Customer>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        customer_dsl::customers
+            .filter(customer_dsl::username.eq(username))
+            .first::<Customer>(&mut conn)
+            .await
+            .optional()
+            .map_err(query_error)
+    }
+
+    async fn update_profile(
+        &self,
+        id: Uuid,
+        username: &str,
+        email: &str,
+    ) -> Result<bool, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let result = diesel::update(customer_dsl::customers.find(id))
+            .set((
+                customer_dsl::username.eq(username),
+                customer_dsl::email.eq(email),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(query_error)?;
+        Ok(result > 0)
+    }
+
+    async fn update_password_hash(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<bool, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let result =
+            diesel::update(customer_dsl::customers.filter(customer_dsl::username.eq(username)))
+                .set(customer_dsl::password_hash.eq(password_hash))
+                .execute(&mut conn)
+                .await
+                .map_err(query_error)?;
+        Ok(result > 0)
+    }
+}
+
+/******************************************/
+// Admins
+/******************************************/
+pub struct PgAdminRepository {
+    pool: PgPool,
+}
+
+impl PgAdminRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AdminRepository for PgAdminRepository {
+    async fn insert(&self, admin: Admin) -> Result<(), CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let result = diesel::insert_into(admin_dsl::admins)
+            .values((
+                admin_dsl::id.eq(admin.id),
+                admin_dsl::username.eq(admin.username),
+                admin_dsl::password_hash.eq(admin.password_hash),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(query_error)?;
+        expect_inserted(result)
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<Admin>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        admin_dsl::admins
+            .filter(admin_dsl::username.eq(username))
+            .first::<Admin>(&mut conn)
+            .await
+            .optional()
+            .map_err(query_error)
+    }
+
+    async fn update_password_hash(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<bool, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let result = diesel::update(admin_dsl::admins.filter(admin_dsl::username.eq(username)))
+            .set(admin_dsl::password_hash.eq(password_hash))
+            .execute(&mut conn)
+            .await
+            .map_err(query_error)?;
+        Ok(result > 0)
+    }
+}
+
+/******************************************/
+// Stock
+/******************************************/
+// Takes the unit an order holds. The product row stays locked until the transaction ends,
+// so two orders can't both take the last unit. Products without a stock level never run
+// out; an unknown product is left to the order's foreign key.
+async fn reserve_stock(conn: &mut AsyncPgConnection, product_id: Uuid) -> Result<(), CustomError> {
+    let stock = product_dsl::products
+        .find(product_id)
+        .select(product_dsl::stock)
+        .for_update()
+        .first::<Option<i32>>(conn)
+        .await
+        .optional()?
+        .flatten();
+    match stock {
+        Some(0) => Err(CustomError::ConflictError(format!(
+            "Product {} is out of stock",
+            product_id
+        ))),
+        Some(_) => {
+            diesel::update(product_dsl::products.find(product_id))
+                .set(product_dsl::stock.eq(product_dsl::stock - 1))
+                .execute(conn)
+                .await?;
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+// Puts back units an order held, on cancellation or an accepted return; a no-op for
+// products whose stock isn't tracked
+pub(crate) async fn return_to_stock(
+    conn: &mut AsyncPgConnection,
+    product_id: Uuid,
+    units: i32,
+) -> QueryResult<()> {
+    diesel::update(
+        product_dsl::products
+            .find(product_id)
+            .filter(product_dsl::stock.is_not_null()),
+    )
+    .set(product_dsl::stock.eq(product_dsl::stock + units))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/******************************************/
+// Orders
+/******************************************/
+pub struct PgOrderRepository {
+    pool: PgPool,
+}
+
+impl PgOrderRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // The order, its totals if any and its `OrderPlaced` event are written together or not at all
+    async fn insert_order(
+        &self,
+        order: Order,
+        totals: Option<OrderTotals>,
+    ) -> Result<(), CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let event = DomainEvent::OrderPlaced {
+            order_id: order.id,
+            customer_id: order.customer_id,
+            product_id: order.product_id,
+            status: order.status,
+            created_at: order.created_at,
+        };
+        let result = conn
+            .transaction::<_, CustomError, _>(|conn| {
+                async move {
+                    reserve_stock(conn, order.product_id).await?;
+                    let result = diesel::insert_into(order_dsl::orders)
+                        .values((
+                            order_dsl::id.eq(order.id),
+                            order_dsl::customer_id.eq(order.customer_id),
+                            order_dsl::product_id.eq(order.product_id),
+                            order_dsl::created_at.eq(order.created_at),
+                            order_dsl::status.eq(order.status),
+                        ))
+                        .execute(conn)
+                        .await?;
+                    if let Some(totals) = totals {
+                        diesel::insert_into(totals_dsl::order_totals)
+                            .values((
+                                totals_dsl::order_id.eq(order.id),
+                                totals_dsl::unit_price.eq(totals.unit_price),
+                                totals_dsl::subtotal.eq(totals.subtotal),
+                                totals_dsl::discount.eq(totals.discount),
+                                totals_dsl::shipping.eq(totals.shipping),
+                                totals_dsl::tax.eq(totals.tax),
+                                totals_dsl::grand_total.eq(totals.grand_total),
+                                totals_dsl::tax_region.eq(totals.tax_region),
+                                totals_dsl::tax_rate_bps.eq(totals.tax_rate_bps),
+                            ))
+                            .execute(conn)
+                            .await?;
+                    }
+                    outbox::record(conn, &event).await?;
+                    Ok(result)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        expect_inserted(result)
+    }
+}
+
+#[async_trait]
+impl OrderRepository for PgOrderRepository {
+    async fn insert(&self, order: Order) -> Result<(), CustomError> {
+        self.insert_order(order, None).await
+    }
+
+    async fn insert_with_totals(
+        &self,
+        order: Order,
+        totals: OrderTotals,
+    ) -> Result<(), CustomError> {
+        self.insert_order(order, Some(totals)).await
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Order>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        order_dsl::orders
+            .find(id)
+            .first::<Order>(&mut conn)
+            .await
+            .optional()
+            .map_err(query_error)
+    }
+
+    async fn find_detail(&self, id: Uuid) -> Result<Option<OrderDetail>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let row = order_dsl::orders
+            .inner_join(product_dsl::products)
+            .inner_join(customer_dsl::customers)
+            .left_join(totals_dsl::order_totals)
+            .left_join(cancellation_dsl::order_cancellations)
+            .filter(order_dsl::id.eq(id))
+            .select((
+                orders::all_columns,
+                (product_dsl::id, product_dsl::name, product_dsl::price),
+                (
+                    customer_dsl::id,
+                    customer_dsl::username,
+                    customer_dsl::email,
+                ),
+                (
+                    totals_dsl::unit_price,
+                    totals_dsl::subtotal,
+                    totals_dsl::discount,
+                    totals_dsl::shipping,
+                    totals_dsl::tax,
+                    totals_dsl::grand_total,
+                    totals_dsl::tax_region,
+                    totals_dsl::tax_rate_bps,
+                )
+                    .nullable(),
+                (
+                    cancellation_dsl::reason,
+                    cancellation_dsl::cancelled_at,
+                    cancellation_dsl::admin_id,
+                )
+                    .nullable(),
+            ))
+            .first::<DetailRow>(&mut conn)
+            .await
+            .optional()
+            .map_err(query_error)?;
+        Ok(row.map(|(order, product, customer, totals, cancellation)| {
+            OrderDetail::from_parts(order, product, customer, totals, cancellation)
+        }))
+    }
+
+    async fn list_page(&self, request: &OrderPageRequest) -> Result<OrderPage, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let filter = &request.filter;
+        let mut query = order_dsl::orders
+            .inner_join(product_dsl::products)
+            .inner_join(customer_dsl::customers)
+            .left_join(totals_dsl::order_totals)
+            .left_join(cancellation_dsl::order_cancellations)
+            .select((
+                orders::all_columns,
+                (product_dsl::id, product_dsl::name, product_dsl::price),
+                (
+                    customer_dsl::id,
+                    customer_dsl::username,
+                    customer_dsl::email,
+                ),
+                (
+                    totals_dsl::unit_price,
+                    totals_dsl::subtotal,
+                    totals_dsl::discount,
+                    totals_dsl::shipping,
+                    totals_dsl::tax,
+                    totals_dsl::grand_total,
+                    totals_dsl::tax_region,
+                    totals_dsl::tax_rate_bps,
+                )
+                    .nullable(),
+                (
+                    cancellation_dsl::reason,
+                    cancellation_dsl::cancelled_at,
+                    cancellation_dsl::admin_id,
+                )
+                    .nullable(),
+            ))
+            .into_boxed();
+        if let Some(status) = filter.status {
+            query = query.filter(order_dsl::status.eq(status));
+        }
+        if let Some(customer_id) = filter.customer_id {
+            query = query.filter(order_dsl::customer_id.eq(customer_id));
+        }
+        if let Some(product_id) = filter.product_id {
+            query = query.filter(order_dsl::product_id.eq(product_id));
+        }
+        if let Some(from) = filter.created_from {
+            query = query.filter(order_dsl::created_at.ge(from));
+        }
+        if let Some(to) = filter.created_to {
+            query = query.filter(order_dsl::created_at.lt(to));
+        }
+        // `(created_at, id) > cursor`, written out since Diesel has no row comparison
+        query = match (request.cursor, request.scans_ascending()) {
+            (None, _) => query,
+            (Some(cursor), true) => query.filter(
+                order_dsl::created_at
+                    .gt(cursor.created_at)
+                    .or(order_dsl::created_at
+                        .eq(cursor.created_at)
+                        .and(order_dsl::id.gt(cursor.id))),
+            ),
+            (Some(cursor), false) => query.filter(
+                order_dsl::created_at
+                    .lt(cursor.created_at)
+                    .or(order_dsl::created_at
+                        .eq(cursor.created_at)
+                        .and(order_dsl::id.lt(cursor.id))),
+            ),
+        };
+        query = if request.scans_ascending() {
+            query.order((order_dsl::created_at.asc(), order_dsl::id.asc()))
+        } else {
+            query.order((order_dsl::created_at.desc(), order_dsl::id.desc()))
+        };
+        let rows = query
+            .limit(request.limit + 1)
+            .load::<DetailRow>(&mut conn)
+            .await
+            .map_err(query_error)?;
+        Ok(request.into_page(
+            rows.into_iter()
+                .map(|(order, product, customer, totals, cancellation)| {
+                    OrderDetail::from_parts(order, product, customer, totals, cancellation)
+                })
+                .collect(),
+        ))
+    }
+
+    async fn list(&self, status: Option<OrderStatus>) -> Result<Vec<Order>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let mut query = order_dsl::orders
+            .order(order_dsl::created_at.asc())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(order_dsl::status.eq(status));
+        }
+        query.load::<Order>(&mut conn).await.map_err(query_error)
+    }
+
+    async fn update_status(&self, id: Uuid, status: OrderStatus) -> Result<bool, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        conn.transaction::<_, CustomError, _>(|conn| {
+            async move {
+                // Locked so concurrent updates record the transitions in the order they happen,
+                // and a refund can't slip in between the payment check and the shipment
+                let current = order_dsl::orders
+                    .find(id)
+                    .for_update()
+                    .first::<Order>(conn)
+                    .await
+                    .optional()?;
+                let Some(current) = current else {
+                    return Ok(false);
+                };
+                check_transition(id, current.status, status)?;
+                if matches!(status, OrderStatus::Shipped | OrderStatus::Delivered)
+                    && !payments::live_payments(conn, id)
+                        .await?
+                        .into_iter()
+                        .any(|payment| payment.is_captured())
+                {
+                    return Err(not_captured(id));
+                }
+                diesel::update(order_dsl::orders.find(id))
+                    .set(order_dsl::status.eq(status))
+                    .execute(conn)
+                    .await?;
+                if current.status != status {
+                    let event = DomainEvent::OrderStatusChanged {
+                        order_id: id,
+                        customer_id: current.customer_id,
+                        from: current.status,
+                        to: status,
+                    };
+                    outbox::record(conn, &event).await?;
+                }
+                Ok(true)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn cancel(
+        &self,
+        id: Uuid,
+        reason: &str,
+        admin_id: Option<Uuid>,
+    ) -> Result<CancelOutcome, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let reason = reason.to_string();
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                // Locked so a concurrent shipment either lands first and wins, or waits
+                let current = order_dsl::orders
+                    .find(id)
+                    .for_update()
+                    .first::<Order>(conn)
+                    .await
+                    .optional()?;
+                let Some(current) = current else {
+                    return Ok(CancelOutcome::NotFound);
+                };
+                if current.status != OrderStatus::Pending {
+                    return Ok(CancelOutcome::NotCancellable(current.status));
+                }
+                // Authorizations lock the order too, so none can be recorded after this check
+                let live = payments::live_payments(conn, id).await?;
+                if let Some(payment) = live.into_iter().next() {
+                    return Ok(CancelOutcome::PaymentOutstanding(payment));
+                }
+                diesel::update(order_dsl::orders.find(id))
+                    .set(order_dsl::status.eq(OrderStatus::Cancelled))
+                    .execute(conn)
+                    .await?;
+                return_to_stock(conn, current.product_id, 1).await?;
+                diesel::insert_into(cancellation_dsl::order_cancellations)
+                    .values((
+                        cancellation_dsl::order_id.eq(id),
+                        cancellation_dsl::reason.eq(reason),
+                        cancellation_dsl::admin_id.eq(admin_id),
+                        cancellation_dsl::cancelled_at.eq(chrono::Utc::now().naive_utc()),
+                    ))
+                    .execute(conn)
+                    .await?;
+                let event = DomainEvent::OrderStatusChanged {
+                    order_id: id,
+                    customer_id: current.customer_id,
+                    from: current.status,
+                    to: OrderStatus::Cancelled,
+                };
+                outbox::record(conn, &event).await?;
+                Ok(CancelOutcome::Cancelled)
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(query_error)
+    }
+}
+
+/******************************************/
+// Products
+/******************************************/
+pub struct PgProductRepository {
+    pool: PgPool,
+}
+
+impl PgProductRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProductRepository for PgProductRepository {
+    async fn insert(&self, product: Product) -> Result<(), CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let result = diesel::insert_into(product_dsl::products)
+            .values((
+                product_dsl::id.eq(product.id),
+                product_dsl::sku.eq(product.sku),
+                product_dsl::name.eq(product.name),
+                product_dsl::is_available.eq(product.is_available),
+                product_dsl::price.eq(product.price),
+                product_dsl::stock.eq(product.stock),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(query_error)?;
+        expect_inserted(result)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Product>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        product_dsl::products
+            .find(id)
+            .first::<Product>(&mut conn)
+            .await
+            .optional()
+            .map_err(query_error)
+    }
+
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        product_dsl::products
+            .filter(product_dsl::sku.eq(sku))
+            .first::<Product>(&mut conn)
+            .await
+            .optional()
+            .map_err(query_error)
+    }
+
+    async fn update(&self, product: Product) -> Result<bool, CustomError> {
+        let mut conn = connection(&self.pool).await?;
+        let result = diesel::update(product_dsl::products.find(product.id))
+            .set((
+                product_dsl::sku.eq(product.sku),
+                product_dsl::name.eq(product.name),
+                product_dsl::is_available.eq(product.is_available),
+                product_dsl::price.eq(product.price),
+                product_dsl::stock.eq(product.stock),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(query_error)?;
+        Ok(result > 0)
+    }
+}
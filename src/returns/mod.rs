@@ -0,0 +1,767 @@
+use crate::db::PgPool;
+use crate::db_models::{Order, OutboxEvent, Payment, Refund, ReturnItem, ReturnRequest};
+use crate::errors::custom::{CustomError, DbError};
+use crate::outbox::{self, DomainEvent, EventEnvelope};
+use crate::payments::{self, PaymentProvider, PaymentStatus};
+use crate::repository::postgres::return_to_stock;
+use crate::routes::order::order::OrderStatus;
+use crate::schema::order_cancellations::dsl as cancellation_dsl;
+use crate::schema::order_totals::dsl as totals_dsl;
+use crate::schema::orders::dsl as order_dsl;
+use crate::schema::outbox_events::dsl as outbox_dsl;
+use crate::schema::payments::dsl as payment_dsl;
+use crate::schema::products::dsl as product_dsl;
+use crate::schema::refunds::dsl as refund_dsl;
+use crate::schema::return_items::dsl as item_dsl;
+use crate::schema::returns::dsl as return_dsl;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// Longest reason or note accepted, in characters
+const MAX_TEXT_LENGTH: usize = 500;
+// Most timeline entries returned for one order
+const TIMELINE_LIMIT: i64 = 500;
+
+/******************************************/
+// Return states
+/******************************************/
+// Requested -> Approved -> Received -> Accepted (refunded) or Declined;
+// Requested -> Rejected. Rejected and Declined returns free the order for another request.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, diesel_derive_enum::DbEnum, Serialize, Deserialize, ToSchema,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::ReturnStatus"]
+pub enum ReturnStatus {
+    Requested,
+    Approved,
+    Rejected,
+    // The goods are back and waiting for inspection
+    Received,
+    // Inspected and refunded
+    Accepted,
+    // Inspected and turned down
+    Declined,
+}
+
+/// An admin's step in the return workflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum ReturnAction {
+    Approve,
+    Reject,
+    Receive,
+    Accept,
+    Decline,
+}
+
+impl ReturnStatus {
+    // Where `action` takes a return in this status; `None` when it isn't allowed
+    pub fn after(self, action: ReturnAction) -> Option<ReturnStatus> {
+        match (self, action) {
+            (ReturnStatus::Requested, ReturnAction::Approve) => Some(ReturnStatus::Approved),
+            (ReturnStatus::Requested, ReturnAction::Reject) => Some(ReturnStatus::Rejected),
+            (ReturnStatus::Approved, ReturnAction::Receive) => Some(ReturnStatus::Received),
+            (ReturnStatus::Received, ReturnAction::Accept) => Some(ReturnStatus::Accepted),
+            (ReturnStatus::Received, ReturnAction::Decline) => Some(ReturnStatus::Declined),
+            _ => None,
+        }
+    }
+}
+
+/// One product being sent back and why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReturnedItem {
+    pub product_id: Uuid,
+    pub reason: String,
+}
+
+impl From<ReturnItem> for ReturnedItem {
+    fn from(item: ReturnItem) -> Self {
+        Self {
+            product_id: item.product_id,
+            reason: item.reason,
+        }
+    }
+}
+
+// Trims and checks the free text a customer or admin sends along
+pub fn validate_text(text: &str, what: &str) -> Result<String, CustomError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(CustomError::ValidationError(format!(
+            "{} is required",
+            what
+        )));
+    }
+    if text.chars().count() > MAX_TEXT_LENGTH {
+        return Err(CustomError::ValidationError(format!(
+            "{} is longer than {} characters",
+            what, MAX_TEXT_LENGTH
+        )));
+    }
+    Ok(text.to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReturnDetail {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    pub status: ReturnStatus,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub items: Vec<ReturnedItem>,
+    /// Present once the return is `Accepted`
+    pub refund: Option<Refund>,
+}
+
+impl ReturnDetail {
+    fn from_parts(request: ReturnRequest, items: Vec<ReturnItem>, refund: Option<Refund>) -> Self {
+        Self {
+            id: request.id,
+            order_id: request.order_id,
+            customer_id: request.customer_id,
+            status: request.status,
+            note: request.note,
+            created_at: request.created_at,
+            updated_at: request.updated_at,
+            items: items.into_iter().map(ReturnedItem::from).collect(),
+            refund,
+        }
+    }
+}
+
+async fn connection(
+    pool: &PgPool,
+) -> Result<diesel_async::pooled_connection::deadpool::Object<AsyncPgConnection>, CustomError> {
+    pool.get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))
+}
+
+async fn load_detail(
+    conn: &mut AsyncPgConnection,
+    request: ReturnRequest,
+) -> Result<ReturnDetail, CustomError> {
+    let items = item_dsl::return_items
+        .filter(item_dsl::return_id.eq(request.id))
+        .order(item_dsl::product_id.asc())
+        .load::<ReturnItem>(conn)
+        .await?;
+    let refund = refund_dsl::refunds
+        .filter(refund_dsl::return_id.eq(request.id))
+        .first::<Refund>(conn)
+        .await
+        .optional()?;
+    Ok(ReturnDetail::from_parts(request, items, refund))
+}
+
+/******************************************/
+// Requesting a return
+/******************************************/
+// Only the customer's own delivered orders, for products on the order, one reason each
+pub async fn request_return(
+    pool: &PgPool,
+    customer_id: Uuid,
+    order_id: Uuid,
+    items: Vec<ReturnedItem>,
+) -> Result<ReturnDetail, CustomError> {
+    if items.is_empty() {
+        return Err(CustomError::ValidationError(
+            "At least one item must be returned".to_string(),
+        ));
+    }
+    let items = items
+        .into_iter()
+        .map(|item| {
+            Ok(ReturnedItem {
+                product_id: item.product_id,
+                reason: validate_text(&item.reason, "Return reason")?,
+            })
+        })
+        .collect::<Result<Vec<_>, CustomError>>()?;
+
+    let mut conn = connection(pool).await?;
+    conn.transaction::<_, CustomError, _>(|conn| {
+        async move {
+            // Locked so two requests for the same order can't both pass the checks below
+            let order = order_dsl::orders
+                .find(order_id)
+                .for_update()
+                .first::<Order>(conn)
+                .await
+                .optional()?
+                .filter(|order| order.customer_id == customer_id)
+                .ok_or_else(|| CustomError::NotFoundError(format!("Order {}", order_id)))?;
+            if order.status != OrderStatus::Delivered {
+                return Err(CustomError::ConflictError(format!(
+                    "Order {} is {:?}; only delivered orders can be returned",
+                    order_id, order.status
+                )));
+            }
+            // Orders carry one unit of a single product for now, so a return holds at most
+            // one item; `apply_action` refunds and restocks on that basis
+            for (index, item) in items.iter().enumerate() {
+                if item.product_id != order.product_id {
+                    return Err(CustomError::ValidationError(format!(
+                        "Product {} is not part of order {}",
+                        item.product_id, order_id
+                    )));
+                }
+                if items[..index]
+                    .iter()
+                    .any(|other| other.product_id == item.product_id)
+                {
+                    return Err(CustomError::ValidationError(format!(
+                        "Product {} is listed more than once",
+                        item.product_id
+                    )));
+                }
+            }
+            let open = return_dsl::returns
+                .filter(return_dsl::order_id.eq(order_id))
+                .filter(
+                    return_dsl::status.ne_all(vec![ReturnStatus::Rejected, ReturnStatus::Declined]),
+                )
+                .count()
+                .get_result::<i64>(conn)
+                .await?;
+            if open > 0 {
+                return Err(CustomError::ConflictError(format!(
+                    "Order {} already has a return in progress",
+                    order_id
+                )));
+            }
+
+            let now = chrono::Utc::now().naive_utc();
+            let request = diesel::insert_into(return_dsl::returns)
+                .values((
+                    return_dsl::id.eq(Uuid::new_v4()),
+                    return_dsl::order_id.eq(order_id),
+                    return_dsl::customer_id.eq(customer_id),
+                    return_dsl::status.eq(ReturnStatus::Requested),
+                    return_dsl::created_at.eq(now),
+                    return_dsl::updated_at.eq(now),
+                ))
+                .get_result::<ReturnRequest>(conn)
+                .await?;
+            let rows: Vec<_> = items
+                .iter()
+                .map(|item| {
+                    (
+                        item_dsl::return_id.eq(request.id),
+                        item_dsl::product_id.eq(item.product_id),
+                        item_dsl::reason.eq(&item.reason),
+                    )
+                })
+                .collect();
+            diesel::insert_into(item_dsl::return_items)
+                .values(rows)
+                .execute(conn)
+                .await?;
+            let event = DomainEvent::ReturnRequested {
+                return_id: request.id,
+                order_id,
+                customer_id,
+                items: items.clone(),
+            };
+            outbox::record(conn, &event).await?;
+            load_detail(conn, request).await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/******************************************/
+// Moving a return along
+/******************************************/
+// Applies an admin's action; accepting also refunds the unit through the payment provider
+// and restocks it. Every step is recorded in the outbox against the order, which is what the
+// order timeline shows.
+pub async fn apply_action(
+    pool: &PgPool,
+    provider: &dyn PaymentProvider,
+    return_id: Uuid,
+    action: ReturnAction,
+    admin_id: Uuid,
+    note: Option<String>,
+) -> Result<ReturnDetail, CustomError> {
+    let note = note.map(|note| validate_text(&note, "Note")).transpose()?;
+    // The money goes back before the return is marked accepted, the same way admins refund
+    // a payment, so an accepted return has always been refunded
+    let refunded = match action {
+        ReturnAction::Accept => Some(refund_unit(pool, provider, return_id).await?),
+        _ => None,
+    };
+    let mut conn = connection(pool).await?;
+    conn.transaction::<_, CustomError, _>(|conn| {
+        async move {
+            let current = return_dsl::returns
+                .find(return_id)
+                .for_update()
+                .first::<ReturnRequest>(conn)
+                .await
+                .optional()?
+                .ok_or_else(|| CustomError::NotFoundError(format!("Return {}", return_id)))?;
+            let next = current.status.after(action).ok_or_else(|| {
+                if let Some(refunded) = refunded {
+                    tracing::error!(
+                        %return_id,
+                        payment_id = %refunded.payment_id,
+                        amount = refunded.amount,
+                        "Refunded a return that moved on meanwhile; reconcile the payment"
+                    );
+                }
+                CustomError::ConflictError(format!(
+                    "Cannot {:?} a return that is {:?}",
+                    action, current.status
+                ))
+            })?;
+            // A step without a note keeps the one already there
+            let updated = diesel::update(return_dsl::returns.find(return_id))
+                .set((
+                    return_dsl::status.eq(next),
+                    return_dsl::note.eq(note.as_deref().or(current.note.as_deref())),
+                    return_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .get_result::<ReturnRequest>(conn)
+                .await?;
+
+            // The product is locked before any event is recorded, the same order
+            // `insert_order` takes them in
+            let refund = match refunded {
+                Some(refunded) => {
+                    let product_id = order_dsl::orders
+                        .find(current.order_id)
+                        .select(order_dsl::product_id)
+                        .first::<Uuid>(conn)
+                        .await?;
+                    return_to_stock(conn, product_id, 1).await?;
+                    let refund = diesel::insert_into(refund_dsl::refunds)
+                        .values((
+                            refund_dsl::id.eq(Uuid::new_v4()),
+                            refund_dsl::return_id.eq(return_id),
+                            refund_dsl::order_id.eq(current.order_id),
+                            refund_dsl::amount.eq(refunded.amount),
+                            refund_dsl::created_at.eq(chrono::Utc::now().naive_utc()),
+                        ))
+                        .get_result::<Refund>(conn)
+                        .await?;
+                    Some(refund)
+                }
+                None => None,
+            };
+
+            let event = DomainEvent::ReturnStatusChanged {
+                return_id,
+                order_id: current.order_id,
+                customer_id: current.customer_id,
+                from: current.status,
+                to: next,
+                admin_id,
+                note: note.clone(),
+            };
+            outbox::record(conn, &event).await?;
+            if let Some(refund) = refund {
+                let event = DomainEvent::RefundCreated {
+                    refund_id: refund.id,
+                    return_id,
+                    order_id: current.order_id,
+                    customer_id: current.customer_id,
+                    amount: refund.amount,
+                };
+                outbox::record(conn, &event).await?;
+            }
+            load_detail(conn, updated).await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+// What `refund_unit` gave back, and from which payment
+#[derive(Debug, Clone, Copy)]
+struct UnitRefund {
+    payment_id: Uuid,
+    amount: i32,
+}
+
+// A return holds the order's single unit (see `request_return`), so accepting it refunds
+// what was paid for that unit: the goods after discount plus their tax, but not shipping.
+// Orders without a price snapshot fall back to today's price. Nothing is refunded unless the
+// return is waiting for inspection.
+async fn refund_unit(
+    pool: &PgPool,
+    provider: &dyn PaymentProvider,
+    return_id: Uuid,
+) -> Result<UnitRefund, CustomError> {
+    let mut conn = connection(pool).await?;
+    let current = return_dsl::returns
+        .find(return_id)
+        .first::<ReturnRequest>(&mut conn)
+        .await
+        .optional()?
+        .ok_or_else(|| CustomError::NotFoundError(format!("Return {}", return_id)))?;
+    if current.status.after(ReturnAction::Accept).is_none() {
+        return Err(CustomError::ConflictError(format!(
+            "Cannot {:?} a return that is {:?}",
+            ReturnAction::Accept,
+            current.status
+        )));
+    }
+    let (price, paid) = order_dsl::orders
+        .inner_join(product_dsl::products)
+        .left_join(totals_dsl::order_totals)
+        .filter(order_dsl::id.eq(current.order_id))
+        .select((
+            product_dsl::price,
+            (totals_dsl::grand_total - totals_dsl::shipping).nullable(),
+        ))
+        .first::<(i32, Option<i32>)>(&mut conn)
+        .await?;
+    let payment = payment_dsl::payments
+        .filter(payment_dsl::order_id.eq(current.order_id))
+        .filter(payment_dsl::status.eq_any(vec![
+            PaymentStatus::Captured,
+            PaymentStatus::PartiallyRefunded,
+        ]))
+        .first::<Payment>(&mut conn)
+        .await
+        .optional()?
+        .ok_or_else(|| {
+            CustomError::ConflictError(format!(
+                "Order {} has no captured payment to refund",
+                current.order_id
+            ))
+        })?;
+    drop(conn);
+
+    let amount = paid
+        .unwrap_or(price)
+        .min(payment.amount - payment.refunded_amount);
+    payments::refund(pool, provider, payment.id, Some(amount)).await?;
+    Ok(UnitRefund {
+        payment_id: payment.id,
+        amount,
+    })
+}
+
+/******************************************/
+// Reading returns
+/******************************************/
+pub async fn find_return(
+    pool: &PgPool,
+    return_id: Uuid,
+) -> Result<Option<ReturnDetail>, CustomError> {
+    let mut conn = connection(pool).await?;
+    let request = return_dsl::returns
+        .find(return_id)
+        .first::<ReturnRequest>(&mut conn)
+        .await
+        .optional()?;
+    match request {
+        Some(request) => Ok(Some(load_detail(&mut conn, request).await?)),
+        None => Ok(None),
+    }
+}
+
+// Oldest first
+pub async fn list_for_order(
+    pool: &PgPool,
+    order_id: Uuid,
+) -> Result<Vec<ReturnDetail>, CustomError> {
+    let mut conn = connection(pool).await?;
+    let requests = return_dsl::returns
+        .filter(return_dsl::order_id.eq(order_id))
+        .order(return_dsl::created_at.asc())
+        .load::<ReturnRequest>(&mut conn)
+        .await?;
+    let mut details = Vec::with_capacity(requests.len());
+    for request in requests {
+        details.push(load_detail(&mut conn, request).await?);
+    }
+    Ok(details)
+}
+
+// Oldest first, for the admin work queue
+pub async fn list_returns(
+    pool: &PgPool,
+    status: Option<ReturnStatus>,
+    limit: i64,
+) -> Result<Vec<ReturnDetail>, CustomError> {
+    let mut conn = connection(pool).await?;
+    let mut query = return_dsl::returns
+        .order(return_dsl::created_at.asc())
+        .limit(limit)
+        .into_boxed();
+    if let Some(status) = status {
+        query = query.filter(return_dsl::status.eq(status));
+    }
+    let requests = query.load::<ReturnRequest>(&mut conn).await?;
+    let mut details = Vec::with_capacity(requests.len());
+    for request in requests {
+        details.push(load_detail(&mut conn, request).await?);
+    }
+    Ok(details)
+}
+
+/******************************************/
+// Order timeline
+/******************************************/
+/// What kind of step a timeline entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum TimelineKind {
+    OrderPlaced,
+    OrderStatusChanged,
+    ReturnRequested,
+    ReturnStatusChanged,
+    RefundCreated,
+    PaymentStatusChanged,
+}
+
+/// One step in an order's life as its customer sees it, oldest first. Who at the shop took
+/// the step, and their notes, are left out.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TimelineEntry {
+    pub event_id: Uuid,
+    pub occurred_at: NaiveDateTime,
+    pub kind: TimelineKind,
+    /// The status the order, return or payment moved to
+    pub status: Option<String>,
+    /// The reasons given for a return, or why the order was cancelled
+    pub reason: Option<String>,
+}
+
+// `OrderStatus::Shipped` as `"Shipped"`, matching how statuses appear everywhere else
+fn status_name<T: Serialize>(status: &T) -> Option<String> {
+    serde_json::to_value(status)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+}
+
+impl TimelineEntry {
+    // `None` for events that aren't part of an order's timeline
+    fn for_customer(envelope: &EventEnvelope, cancel_reason: Option<&str>) -> Option<Self> {
+        let (kind, status, reason) = match &envelope.event {
+            DomainEvent::OrderPlaced { status, .. } => {
+                (TimelineKind::OrderPlaced, status_name(status), None)
+            }
+            DomainEvent::OrderStatusChanged { to, .. } => (
+                TimelineKind::OrderStatusChanged,
+                status_name(to),
+                cancel_reason
+                    .filter(|_| *to == OrderStatus::Cancelled)
+                    .map(str::to_string),
+            ),
+            DomainEvent::ReturnRequested { items, .. } => (
+                TimelineKind::ReturnRequested,
+                status_name(&ReturnStatus::Requested),
+                Some(
+                    items
+                        .iter()
+                        .map(|item| item.reason.as_str())
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                ),
+            ),
+            DomainEvent::ReturnStatusChanged { to, .. } => {
+                (TimelineKind::ReturnStatusChanged, status_name(to), None)
+            }
+            DomainEvent::RefundCreated { .. } => (TimelineKind::RefundCreated, None, None),
+            DomainEvent::PaymentStatusChanged { to, .. } => {
+                (TimelineKind::PaymentStatusChanged, status_name(to), None)
+            }
+            DomainEvent::CustomerRegistered { .. } => return None,
+        };
+        Some(Self {
+            event_id: envelope.id,
+            occurred_at: envelope.occurred_at,
+            kind,
+            status,
+            reason,
+        })
+    }
+}
+
+/// One recorded event about an order with all of its fields, for admins
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub event_id: Uuid,
+    pub occurred_at: NaiveDateTime,
+    /// A domain event type, e.g. `OrderStatusChanged` or `ReturnStatusChanged`
+    pub event_type: String,
+    /// The event's fields
+    #[schema(value_type = Object)]
+    pub data: serde_json::Value,
+}
+
+impl AuditEntry {
+    fn from_row(row: OutboxEvent) -> Self {
+        // Payloads are `{"type": ..., "data": {...}}`, see `DomainEvent`
+        let data = row
+            .payload
+            .get("data")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        Self {
+            event_id: row.id,
+            occurred_at: row.created_at,
+            event_type: row.event_type,
+            data,
+        }
+    }
+}
+
+// Built from the outbox, where every change to an order and its returns is recorded in the
+// same transaction as the change itself
+async fn order_events(
+    conn: &mut AsyncPgConnection,
+    order_id: Uuid,
+) -> Result<Vec<OutboxEvent>, CustomError> {
+    let rows = outbox_dsl::outbox_events
+        .filter(outbox_dsl::aggregate_id.eq(order_id))
+        .order(outbox_dsl::seq.asc())
+        .limit(TIMELINE_LIMIT)
+        .load::<OutboxEvent>(conn)
+        .await?;
+    Ok(rows)
+}
+
+// What the customer is shown about their order
+pub async fn order_timeline(
+    pool: &PgPool,
+    order_id: Uuid,
+) -> Result<Vec<TimelineEntry>, CustomError> {
+    let mut conn = connection(pool).await?;
+    let rows = order_events(&mut conn, order_id).await?;
+    let cancel_reason = cancellation_dsl::order_cancellations
+        .find(order_id)
+        .select(cancellation_dsl::reason)
+        .first::<String>(&mut conn)
+        .await
+        .optional()?;
+    let mut timeline = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let envelope = EventEnvelope::from_row(row).map_err(|err| {
+            CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string()))
+        })?;
+        timeline.extend(TimelineEntry::for_customer(
+            &envelope,
+            cancel_reason.as_deref(),
+        ));
+    }
+    Ok(timeline)
+}
+
+// Every event as recorded, including which admin acted and their notes
+pub async fn order_audit_trail(
+    pool: &PgPool,
+    order_id: Uuid,
+) -> Result<Vec<AuditEntry>, CustomError> {
+    let mut conn = connection(pool).await?;
+    let rows = order_events(&mut conn, order_id).await?;
+    Ok(rows.into_iter().map(AuditEntry::from_row).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_text, ReturnAction, ReturnStatus, TimelineEntry, TimelineKind};
+    use crate::outbox::{DomainEvent, EventEnvelope};
+    use crate::routes::order::order::OrderStatus;
+    use uuid::Uuid;
+
+    fn envelope(event: DomainEvent) -> EventEnvelope {
+        EventEnvelope {
+            id: Uuid::new_v4(),
+            seq: 1,
+            occurred_at: chrono::Utc::now().naive_utc(),
+            event,
+        }
+    }
+
+    #[test]
+    fn returns_move_through_inspection_to_a_decision() {
+        let received = ReturnStatus::Requested
+            .after(ReturnAction::Approve)
+            .and_then(|status| status.after(ReturnAction::Receive));
+        assert_eq!(received, Some(ReturnStatus::Received));
+        assert_eq!(
+            ReturnStatus::Received.after(ReturnAction::Accept),
+            Some(ReturnStatus::Accepted)
+        );
+        assert_eq!(
+            ReturnStatus::Received.after(ReturnAction::Decline),
+            Some(ReturnStatus::Declined)
+        );
+        assert_eq!(
+            ReturnStatus::Requested.after(ReturnAction::Reject),
+            Some(ReturnStatus::Rejected)
+        );
+    }
+
+    #[test]
+    fn steps_cannot_be_skipped_or_repeated() {
+        assert_eq!(ReturnStatus::Requested.after(ReturnAction::Accept), None);
+        assert_eq!(ReturnStatus::Requested.after(ReturnAction::Receive), None);
+        assert_eq!(ReturnStatus::Approved.after(ReturnAction::Reject), None);
+        assert_eq!(ReturnStatus::Accepted.after(ReturnAction::Accept), None);
+        assert_eq!(ReturnStatus::Rejected.after(ReturnAction::Approve), None);
+    }
+
+    #[test]
+    fn reasons_are_trimmed_and_bounded() {
+        assert_eq!(validate_text("  Broken  ", "Reason").unwrap(), "Broken");
+        assert!(validate_text("   ", "Reason").is_err());
+        assert!(validate_text(&"x".repeat(501), "Reason").is_err());
+    }
+
+    #[test]
+    fn customers_never_see_the_admin_or_their_notes() {
+        let admin_id = Uuid::new_v4();
+        let event = envelope(DomainEvent::ReturnStatusChanged {
+            return_id: Uuid::new_v4(),
+            order_id: Uuid::new_v4(),
+            customer_id: Uuid::new_v4(),
+            from: ReturnStatus::Received,
+            to: ReturnStatus::Declined,
+            admin_id,
+            note: Some("Customer dropped it, see photos".to_string()),
+        });
+        let entry = TimelineEntry::for_customer(&event, None).unwrap();
+        let shown = serde_json::to_string(&entry).unwrap();
+
+        assert_eq!(entry.kind, TimelineKind::ReturnStatusChanged);
+        assert_eq!(entry.status.as_deref(), Some("Declined"));
+        assert_eq!(entry.reason, None);
+        assert!(!shown.contains(&admin_id.to_string()));
+        assert!(!shown.contains("photos"));
+    }
+
+    #[test]
+    fn only_cancellations_carry_the_cancellation_reason() {
+        let changed = |to| {
+            envelope(DomainEvent::OrderStatusChanged {
+                order_id: Uuid::new_v4(),
+                customer_id: Uuid::new_v4(),
+                from: OrderStatus::Pending,
+                to,
+            })
+        };
+        let cancelled =
+            TimelineEntry::for_customer(&changed(OrderStatus::Cancelled), Some("Ordered twice"))
+                .unwrap();
+        let shipped =
+            TimelineEntry::for_customer(&changed(OrderStatus::Shipped), Some("Ordered twice"))
+                .unwrap();
+
+        assert_eq!(cancelled.status.as_deref(), Some("Cancelled"));
+        assert_eq!(cancelled.reason.as_deref(), Some("Ordered twice"));
+        assert_eq!(shipped.reason, None);
+    }
+}
@@ -1,24 +1,40 @@
-use super::validate_admin::validate_admin_credentials;
+use super::validate_admin::{require_admin, validate_admin_credentials};
 use crate::auth_jwt::auth::create_jwt;
-use crate::db::PgPool;
-use crate::db_models::Order;
-use crate::errors::custom::{AuthError, CustomError, DbError};
-use crate::routes::order::order::OrderStatus;
-use crate::schema::admins::dsl as admin_dsl;
-use crate::schema::orders::dsl as orders;
+use crate::db_models::Admin;
+use crate::errors::custom::{AuthError, CustomError};
+use crate::metrics::METRICS;
+use crate::repository::pagination::{OrderFilter, OrderPageRequest, OrderSort};
+use crate::repository::{AdminRepository, OrderRepository};
+use crate::routes::order::order::{self as order_routes, CancelOrderBody, OrderStatus};
 use crate::session_state::TypedSession;
 use crate::validations::name_email::UserName;
 use actix_web::{web, HttpResponse, Responder};
 use argon2::{self, password_hash::SaltString, Argon2, PasswordHasher};
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
-use rand::Rng;
+use chrono::NaiveDateTime;
 use serde::Deserialize;
 use serde_json;
 use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+pub struct AdminOrderListQuery {
+    /// Defaults to 50, capped at 200
+    pub limit: Option<i64>,
+    /// `next_cursor` or `prev_cursor` of a previous page
+    pub cursor: Option<String>,
+    pub status: Option<OrderStatus>,
+    pub customer_id: Option<Uuid>,
+    pub product_id: Option<Uuid>,
+    /// Only orders created at or after this time, e.g. `2024-11-01T00:00:00`
+    pub created_from: Option<NaiveDateTime>,
+    /// Only orders created before this time
+    pub created_to: Option<NaiveDateTime>,
+    /// `newest` (default) or `oldest` first
+    pub sort: Option<OrderSort>,
+}
+
+#[derive(Deserialize, ToSchema)]
 pub struct CreateAdminBody {
     username: String,
     password: String,
@@ -29,18 +45,23 @@ impl CreateAdminBody {
         Ok(user_name)
     }
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginAdminBody {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdateStatusBody {
     pub order_id: Uuid,
     pub status: OrderStatus,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct OrderStatusBody {
+    pub status: OrderStatus,
+}
+
 fn generate_random_salt() -> SaltString {
     let mut rng = rand::thread_rng();
     SaltString::generate(&mut rng)
@@ -50,26 +71,33 @@ fn generate_random_salt() -> SaltString {
 // Registering Admin Route
 /******************************************/
 /**
- * @route   POST /admin/register
+ * @route   POST /api/v1/admins
+ * @legacy  POST /admin/register (deprecated alias)
  * @access  Public
  */
-#[instrument(name = "Register Admin", skip(req_admin, pool, session))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/admins",
+    tag = "admin",
+    request_body = CreateAdminBody,
+    responses(
+        (status = 200, description = "Admin registered", body = String),
+        (status = 400, description = "Invalid username", body = ErrorBody),
+        (status = 500, description = "Database or hashing failure", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Register Admin", skip(req_admin, admins, session))]
 pub async fn register_admin(
-    pool: web::Data<PgPool>,
+    admins: web::Data<dyn AdminRepository>,
     req_admin: web::Json<CreateAdminBody>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
-    let pool = pool.clone();
     let admin_data = req_admin.into_inner();
     let admin_password = admin_data.password.clone();
     let validated_name = admin_data
         .validate()
         .map_err(|err| CustomError::ValidationError(err.to_string()))?;
     let uuid: Uuid = Uuid::new_v4();
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
     let argon2 = Argon2::default();
 
     let salt = generate_random_salt();
@@ -77,21 +105,13 @@ pub async fn register_admin(
         .hash_password(admin_password.as_bytes(), &salt)
         .map_err(|err| CustomError::HashingError(err.to_string()))?;
 
-    let result = diesel::insert_into(admin_dsl::admins)
-        .values((
-            admin_dsl::id.eq(uuid),
-            admin_dsl::username.eq(validated_name.as_ref()),
-            admin_dsl::password_hash.eq(password_hashed.to_string()),
-        ))
-        .execute(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
-
-    if result == 0 {
-        return Err(CustomError::DatabaseError(DbError::InsertionError(
-            "Failed data insertion in db".to_string(),
-        )));
-    }
+    admins
+        .insert(Admin {
+            id: uuid,
+            username: validated_name.as_ref().to_string(),
+            password_hash: password_hashed.to_string(),
+        })
+        .await?;
     let _ = session.insert_admin_id(uuid);
     Ok(HttpResponse::Ok().body("Admin registered successfully"))
 }
@@ -100,17 +120,29 @@ pub async fn register_admin(
 // Login Admin Route
 /******************************************/
 /**
- * @route   POST /admin/login
+ * @route   POST /api/v1/admin/sessions
+ * @legacy  POST /admin/login (deprecated alias)
  * @access  Public
  */
-#[instrument(name = "Login admin", skip(req_login, pool, session))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/sessions",
+    tag = "admin",
+    request_body = LoginAdminBody,
+    responses(
+        (status = 200, description = "JWT issued", body = Object, example = json!({"token": "<jwt>"})),
+        (status = 401, description = "Invalid credentials", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Login admin", skip(req_login, admins, session))]
 
 pub async fn login_admin(
-    pool: web::Data<PgPool>,
+    admins: web::Data<dyn AdminRepository>,
     req_login: web::Json<LoginAdminBody>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
-    let id_admin = validate_admin_credentials(&pool, &req_login.into_inner()).await;
+    let id_admin = validate_admin_credentials(admins.get_ref(), &req_login.into_inner()).await;
+    METRICS.record_login("admin", id_admin.is_ok());
 
     match id_admin {
         Ok(admin_id) => {
@@ -132,9 +164,20 @@ pub async fn login_admin(
 // Logout Admin Route
 /******************************************/
 /**
- * @route   POST /protected/admin/logout
+ * @route   DELETE /api/v1/admin/session
+ * @legacy  POST /protected/admin/logout (deprecated alias)
  * @access  JWT Protected
  */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/session",
+    tag = "admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Session purged", body = String),
+        (status = 401, description = "Missing or invalid token")
+    )
+)]
 #[instrument(name = "Logout admin", skip(session))]
 pub async fn logout_admin(session: TypedSession) -> impl Responder {
     session.admin_log_out();
@@ -144,15 +187,87 @@ pub async fn logout_admin(session: TypedSession) -> impl Responder {
 /******************************************/
 // Updating Order Status Route
 /******************************************/
+/**
+ * @route   PATCH /api/v1/admin/orders/{id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/orders/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Order id")),
+    request_body = OrderStatusBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Order status updated", body = String),
+        (status = 400, description = "Cancelling, which has its own route", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such order", body = ErrorBody),
+        (status = 409, description = "The order can't move to that status, or its payment isn't captured yet", body = ErrorBody),
+        (status = 500, description = "Database failure", body = ErrorBody)
+    )
+)]
+#[instrument(
+    name = "Update order status admin",
+    skip(order_id, req_update, orders, session)
+)]
+pub async fn update_order_status(
+    orders: web::Data<dyn OrderRepository>,
+    order_id: web::Path<Uuid>,
+    req_update: web::Json<OrderStatusBody>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let data = req_update.into_inner();
+    set_order_status(
+        orders.get_ref(),
+        &session,
+        order_id.into_inner(),
+        data.status,
+    )
+    .await
+}
+
+/******************************************/
+// Updating Order Status Route (legacy)
+/******************************************/
 /**
  * @route   POST /protected/admin/update_status
  * @access  JWT Protected
+ * Deprecated alias of PATCH /api/v1/admin/orders/{id}
  */
-#[instrument(name = "Update order status admin", skip(req_update, pool, session))]
+#[utoipa::path(
+    post,
+    path = "/protected/admin/update_status",
+    tag = "admin",
+    request_body = UpdateStatusBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Order status updated", body = String),
+        (status = 400, description = "Cancelling, which has its own route", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such order", body = ErrorBody),
+        (status = 409, description = "The order can't move to that status, or its payment isn't captured yet", body = ErrorBody),
+        (status = 500, description = "Database failure", body = ErrorBody)
+    )
+)]
+#[instrument(
+    name = "Update order status admin (legacy)",
+    skip(req_update, orders, session)
+)]
 pub async fn update_status(
-    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
     req_update: web::Json<UpdateStatusBody>,
     session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let data: UpdateStatusBody = req_update.into_inner();
+    set_order_status(orders.get_ref(), &session, data.order_id, data.status).await
+}
+
+async fn set_order_status(
+    orders: &dyn OrderRepository,
+    session: &TypedSession,
+    order_id: Uuid,
+    status: OrderStatus,
 ) -> Result<HttpResponse, CustomError> {
     let admin_id = session.get_admin_id().map_err(|_| {
         CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
@@ -160,11 +275,6 @@ pub async fn update_status(
         ))
     })?;
     session.renew();
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
-    let data: UpdateStatusBody = req_update.into_inner();
     if admin_id.is_none() {
         return Err(CustomError::AuthenticationError(
             AuthError::SessionAuthenticationError("User not found".to_string()),
@@ -172,17 +282,22 @@ pub async fn update_status(
     }
 
     let _admin_id = admin_id.unwrap();
-    let result = diesel::update(orders::orders.filter(orders::id.eq(data.order_id)))
-        .set(orders::status.eq(data.status))
-        .execute(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
-
-    if result == 0 {
-        return Err(CustomError::DatabaseError(DbError::InsertionError(
-            "Failed data insertion in db".to_string(),
-        )));
+    // Cancelling records a reason, so it has its own route
+    if status == OrderStatus::Cancelled {
+        return Err(CustomError::ValidationError(
+            "Use POST /api/v1/admin/orders/{id}/cancel to cancel an order".to_string(),
+        ));
     }
+    let status_label = format!("{:?}", status).to_lowercase();
+    let updated = orders.update_status(order_id, status).await?;
+
+    if !updated {
+        return Err(CustomError::NotFoundError(format!("Order {}", order_id)));
+    }
+    METRICS
+        .order_status_transitions_total
+        .with_label_values(&[&status_label])
+        .inc();
     Ok(HttpResponse::Ok().body("Updated Status Successfully"))
 }
 
@@ -190,12 +305,26 @@ pub async fn update_status(
 // Fetching All Orders Route
 /******************************************/
 /**
- * @route   GET /protected/admin/orders
+ * @route   GET /api/v1/admin/orders
+ * @legacy  GET /protected/admin/fetch_all_orders (deprecated alias)
  * @access  JWT Protected
  */
-#[instrument(name = "Fetch all orders", skip(pool, session))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/orders",
+    tag = "admin",
+    params(AdminOrderListQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "One page of orders across all customers, with customer summaries", body = OrderPage),
+        (status = 400, description = "Invalid cursor or filter", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Fetch all orders", skip(orders, query, session))]
 pub async fn fetch_all_orders(
-    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
+    query: web::Query<AdminOrderListQuery>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
     let admin_id = session.get_admin_id().map_err(|_| {
@@ -210,14 +339,97 @@ pub async fn fetch_all_orders(
         ));
     }
 
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
-    let orders = orders::orders
-        .load::<Order>(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
+    let query = query.into_inner();
+    let filter = OrderFilter {
+        status: query.status,
+        customer_id: query.customer_id,
+        product_id: query.product_id,
+        created_from: query.created_from,
+        created_to: query.created_to,
+    };
+    let request = OrderPageRequest::parse(filter, query.sort, query.limit, query.cursor.as_deref())
+        .map_err(CustomError::ValidationError)?;
+    let page = orders.list_page(&request).await?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/******************************************/
+// Fetching One Order Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/orders/{id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/orders/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Order id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The order with its product and customer", body = OrderDetail),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such order", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Fetch order", skip(orders, order_id, session))]
+pub async fn fetch_order(
+    orders: web::Data<dyn OrderRepository>,
+    order_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let order_id = order_id.into_inner();
+    let order = orders
+        .find_detail(order_id)
+        .await?
+        .ok_or_else(|| CustomError::NotFoundError(format!("Order {}", order_id)))?;
+
+    Ok(HttpResponse::Ok().json(order))
+}
+
+/******************************************/
+// Cancelling an Order Route
+/******************************************/
+/**
+ * @route   POST /api/v1/admin/orders/{id}/cancel
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/orders/{id}/cancel",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Order id")),
+    request_body = CancelOrderBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The cancelled order", body = OrderDetail),
+        (status = 400, description = "Missing or overlong reason", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such order", body = ErrorBody),
+        (status = 409, description = "The order is no longer pending or holds an unsettled payment", body = ErrorBody)
+    )
+)]
+#[instrument(
+    name = "Cancel order admin",
+    skip(orders, order_id, req_cancel, session)
+)]
+pub async fn admin_cancel_order(
+    orders: web::Data<dyn OrderRepository>,
+    order_id: web::Path<Uuid>,
+    req_cancel: web::Json<CancelOrderBody>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let admin_id = require_admin(&session)?;
+    let reason = req_cancel.validated_reason()?;
+    let order = order_routes::cancel(
+        orders.get_ref(),
+        order_id.into_inner(),
+        reason,
+        Some(admin_id),
+    )
+    .await?;
 
-    Ok(HttpResponse::Ok().json(orders))
+    Ok(HttpResponse::Ok().json(order))
 }
@@ -0,0 +1,119 @@
+use super::validate_admin::require_admin;
+use crate::db::PgPool;
+use crate::errors::custom::CustomError;
+use crate::jobs::{self, JobStatus};
+use crate::session_state::TypedSession;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+const DEFAULT_JOB_LIMIT: i64 = 50;
+const MAX_JOB_LIMIT: i64 = 500;
+
+#[derive(Deserialize, IntoParams)]
+pub struct JobsQuery {
+    /// Only return jobs in this state, e.g. `Dead` for the dead-letter queue
+    pub status: Option<JobStatus>,
+    /// Defaults to 50, capped at 500
+    pub limit: Option<i64>,
+}
+
+/******************************************/
+// Listing Background Jobs Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/jobs
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs",
+    tag = "admin",
+    params(JobsQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Most recently updated jobs first", body = [BackgroundJob]),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "List background jobs", skip(pool, query, session))]
+pub async fn list_jobs(
+    pool: web::Data<PgPool>,
+    query: web::Query<JobsQuery>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let query = query.into_inner();
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_JOB_LIMIT)
+        .clamp(1, MAX_JOB_LIMIT);
+    let jobs = jobs::list_jobs(&pool, query.status, limit).await?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+/******************************************/
+// Viewing A Background Job Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/jobs/{id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Job id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The job with its last error", body = BackgroundJob),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such job", body = ErrorBody)
+    )
+)]
+#[instrument(name = "View background job", skip(pool, session))]
+pub async fn get_job(
+    pool: web::Data<PgPool>,
+    job_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let job_id = job_id.into_inner();
+    match jobs::find_job(&pool, job_id).await? {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Err(CustomError::NotFoundError(format!("Job {}", job_id))),
+    }
+}
+
+/******************************************/
+// Requeueing A Dead Job Route
+/******************************************/
+/**
+ * @route   POST /api/v1/admin/jobs/{id}/requeue
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/jobs/{id}/requeue",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Job id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Job is pending again with a fresh set of attempts", body = BackgroundJob),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such job", body = ErrorBody),
+        (status = 409, description = "Job is not dead", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Requeue background job", skip(pool, session))]
+pub async fn requeue_job(
+    pool: web::Data<PgPool>,
+    job_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let job = jobs::requeue_job(&pool, job_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(job))
+}
@@ -0,0 +1,269 @@
+use super::validate_admin::require_admin;
+use crate::errors::custom::CustomError;
+use crate::live::LiveEvents;
+use crate::outbox::{DomainEvent, EventEnvelope};
+use crate::routes::order::order::OrderStatus;
+use crate::session_state::TypedSession;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::Message;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// Heartbeats without hearing from the client before the socket is closed
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// Sent by the client, as a JSON text frame, to change which orders it is shown
+#[derive(Debug, PartialEq, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    /// Only orders that are now in one of `statuses`; empty means every order
+    Subscribe {
+        #[serde(default)]
+        statuses: Vec<OrderStatus>,
+    },
+}
+
+/// Pushed to the client as JSON text frames
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum FeedMessage {
+    Subscribed {
+        statuses: Vec<OrderStatus>,
+    },
+    OrderPlaced {
+        event_id: Uuid,
+        order_id: Uuid,
+        customer_id: Uuid,
+        product_id: Uuid,
+        status: OrderStatus,
+        occurred_at: NaiveDateTime,
+    },
+    OrderStatusChanged {
+        event_id: Uuid,
+        order_id: Uuid,
+        customer_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+        occurred_at: NaiveDateTime,
+    },
+    /// A malformed client message, or events the feed had to drop; reload the order list
+    Error {
+        message: String,
+    },
+}
+
+impl FeedMessage {
+    // `None` for events that aren't about orders or don't match the subscription
+    pub fn for_subscription(envelope: &EventEnvelope, statuses: &[OrderStatus]) -> Option<Self> {
+        let wanted = |status: &OrderStatus| statuses.is_empty() || statuses.contains(status);
+        match &envelope.event {
+            DomainEvent::OrderPlaced {
+                order_id,
+                customer_id,
+                product_id,
+                status,
+                ..
+            } if wanted(status) => Some(FeedMessage::OrderPlaced {
+                event_id: envelope.id,
+                order_id: *order_id,
+                customer_id: *customer_id,
+                product_id: *product_id,
+                status: *status,
+                occurred_at: envelope.occurred_at,
+            }),
+            DomainEvent::OrderStatusChanged {
+                order_id,
+                customer_id,
+                from,
+                to,
+            } if wanted(to) => Some(FeedMessage::OrderStatusChanged {
+                event_id: envelope.id,
+                order_id: *order_id,
+                customer_id: *customer_id,
+                from: *from,
+                to: *to,
+                occurred_at: envelope.occurred_at,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/******************************************/
+// Live Orders WebSocket Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/orders/live
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/orders/live",
+    tag = "admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 101, description = "WebSocket of `FeedMessage`s for placed orders and status changes; send a `ClientMessage` to filter by status", body = FeedMessage),
+        (status = 400, description = "Not a WebSocket handshake", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Open live orders feed", skip(live, req, body, session))]
+pub async fn live_orders(
+    live: web::Data<LiveEvents>,
+    req: HttpRequest,
+    body: web::Payload,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    // Checked before the upgrade so customers get a plain 401
+    let admin_id = require_admin(&session)?;
+    let (response, mut socket, mut messages) = actix_ws::handle(&req, body)
+        .map_err(|err| CustomError::ValidationError(err.to_string()))?;
+
+    let mut receiver = live.subscribe();
+    let heartbeat = live.heartbeat();
+    // The message stream reads the request payload, which is tied to this worker's thread
+    actix_web::rt::spawn(async move {
+        let mut statuses: Vec<OrderStatus> = Vec::new();
+        let mut ticker = tokio::time::interval(heartbeat);
+        let mut last_heard = Instant::now();
+        loop {
+            let reply = tokio::select! {
+                _ = ticker.tick() => {
+                    if last_heard.elapsed() > heartbeat * MISSED_HEARTBEATS {
+                        tracing::info!(%admin_id, "Live orders client stopped responding");
+                        break;
+                    }
+                    if socket.ping(b"").await.is_err() {
+                        return;
+                    }
+                    None
+                }
+                message = messages.recv() => {
+                    last_heard = Instant::now();
+                    match message {
+                        Some(Ok(Message::Text(text))) => {
+                            match serde_json::from_str::<ClientMessage>(&text) {
+                                Ok(ClientMessage::Subscribe { statuses: requested }) => {
+                                    statuses = requested;
+                                    Some(FeedMessage::Subscribed { statuses: statuses.clone() })
+                                }
+                                Err(err) => Some(FeedMessage::Error {
+                                    message: format!("Invalid message: {}", err),
+                                }),
+                            }
+                        }
+                        Some(Ok(Message::Ping(bytes))) => {
+                            if socket.pong(&bytes).await.is_err() {
+                                return;
+                            }
+                            None
+                        }
+                        Some(Ok(Message::Close(reason))) => {
+                            let _ = socket.close(reason).await;
+                            return;
+                        }
+                        Some(Ok(_)) => None,
+                        Some(Err(_)) | None => break,
+                    }
+                }
+                event = receiver.recv() => match event {
+                    Ok(envelope) => FeedMessage::for_subscription(&envelope, &statuses),
+                    Err(RecvError::Lagged(skipped)) => Some(FeedMessage::Error {
+                        message: format!("Missed {} events; reload the order list", skipped),
+                    }),
+                    Err(RecvError::Closed) => break,
+                },
+            };
+            if let Some(reply) = reply {
+                let Ok(text) = serde_json::to_string(&reply) else {
+                    continue;
+                };
+                if socket.text(text).await.is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = socket.close(None).await;
+    });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientMessage, FeedMessage};
+    use crate::outbox::{DomainEvent, EventEnvelope};
+    use crate::routes::order::order::OrderStatus;
+    use uuid::Uuid;
+
+    fn envelope(event: DomainEvent) -> EventEnvelope {
+        EventEnvelope {
+            id: Uuid::new_v4(),
+            seq: 1,
+            occurred_at: chrono::Utc::now().naive_utc(),
+            event,
+        }
+    }
+
+    fn placed() -> EventEnvelope {
+        envelope(DomainEvent::OrderPlaced {
+            order_id: Uuid::new_v4(),
+            customer_id: Uuid::new_v4(),
+            product_id: Uuid::new_v4(),
+            status: OrderStatus::Pending,
+            created_at: chrono::Utc::now().naive_utc(),
+        })
+    }
+
+    fn shipped() -> EventEnvelope {
+        envelope(DomainEvent::OrderStatusChanged {
+            order_id: Uuid::new_v4(),
+            customer_id: Uuid::new_v4(),
+            from: OrderStatus::Pending,
+            to: OrderStatus::Shipped,
+        })
+    }
+
+    #[test]
+    fn empty_subscription_sees_every_order_event() {
+        assert!(FeedMessage::for_subscription(&placed(), &[]).is_some());
+        assert!(FeedMessage::for_subscription(&shipped(), &[]).is_some());
+        let registered = envelope(DomainEvent::CustomerRegistered {
+            customer_id: Uuid::new_v4(),
+            username: "ada".to_string(),
+            email: "ada@example.com".to_string(),
+        });
+        assert!(FeedMessage::for_subscription(&registered, &[]).is_none());
+    }
+
+    #[test]
+    fn status_subscription_filters_on_the_resulting_status() {
+        let pending_only = [OrderStatus::Pending];
+        assert!(matches!(
+            FeedMessage::for_subscription(&placed(), &pending_only),
+            Some(FeedMessage::OrderPlaced { .. })
+        ));
+        assert!(FeedMessage::for_subscription(&shipped(), &pending_only).is_none());
+        assert!(FeedMessage::for_subscription(&shipped(), &[OrderStatus::Shipped]).is_some());
+    }
+
+    #[test]
+    fn parses_subscribe_messages() {
+        let message: ClientMessage =
+            serde_json::from_str(r#"{"type": "Subscribe", "statuses": ["Pending"]}"#).unwrap();
+        assert_eq!(
+            message,
+            ClientMessage::Subscribe {
+                statuses: vec![OrderStatus::Pending]
+            }
+        );
+        let message: ClientMessage = serde_json::from_str(r#"{"type": "Subscribe"}"#).unwrap();
+        assert_eq!(message, ClientMessage::Subscribe { statuses: vec![] });
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"type": "Unsubscribe"}"#).is_err());
+    }
+}
@@ -1,2 +1,8 @@
+#[allow(clippy::module_inception)]
 pub mod admin;
+pub mod jobs;
+pub mod live_orders;
+pub mod payments;
+pub mod returns;
 pub mod validate_admin;
+pub mod webhooks;
@@ -0,0 +1,156 @@
+use super::validate_admin::require_admin;
+use crate::db::PgPool;
+use crate::errors::custom::CustomError;
+use crate::payments::{self, PaymentProvider};
+use crate::session_state::TypedSession;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefundPaymentBody {
+    /// Defaults to everything captured and not yet refunded
+    pub amount: Option<i32>,
+}
+
+/******************************************/
+// Listing an Order's Payments Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/orders/{id}/payments
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/orders/{id}/payments",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Order id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Every payment attempt for the order, oldest first", body = [Payment]),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "List order payments admin", skip(pool, session))]
+pub async fn admin_order_payments(
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let payments = payments::list_for_order(&pool, order_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(payments))
+}
+
+/******************************************/
+// Capturing a Payment Route
+/******************************************/
+/**
+ * @route   POST /api/v1/admin/payments/{id}/capture
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/payments/{id}/capture",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Payment id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Payment captured; the order can now be shipped", body = Payment),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 402, description = "Declined by the provider", body = ErrorBody),
+        (status = 404, description = "No such payment", body = ErrorBody),
+        (status = 409, description = "The payment isn't authorized or its order is cancelled", body = ErrorBody),
+        (status = 502, description = "The payment provider couldn't be reached", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Capture payment", skip(pool, provider, session))]
+pub async fn capture_payment(
+    pool: web::Data<PgPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    payment_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let payment = payments::capture(&pool, provider.get_ref(), payment_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(payment))
+}
+
+/******************************************/
+// Voiding a Payment Route
+/******************************************/
+/**
+ * @route   POST /api/v1/admin/payments/{id}/void
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/payments/{id}/void",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Payment id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Authorization released without taking any money", body = Payment),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 402, description = "Declined by the provider", body = ErrorBody),
+        (status = 404, description = "No such payment", body = ErrorBody),
+        (status = 409, description = "The payment isn't authorized", body = ErrorBody),
+        (status = 502, description = "The payment provider couldn't be reached", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Void payment", skip(pool, provider, session))]
+pub async fn void_payment(
+    pool: web::Data<PgPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    payment_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let payment = payments::void(&pool, provider.get_ref(), payment_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(payment))
+}
+
+/******************************************/
+// Refunding a Payment Route
+/******************************************/
+/**
+ * @route   POST /api/v1/admin/payments/{id}/refund
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/payments/{id}/refund",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Payment id")),
+    request_body = RefundPaymentBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Refunded in full or in part", body = Payment),
+        (status = 400, description = "Amount is not positive or exceeds what is left", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 402, description = "Declined by the provider", body = ErrorBody),
+        (status = 404, description = "No such payment", body = ErrorBody),
+        (status = 409, description = "Nothing captured to refund", body = ErrorBody),
+        (status = 502, description = "The payment provider couldn't be reached", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Refund payment", skip(pool, provider, req_refund, session))]
+pub async fn refund_payment(
+    pool: web::Data<PgPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    payment_id: web::Path<Uuid>,
+    req_refund: web::Json<RefundPaymentBody>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let payment = payments::refund(
+        &pool,
+        provider.get_ref(),
+        payment_id.into_inner(),
+        req_refund.amount,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(payment))
+}
@@ -0,0 +1,170 @@
+use super::validate_admin::require_admin;
+use crate::db::PgPool;
+use crate::errors::custom::CustomError;
+use crate::payments::PaymentProvider;
+use crate::returns::{self, ReturnAction, ReturnStatus};
+use crate::session_state::TypedSession;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const DEFAULT_RETURN_LIMIT: i64 = 50;
+const MAX_RETURN_LIMIT: i64 = 500;
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReturnsQuery {
+    /// Only returns in this state, e.g. `Requested` for those awaiting a decision
+    pub status: Option<ReturnStatus>,
+    /// Defaults to 50, capped at 500
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReturnActionBody {
+    pub action: ReturnAction,
+    /// Why, e.g. the rejection reason or what inspection found
+    pub note: Option<String>,
+}
+
+/******************************************/
+// Listing Returns Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/returns
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/returns",
+    tag = "admin",
+    params(ReturnsQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Returns, oldest first", body = [ReturnDetail]),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "List returns", skip(pool, query, session))]
+pub async fn list_returns(
+    pool: web::Data<PgPool>,
+    query: web::Query<ReturnsQuery>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_RETURN_LIMIT)
+        .clamp(1, MAX_RETURN_LIMIT);
+    let details = returns::list_returns(&pool, query.status, limit).await?;
+    Ok(HttpResponse::Ok().json(details))
+}
+
+/******************************************/
+// Fetching One Return Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/returns/{id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/returns/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Return id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The return with its items and refund", body = ReturnDetail),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such return", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Get return", skip(pool, session))]
+pub async fn get_return(
+    pool: web::Data<PgPool>,
+    return_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let return_id = return_id.into_inner();
+    match returns::find_return(&pool, return_id).await? {
+        Some(detail) => Ok(HttpResponse::Ok().json(detail)),
+        None => Err(CustomError::NotFoundError(format!("Return {}", return_id))),
+    }
+}
+
+/******************************************/
+// Moving a Return Along Route
+/******************************************/
+/**
+ * @route   PATCH /api/v1/admin/returns/{id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/returns/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Return id")),
+    request_body = ReturnActionBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The return after the step; `Accept` also refunds the order's payment and records the refund", body = ReturnDetail),
+        (status = 400, description = "Empty or overlong note", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 402, description = "Refund declined by the provider", body = ErrorBody),
+        (status = 404, description = "No such return", body = ErrorBody),
+        (status = 409, description = "The action doesn't apply to the return's current status, or there is no captured payment to refund", body = ErrorBody),
+        (status = 502, description = "The payment provider couldn't be reached", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Update return", skip(pool, provider, req_action, session))]
+pub async fn update_return(
+    pool: web::Data<PgPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    return_id: web::Path<Uuid>,
+    req_action: web::Json<ReturnActionBody>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let admin_id = require_admin(&session)?;
+    let body = req_action.into_inner();
+    let detail = returns::apply_action(
+        &pool,
+        provider.get_ref(),
+        return_id.into_inner(),
+        body.action,
+        admin_id,
+        body.note,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(detail))
+}
+
+/******************************************/
+// Order Timeline Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/orders/{id}/timeline
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/orders/{id}/timeline",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Order id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Everything recorded about the order and its returns, oldest first, with the admin behind each step", body = [AuditEntry]),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Get order timeline admin", skip(pool, session))]
+pub async fn admin_order_timeline(
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let timeline = returns::order_audit_trail(&pool, order_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(timeline))
+}
@@ -1,53 +1,23 @@
 use super::admin::LoginAdminBody;
-use crate::db::PgPool;
-use crate::errors::custom::{AuthError, CustomError, DbError};
-use crate::schema::admins::dsl::*;
+use crate::errors::custom::{AuthError, CustomError};
+use crate::repository::AdminRepository;
+use crate::session_state::TypedSession;
 use crate::telemetry::spawn_blocking_with_tracing;
 use argon2::{self, Argon2, PasswordHash, PasswordVerifier};
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
 use tracing::instrument;
 use uuid::Uuid;
 
-#[instrument(name = "Get stored admin credentials", skip(user_name, pool), fields(username = %user_name))]
+#[instrument(name = "Get stored admin credentials", skip(user_name, admins), fields(username = %user_name))]
 async fn get_stored_admin_credentials(
     user_name: &str,
-    pool: &PgPool,
+    admins: &dyn AdminRepository,
 ) -> Result<(Uuid, String), CustomError> {
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
-
-    let row: Result<Option<Vec<(String, Uuid)>>, diesel::result::Error> = admins
-        .filter(username.eq(user_name))
-        .select((password_hash, id))
-        .load::<(String, Uuid)>(&mut conn)
-        .await
-        .optional();
-
-    let (id_user, expected_hash_password) = match row {
-        Ok(Some(vec)) => {
-            if let Some((hash_password, id_user)) = vec.into_iter().next() {
-                (id_user, hash_password)
-            } else {
-                return Err(CustomError::AuthenticationError(
-                    AuthError::OtherAuthenticationError("Invalid username or password".to_string()),
-                ));
-            }
-        }
-        Ok(None) => {
-            return Err(CustomError::AuthenticationError(
-                AuthError::OtherAuthenticationError("Invalid username or password".to_string()),
-            ));
-        }
-        Err(err) => {
-            return Err(CustomError::DatabaseError(DbError::QueryBuilderError(
-                err.to_string(),
-            )))
-        }
-    };
-    Ok((id_user, expected_hash_password))
+    match admins.find_by_username(user_name).await? {
+        Some(account) => Ok((account.id, account.password_hash)),
+        None => Err(CustomError::AuthenticationError(
+            AuthError::OtherAuthenticationError("Invalid username or password".to_string()),
+        )),
+    }
 }
 
 #[instrument(name = "Verify admin password", skip(expected_hash, candidate))]
@@ -60,13 +30,13 @@ fn verify_admin_password(expected_hash: &str, candidate: String) -> bool {
         .is_ok()
 }
 
-#[instrument(name = "Validate admin credentials", skip(req_login, pool), fields(username = %req_login.username))]
+#[instrument(name = "Validate admin credentials", skip(req_login, admins), fields(username = %req_login.username))]
 pub async fn validate_admin_credentials(
-    pool: &PgPool,
+    admins: &dyn AdminRepository,
     req_login: &LoginAdminBody,
 ) -> Result<Uuid, CustomError> {
     let (admin_id, stored_password_hash) =
-        get_stored_admin_credentials(&req_login.username, pool).await?;
+        get_stored_admin_credentials(&req_login.username, admins).await?;
 
     let entered_pasword = req_login.password.to_owned();
     let is_valid = spawn_blocking_with_tracing(move || {
@@ -82,3 +52,17 @@ pub async fn validate_admin_credentials(
         ));
     }
 }
+
+// Resolves the admin id from the session, for handlers behind `jwt_auth_middleware`
+pub fn require_admin(session: &TypedSession) -> Result<Uuid, CustomError> {
+    let admin_id = session.get_admin_id().map_err(|_| {
+        CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
+            "User not logged in".to_string(),
+        ))
+    })?;
+    admin_id.ok_or_else(|| {
+        CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
+            "User not found".to_string(),
+        ))
+    })
+}
@@ -0,0 +1,229 @@
+use super::validate_admin::require_admin;
+use crate::db::PgPool;
+use crate::errors::custom::CustomError;
+use crate::outbox::DomainEvent;
+use crate::session_state::TypedSession;
+use crate::webhooks;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const MIN_SECRET_LENGTH: usize = 16;
+const DEFAULT_DELIVERY_LIMIT: i64 = 50;
+const MAX_DELIVERY_LIMIT: i64 = 500;
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterWebhookBody {
+    /// http(s) URL that receives a signed POST per event
+    pub url: String,
+    /// Shared secret for the `X-Webhook-Signature` HMAC, at least 16 characters
+    pub secret: String,
+    /// Event types to deliver, e.g. `OrderPlaced`; empty means all of them
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+impl RegisterWebhookBody {
+    pub fn validate(&self) -> Result<(), String> {
+        let url = reqwest::Url::parse(&self.url).map_err(|err| format!("Invalid url: {}", err))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Unsupported url scheme {}", url.scheme()));
+        }
+        if self.secret.chars().count() < MIN_SECRET_LENGTH {
+            return Err(format!(
+                "Secret must be at least {} characters",
+                MIN_SECRET_LENGTH
+            ));
+        }
+        if let Some(unknown) = self
+            .event_types
+            .iter()
+            .find(|event_type| !DomainEvent::EVENT_TYPES.contains(&event_type.as_str()))
+        {
+            return Err(format!("Unknown event type {}", unknown));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct DeliveriesQuery {
+    /// Defaults to 50, capped at 500
+    pub limit: Option<i64>,
+}
+
+/******************************************/
+// Registering Webhook Endpoint Route
+/******************************************/
+/**
+ * @route   POST /api/v1/admin/webhooks
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    request_body = RegisterWebhookBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 201, description = "Endpoint registered; the secret is not echoed back", body = WebhookEndpoint),
+        (status = 400, description = "Invalid url, secret or event type", body = ErrorBody),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Register webhook endpoint", skip(pool, req_webhook, session))]
+pub async fn register_webhook(
+    pool: web::Data<PgPool>,
+    req_webhook: web::Json<RegisterWebhookBody>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let webhook = req_webhook.into_inner();
+    webhook.validate().map_err(CustomError::ValidationError)?;
+    let endpoint =
+        webhooks::create_endpoint(&pool, &webhook.url, &webhook.secret, webhook.event_types)
+            .await?;
+    Ok(HttpResponse::Created().json(endpoint))
+}
+
+/******************************************/
+// Listing Webhook Endpoints Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/webhooks
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks",
+    tag = "admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Registered endpoints, oldest first", body = [WebhookEndpoint]),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "List webhook endpoints", skip(pool, session))]
+pub async fn list_webhooks(
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let endpoints = webhooks::list_endpoints(&pool).await?;
+    Ok(HttpResponse::Ok().json(endpoints))
+}
+
+/******************************************/
+// Deleting Webhook Endpoint Route
+/******************************************/
+/**
+ * @route   DELETE /api/v1/admin/webhooks/{id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Webhook endpoint id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 204, description = "Endpoint and its delivery log removed"),
+        (status = 401, description = "Admin not logged in", body = ErrorBody),
+        (status = 404, description = "No such endpoint", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Delete webhook endpoint", skip(pool, session))]
+pub async fn delete_webhook(
+    pool: web::Data<PgPool>,
+    endpoint_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let endpoint_id = endpoint_id.into_inner();
+    if !webhooks::delete_endpoint(&pool, endpoint_id).await? {
+        return Err(CustomError::NotFoundError(format!(
+            "Webhook endpoint {}",
+            endpoint_id
+        )));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/******************************************/
+// Webhook Delivery Log Route
+/******************************************/
+/**
+ * @route   GET /api/v1/admin/webhooks/{id}/deliveries
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks/{id}/deliveries",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Webhook endpoint id"), DeliveriesQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Deliveries to the endpoint, newest first", body = [WebhookDelivery]),
+        (status = 401, description = "Admin not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "List webhook deliveries", skip(pool, query, session))]
+pub async fn list_webhook_deliveries(
+    pool: web::Data<PgPool>,
+    endpoint_id: web::Path<Uuid>,
+    query: web::Query<DeliveriesQuery>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    require_admin(&session)?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERY_LIMIT)
+        .clamp(1, MAX_DELIVERY_LIMIT);
+    let deliveries = webhooks::list_deliveries(&pool, endpoint_id.into_inner(), limit).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RegisterWebhookBody;
+
+    fn body(url: &str, secret: &str, event_types: &[&str]) -> RegisterWebhookBody {
+        RegisterWebhookBody {
+            url: url.to_string(),
+            secret: secret.to_string(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_known_event_types() {
+        let webhook = body(
+            "https://erp.example.com/hooks",
+            "0123456789abcdef",
+            &["OrderPlaced", "OrderStatusChanged"],
+        );
+        assert!(webhook.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_registrations() {
+        assert!(body("ftp://erp.example.com", "0123456789abcdef", &[])
+            .validate()
+            .is_err());
+        assert!(body("not a url", "0123456789abcdef", &[])
+            .validate()
+            .is_err());
+        assert!(body("https://erp.example.com", "short", &[])
+            .validate()
+            .is_err());
+        assert!(body(
+            "https://erp.example.com",
+            "0123456789abcdef",
+            &["OrderEaten"]
+        )
+        .validate()
+        .is_err());
+    }
+}
@@ -1,21 +1,20 @@
 use super::validate_customer::validate_credentials;
 use crate::auth_jwt::auth::create_jwt;
-use crate::db::PgPool;
+use crate::db_models::Customer;
 use crate::errors::custom::{AuthError, CustomError, DbError};
-use crate::schema::customers::dsl::*;
+use crate::metrics::METRICS;
+use crate::repository::CustomerRepository;
 use crate::session_state::TypedSession;
 use crate::validations::name_email::{UserEmail, UserName};
 use actix_web::{web, HttpResponse};
 use argon2::{self, password_hash::SaltString, Argon2, PasswordHasher};
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
-use rand::Rng;
 use serde::Deserialize;
 use serde_json::json;
 use tracing::instrument;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateCustomerBody {
     username: String,
     password: String,
@@ -28,7 +27,7 @@ impl CreateCustomerBody {
         Ok((user_name, user_email))
     }
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdateCustomerBody {
     username: String,
     email: String,
@@ -41,7 +40,7 @@ impl UpdateCustomerBody {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginCustomerBody {
     pub username: String,
     pub password: String,
@@ -56,26 +55,33 @@ fn generate_random_salt() -> SaltString {
 // Registering Customer Route
 /******************************************/
 /**
- * @route   POST /register
+ * @route   POST /api/v1/customers
+ * @legacy  POST /register (deprecated alias)
  * @access  Public
  */
-#[instrument(name = "Register a new customer", skip(req_user, pool, session), fields(username = %req_user.username, email = %req_user.email))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/customers",
+    tag = "customer",
+    request_body = CreateCustomerBody,
+    responses(
+        (status = 200, description = "Customer created", body = String),
+        (status = 400, description = "Invalid username or email", body = ErrorBody),
+        (status = 500, description = "Database or hashing failure", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Register a new customer", skip(req_user, customers, session), fields(username = %req_user.username, email = %req_user.email))]
 pub async fn register_customer(
-    pool: web::Data<PgPool>,
+    customers: web::Data<dyn CustomerRepository>,
     req_user: web::Json<CreateCustomerBody>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
-    let pool = pool.clone();
     let customer_data = req_user.into_inner();
     let user_password = customer_data.password.clone();
     let (validated_name, validated_email) = customer_data
         .validate()
         .map_err(|err| CustomError::ValidationError(err.to_string()))?;
     let uuid = Uuid::new_v4();
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
     let argon2 = Argon2::default();
 
     let salt = generate_random_salt();
@@ -83,21 +89,15 @@ pub async fn register_customer(
         .hash_password(user_password.as_bytes(), &salt)
         .map_err(|err| CustomError::HashingError(err.to_string()))?;
 
-    let result = diesel::insert_into(customers)
-        .values((
-            id.eq(uuid),
-            username.eq(validated_name.as_ref()),
-            password_hash.eq(password_hashed.to_string()),
-            email.eq(validated_email.as_ref()),
-        ))
-        .execute(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
-    if result == 0 {
-        return Err(CustomError::DatabaseError(DbError::InsertionError(
-            "Failed data insertion in db".to_string(),
-        )));
-    }
+    customers
+        .insert(Customer {
+            id: uuid,
+            username: validated_name.as_ref().to_string(),
+            password_hash: password_hashed.to_string(),
+            email: validated_email.as_ref().to_string(),
+            created_at: None,
+        })
+        .await?;
     let _ = session.insert_user_id(uuid);
     Ok(HttpResponse::Ok().body("User created successfully".to_string()))
 }
@@ -106,17 +106,29 @@ pub async fn register_customer(
 // Login Route
 /******************************************/
 /**
- * @route   POST /login
+ * @route   POST /api/v1/sessions
+ * @legacy  POST /login (deprecated alias)
  * @access  Public
  */
-#[instrument(name = "Login a customer", skip(req_login, pool, session), fields(username = %req_login.username))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions",
+    tag = "customer",
+    request_body = LoginCustomerBody,
+    responses(
+        (status = 200, description = "JWT issued", body = Object, example = json!({"token": "<jwt>"})),
+        (status = 401, description = "Invalid credentials", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Login a customer", skip(req_login, customers, session), fields(username = %req_login.username))]
 
 pub async fn login_customer(
-    pool: web::Data<PgPool>,
+    customers: web::Data<dyn CustomerRepository>,
     req_login: web::Json<LoginCustomerBody>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
-    let user_id = validate_credentials(&pool, &req_login.into_inner()).await;
+    let user_id = validate_credentials(customers.get_ref(), &req_login.into_inner()).await;
+    METRICS.record_login("customer", user_id.is_ok());
 
     match user_id {
         Ok(id_user) => {
@@ -138,9 +150,20 @@ pub async fn login_customer(
 // Logout Customer Route
 /******************************************/
 /**
- * @route   POST /protected/logout
+ * @route   DELETE /api/v1/me/session
+ * @legacy  POST /protected/logout (deprecated alias)
  * @access  JWT Protected
  */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/me/session",
+    tag = "customer",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Session purged", body = String),
+        (status = 401, description = "Missing or invalid token")
+    )
+)]
 #[instrument(name = "Logout a customer", skip(session))]
 pub async fn logout_customer(session: TypedSession) -> HttpResponse {
     session.log_out();
@@ -151,12 +174,25 @@ pub async fn logout_customer(session: TypedSession) -> HttpResponse {
 // Updating Customer Profile Route
 /******************************************/
 /**
- * @route   POST /protected/update
+ * @route   PATCH /api/v1/me
+ * @legacy  POST /protected/update (deprecated alias)
  * @access  JWT Protected
  */
-#[instrument(name = "Update customer", skip(req_user, pool, session), fields(username = %req_user.username, email = %req_user.email))]
+#[utoipa::path(
+    patch,
+    path = "/api/v1/me",
+    tag = "customer",
+    request_body = UpdateCustomerBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Customer updated", body = String),
+        (status = 400, description = "Invalid username or email", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Update customer", skip(req_user, customers, session), fields(username = %req_user.username, email = %req_user.email))]
 pub async fn update_customer(
-    pool: web::Data<PgPool>,
+    customers: web::Data<dyn CustomerRepository>,
     req_user: web::Json<UpdateCustomerBody>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
@@ -165,7 +201,6 @@ pub async fn update_customer(
             "User not found".to_string(),
         ))
     })?;
-    let pool = pool.clone();
     let customer_data = req_user.into_inner();
     let (validated_name, validated_email) = customer_data
         .validate()
@@ -177,20 +212,11 @@ pub async fn update_customer(
     }
 
     let user_id = user_id.unwrap();
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
-    let result = diesel::update(customers.find(user_id))
-        .set((
-            username.eq(validated_name.as_ref()),
-            email.eq(validated_email.as_ref()),
-        ))
-        .execute(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
+    let updated = customers
+        .update_profile(user_id, validated_name.as_ref(), validated_email.as_ref())
+        .await?;
 
-    if result == 0 {
+    if !updated {
         return Err(CustomError::DatabaseError(DbError::UpdationError(
             "Failed data update data in db".to_string(),
         )));
@@ -204,12 +230,23 @@ pub async fn update_customer(
 // View Customer Info Route
 /******************************************/
 /**
- * @route   Get /protected/view
+ * @route   GET /api/v1/me
+ * @legacy  GET /protected/view (deprecated alias)
  * @access  JWT Protected
  */
-#[instrument(name = "Get customer", skip(pool, session))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/me",
+    tag = "customer",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Username and email of the logged in customer", body = Object, example = json!(["kashish", "kk@gmail.com"])),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Get customer", skip(customers, session))]
 pub async fn view_customer(
-    pool: web::Data<PgPool>,
+    customers: web::Data<dyn CustomerRepository>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
     let user_id = session.get_user_id().map_err(|_| {
@@ -217,10 +254,6 @@ pub async fn view_customer(
             "User not found".to_string(),
         ))
     })?;
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
     if user_id.is_none() {
         return Err(CustomError::AuthenticationError(
             AuthError::SessionAuthenticationError("User not found".to_string()),
@@ -229,11 +262,8 @@ pub async fn view_customer(
     let user_id = user_id.unwrap();
 
     println!("User session: {}", user_id);
-    let customer: (String, String) = customers
-        .filter(id.eq(user_id))
-        .select((username, email))
-        .first(&mut conn)
-        .await // if used load then I would have got Vec<(String, String)>
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
-    Ok(HttpResponse::Ok().json(customer))
+    let customer = customers.find(user_id).await?.ok_or_else(|| {
+        CustomError::DatabaseError(DbError::QueryBuilderError("Record not found".to_string()))
+    })?;
+    Ok(HttpResponse::Ok().json((customer.username, customer.email)))
 }
@@ -1,8 +1,145 @@
-use actix_web::HttpResponse;
+use crate::db::PgPool;
+use actix_web::{web, HttpResponse};
+use diesel::sql_query;
+use diesel_async::RunQueryDsl;
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, ToSchema)]
+pub struct DependencyStatus {
+    pub healthy: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DependencyChecks {
+    pub postgres: DependencyStatus,
+    // Absent unless Redis is the session backend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis: Option<DependencyStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessReport {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub checks: DependencyChecks,
+}
 
 /******************************************/
 // Health check route
 /******************************************/
+/**
+ * @route   GET /health_check
+ * @access  Public
+ * Liveness probe: answers as long as the process is serving requests.
+ */
+#[utoipa::path(
+    get,
+    path = "/health_check",
+    tag = "health",
+    responses((status = 200, description = "Process is alive"))
+)]
 pub async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+/******************************************/
+// Readiness check route
+/******************************************/
+/**
+ * @route   GET /health_check/ready
+ * @access  Public
+ * Readiness probe: returns 503 when Postgres (or Redis, if it backs sessions) can't be reached.
+ */
+#[utoipa::path(
+    get,
+    path = "/health_check/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "All dependencies are reachable", body = ReadinessReport),
+        (status = 503, description = "At least one dependency is down", body = ReadinessReport)
+    )
+)]
+#[instrument(name = "Readiness check", skip(pool, redis_client))]
+pub async fn readiness_check(
+    pool: web::Data<PgPool>,
+    redis_client: Option<web::Data<redis::Client>>,
+) -> HttpResponse {
+    let (postgres, redis) = tokio::join!(timed_check(check_postgres(&pool)), async {
+        match &redis_client {
+            Some(client) => Some(timed_check(check_redis(client)).await),
+            None => None,
+        }
+    });
+    let healthy = postgres.healthy && redis.as_ref().is_none_or(|redis| redis.healthy);
+    let report = ReadinessReport {
+        status: if healthy { "ok" } else { "unavailable" },
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("GIT_SHA"),
+        checks: DependencyChecks { postgres, redis },
+    };
+
+    if healthy {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+async fn timed_check<F>(check: F) -> DependencyStatus
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(()) => DependencyStatus {
+            healthy: true,
+            latency_ms,
+            error: None,
+        },
+        Err(err) => {
+            tracing::warn!("Readiness check failed: {}", err);
+            DependencyStatus {
+                healthy: false,
+                latency_ms,
+                error: Some(err),
+            }
+        }
+    }
+}
+
+async fn check_postgres(pool: &PgPool) -> Result<(), String> {
+    let mut conn = pool.get().await.map_err(|err| err.to_string())?;
+    sql_query("SELECT 1")
+        .execute(&mut conn)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+async fn check_redis(client: &redis::Client) -> Result<(), String> {
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|err| err.to_string())?;
+    redis::cmd("PING")
+        .query_async::<String>(&mut conn)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
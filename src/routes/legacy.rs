@@ -0,0 +1,101 @@
+/******************************************/
+// Legacy route aliases
+/******************************************/
+// Pre-`/api/v1` paths kept alive until clients migrate. Responses on these paths carry a
+// `Deprecation` header and a `Link` to the successor, and the OpenAPI document marks them
+// deprecated. Remove an entry together with its endpoint in `routes::table`.
+pub struct LegacyRoute {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub successor_method: &'static str,
+    // `{param}` segments are filled from the legacy path's own; without a value for each
+    // there is no `Link`
+    pub successor: &'static str,
+}
+
+pub const LEGACY_ROUTES: &[LegacyRoute] = &[
+    LegacyRoute {
+        method: "POST",
+        path: "/register",
+        successor_method: "POST",
+        successor: "/api/v1/customers",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/login",
+        successor_method: "POST",
+        successor: "/api/v1/sessions",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/admin/register",
+        successor_method: "POST",
+        successor: "/api/v1/admins",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/admin/login",
+        successor_method: "POST",
+        successor: "/api/v1/admin/sessions",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/protected/logout",
+        successor_method: "DELETE",
+        successor: "/api/v1/me/session",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/protected/update",
+        successor_method: "PATCH",
+        successor: "/api/v1/me",
+    },
+    LegacyRoute {
+        method: "GET",
+        path: "/protected/view",
+        successor_method: "GET",
+        successor: "/api/v1/me",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/protected/orders/new",
+        successor_method: "POST",
+        successor: "/api/v1/orders",
+    },
+    LegacyRoute {
+        method: "GET",
+        path: "/protected/orders/{id}/view",
+        successor_method: "GET",
+        successor: "/api/v1/orders/{id}",
+    },
+    LegacyRoute {
+        method: "GET",
+        path: "/protected/orders/list/all",
+        successor_method: "GET",
+        successor: "/api/v1/orders",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/protected/admin/update_status",
+        successor_method: "PATCH",
+        successor: "/api/v1/admin/orders/{id}",
+    },
+    LegacyRoute {
+        method: "POST",
+        path: "/protected/admin/logout",
+        successor_method: "DELETE",
+        successor: "/api/v1/admin/session",
+    },
+    LegacyRoute {
+        method: "GET",
+        path: "/protected/admin/fetch_all_orders",
+        successor_method: "GET",
+        successor: "/api/v1/admin/orders",
+    },
+];
+
+pub fn find_legacy_route(method: &str, pattern: &str) -> Option<&'static LegacyRoute> {
+    LEGACY_ROUTES
+        .iter()
+        .find(|route| route.method == method && route.path == pattern)
+}
@@ -1,5 +1,8 @@
 pub mod admin;
 pub mod customer;
 pub mod health_check;
+pub mod legacy;
 pub mod order;
+pub mod payments;
 pub mod products;
+pub mod table;
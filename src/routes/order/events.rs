@@ -0,0 +1,199 @@
+use crate::{
+    db::PgPool,
+    errors::custom::{AuthError, CustomError},
+    live::{self, LiveEvents},
+    outbox::{DomainEvent, EventEnvelope},
+    routes::order::order::OrderStatus,
+    session_state::TypedSession,
+};
+use actix_web::{web, HttpRequest, Responder};
+use actix_web_lab::sse;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+const CLIENT_BUFFER: usize = 16;
+// How long browsers wait before reconnecting a dropped stream
+const RETRY_AFTER: Duration = Duration::from_secs(3);
+
+// `data` of every `order_status` event; the SSE `id` is the outbox event id
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrderStatusEvent {
+    pub order_id: Uuid,
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+    pub occurred_at: NaiveDateTime,
+}
+
+impl OrderStatusEvent {
+    // `Some` only for status changes to the given customer's orders
+    fn for_customer(envelope: &EventEnvelope, customer_id: Uuid) -> Option<Self> {
+        match &envelope.event {
+            DomainEvent::OrderStatusChanged {
+                order_id,
+                customer_id: owner,
+                from,
+                to,
+            } if *owner == customer_id => Some(Self {
+                order_id: *order_id,
+                from: *from,
+                to: *to,
+                occurred_at: envelope.occurred_at,
+            }),
+            _ => None,
+        }
+    }
+
+    fn to_sse(&self, event_id: Uuid) -> Option<sse::Event> {
+        let data = sse::Data::new_json(self).ok()?;
+        Some(data.event("order_status").id(event_id.to_string()).into())
+    }
+}
+
+/******************************************/
+// Streaming Order Status Changes
+/******************************************/
+/**
+ * @route   GET /api/v1/orders/events
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/events",
+    tag = "order",
+    params(("Last-Event-ID" = Option<Uuid>, Header, description = "Id of the last event received; missed events are replayed first")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "`text/event-stream` of `order_status` events for the caller's orders, with periodic heartbeat comments", body = OrderStatusEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Stream order events", skip(pool, live, req, session))]
+pub async fn order_events(
+    pool: web::Data<PgPool>,
+    live: web::Data<LiveEvents>,
+    req: HttpRequest,
+    session: TypedSession,
+) -> Result<impl Responder, CustomError> {
+    let customer_id = session
+        .get_user_id()
+        .map_err(|_| {
+            CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
+                "User not found".to_string(),
+            ))
+        })?
+        .ok_or_else(|| {
+            CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
+                "User not found".to_string(),
+            ))
+        })?;
+    let last_event_id = req
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Uuid::parse_str(value.trim()).ok());
+
+    // Resolved before subscribing, so anything committed before `subscribe` is replayed.
+    // A new stream, or one resuming from an id that is gone, starts from the latest change.
+    let last_seq = match last_event_id {
+        Some(last_event_id) => live::sequence_of(&pool, last_event_id).await?,
+        None => None,
+    };
+    let last_seq = match last_seq {
+        Some(last_seq) => last_seq,
+        None => live::latest_sequence(&pool, customer_id).await?,
+    };
+
+    let mut receiver = live.subscribe();
+    let (sender, events) = mpsc::channel(CLIENT_BUFFER);
+    let pool = pool.into_inner();
+    tokio::spawn(async move {
+        // A customer's status changes are numbered in commit order, so everything of theirs
+        // up to `seen_through` has been sent; replayed events that also arrive live are
+        // dropped by it
+        let mut seen_through = last_seq;
+        if !replay(&pool, &sender, customer_id, &mut seen_through).await {
+            return;
+        }
+        loop {
+            let received = tokio::select! {
+                // Ends the task as soon as the client goes away, even if its orders are quiet
+                _ = sender.closed() => return,
+                received = receiver.recv() => received,
+            };
+            match received {
+                Ok(envelope) => {
+                    let Some(event) = OrderStatusEvent::for_customer(&envelope, customer_id) else {
+                        continue;
+                    };
+                    if envelope.seq <= seen_through {
+                        continue;
+                    }
+                    seen_through = envelope.seq;
+                    if !send(&sender, &event, envelope.id).await {
+                        return;
+                    }
+                }
+                // Fell behind the channel; pick the missed events up from the outbox
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Order event stream lagged by {} events", skipped);
+                    if !replay(&pool, &sender, customer_id, &mut seen_through).await {
+                        return;
+                    }
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+
+    Ok(sse::Sse::from_infallible_receiver(events)
+        .with_keep_alive(live.heartbeat())
+        .with_retry_duration(RETRY_AFTER))
+}
+
+// Sends the customer's status changes committed after `seen_through`, a page at a time,
+// advancing it as it goes. Returns `false` once the stream should end: the client has
+// disconnected, or the outbox couldn't be read and the client is left to reconnect with
+// `Last-Event-ID`.
+async fn replay(
+    pool: &PgPool,
+    sender: &mpsc::Sender<sse::Event>,
+    customer_id: Uuid,
+    seen_through: &mut i64,
+) -> bool {
+    loop {
+        let page = match live::status_changes_since(pool, customer_id, *seen_through).await {
+            Ok(page) => page,
+            Err(err) => {
+                tracing::warn!("Failed to replay order events: {}", err);
+                return false;
+            }
+        };
+        for envelope in &page {
+            *seen_through = envelope.seq;
+            let Some(event) = OrderStatusEvent::for_customer(envelope, customer_id) else {
+                continue;
+            };
+            if !send(sender, &event, envelope.id).await {
+                return false;
+            }
+        }
+        if (page.len() as i64) < live::REPLAY_PAGE {
+            return true;
+        }
+    }
+}
+
+// Returns `false` once the client has disconnected
+async fn send(sender: &mpsc::Sender<sse::Event>, event: &OrderStatusEvent, event_id: Uuid) -> bool {
+    match event.to_sse(event_id) {
+        Some(frame) => sender.send(frame).await.is_ok(),
+        None => true,
+    }
+}
@@ -1 +1,5 @@
+pub mod events;
+#[allow(clippy::module_inception)]
 pub mod order;
+pub mod payments;
+pub mod returns;
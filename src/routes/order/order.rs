@@ -1,37 +1,118 @@
 use crate::{
-    db::PgPool,
-    errors::custom::{AuthError, CustomError, DbError},
-    schema::orders::dsl as order,
+    db_models::Order,
+    errors::custom::{AuthError, CustomError},
+    metrics::METRICS,
+    pricing::Pricing,
+    repository::details::OrderDetail,
+    repository::pagination::{OrderFilter, OrderPageRequest, OrderSort},
+    repository::{CancelOutcome, OrderRepository, ProductRepository},
     session_state::TypedSession,
 };
 use actix_web::{web, HttpResponse};
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use chrono::NaiveDateTime;
 use diesel_derive_enum;
 use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct CreateOrder {
     pub product_id: Uuid,
+    /// Tax region of the delivery address, e.g. `US-CA`; must have a configured rate. Orders
+    /// without one are taxed at the default rate
+    pub region: Option<String>,
 }
-#[derive(Debug, diesel_derive_enum::DbEnum, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    diesel_derive_enum::DbEnum,
+    serde::Serialize,
+    serde::Deserialize,
+    ToSchema,
+)]
 #[ExistingTypePath = "crate::schema::sql_types::OrderStatus"]
 pub enum OrderStatus {
     Pending,
     Shipped,
     Delivered,
+    Cancelled,
+}
+
+// Longest cancellation reason accepted, in characters
+const MAX_REASON_LENGTH: usize = 500;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct CancelOrderBody {
+    /// Why the order is being cancelled; required, at most 500 characters
+    pub reason: String,
+}
+
+impl CancelOrderBody {
+    pub fn validated_reason(&self) -> Result<&str, CustomError> {
+        let reason = self.reason.trim();
+        if reason.is_empty() {
+            return Err(CustomError::ValidationError(
+                "A cancellation reason is required".to_string(),
+            ));
+        }
+        if reason.chars().count() > MAX_REASON_LENGTH {
+            return Err(CustomError::ValidationError(format!(
+                "Cancellation reason is longer than {} characters",
+                MAX_REASON_LENGTH
+            )));
+        }
+        Ok(reason)
+    }
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+pub struct OrderListQuery {
+    /// Defaults to 50, capped at 200
+    pub limit: Option<i64>,
+    /// `next_cursor` or `prev_cursor` of a previous page
+    pub cursor: Option<String>,
+    pub status: Option<OrderStatus>,
+    pub product_id: Option<Uuid>,
+    /// Only orders created at or after this time, e.g. `2024-11-01T00:00:00`
+    pub created_from: Option<NaiveDateTime>,
+    /// Only orders created before this time
+    pub created_to: Option<NaiveDateTime>,
+    /// `newest` (default) or `oldest` first
+    pub sort: Option<OrderSort>,
 }
 /******************************************/
 // New Order Creation route
 /******************************************/
 /**
- * @route   POST /protected/orders/new
+ * @route   POST /api/v1/orders
+ * @legacy  POST /protected/orders/new (deprecated alias)
  * @access  JWT Protected
  */
-#[instrument(name = "Create new Order", skip(req_order, pool, session))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders",
+    tag = "order",
+    request_body = CreateOrder,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Order created, with what it costs", body = Object, example = json!({"message": "Order created successfully", "order_id": "5fcd7d83-7adf-4d4d-931a-68b9678009db", "totals": {"unit_price": 50000, "subtotal": 50000, "discount": 0, "shipping": 0, "tax": 3625, "grand_total": 53625, "tax_region": "US-CA", "tax_rate_bps": 725}})),
+        (status = 400, description = "Malformed region, or one without a tax rate", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No such product", body = ErrorBody),
+        (status = 409, description = "The product is out of stock", body = ErrorBody)
+    )
+)]
+#[instrument(
+    name = "Create new Order",
+    skip(req_order, orders, products, pricing, session)
+)]
 pub async fn create_order(
-    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
+    products: web::Data<dyn ProductRepository>,
+    pricing: web::Data<Pricing>,
     req_order: web::Json<CreateOrder>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
@@ -40,7 +121,6 @@ pub async fn create_order(
             "User not found".to_string(),
         ))
     })?;
-    let pool = pool.clone();
     let order_data = req_order.into_inner();
     let order_id = Uuid::new_v4();
     let order_created_at = chrono::Local::now().naive_utc();
@@ -51,40 +131,54 @@ pub async fn create_order(
     }
 
     let customer_id = customer_id.unwrap();
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
-    let result = diesel::insert_into(order::orders)
-        .values((
-            order::id.eq(order_id),
-            order::customer_id.eq(customer_id),
-            order::product_id.eq(order_data.product_id),
-            order::created_at.eq(order_created_at),
-            order::status.eq(OrderStatus::Pending),
-        ))
-        .execute(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
-    if result == 0 {
-        return Err(CustomError::DatabaseError(DbError::UpdationError(
-            "Failed data update data in db".to_string(),
-        )));
-    }
+    let product = products
+        .find(order_data.product_id)
+        .await?
+        .ok_or_else(|| CustomError::NotFoundError(format!("Product {}", order_data.product_id)))?;
+    // There are no promotions yet, so nothing is discounted
+    let totals = pricing
+        .quote(product.price, 0, order_data.region.as_deref())
+        .await?;
+    orders
+        .insert_with_totals(
+            Order {
+                id: order_id,
+                customer_id,
+                status: OrderStatus::Pending,
+                created_at: order_created_at,
+                product_id: product.id,
+            },
+            totals.clone(),
+        )
+        .await?;
+    METRICS.orders_created_total.inc();
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({"message": "Order created successfully".to_string(), "order_id": order_id})))
+    Ok(HttpResponse::Ok().json(serde_json::json!({"message": "Order created successfully".to_string(), "order_id": order_id, "totals": totals})))
 }
 
 /******************************************/
 // Reteriving Order using id
 /******************************************/
 /**
- * @route   Get /protected/orders/{id}/view
+ * @route   GET /api/v1/orders/{id}
+ * @legacy  GET /protected/orders/{id}/view (deprecated alias)
  * @access  JWT Protected
  */
-#[instrument(name = "Get Order", skip(order_id, pool, session))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}",
+    tag = "order",
+    params(("id" = Uuid, Path, description = "Order id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The order with its product", body = OrderDetail),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No such order for this customer", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Get Order", skip(order_id, orders, session))]
 pub async fn get_order(
-    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
     order_id: web::Path<Uuid>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
@@ -99,31 +193,98 @@ pub async fn get_order(
         ));
     }
 
-    let _customer_id = customer_id.unwrap();
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
-    let order: (Uuid, Uuid, OrderStatus) = order::orders
-        .filter(order::id.eq(order_id.into_inner()))
-        .select((order::product_id, order::customer_id, order::status))
-        .first(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
-
-    Ok(HttpResponse::Ok().json(order))
+    let customer_id = customer_id.unwrap();
+    let order_id = order_id.into_inner();
+    // Other customers' orders are reported as missing rather than forbidden
+    let order = orders
+        .find_detail(order_id)
+        .await?
+        .filter(|order| order.customer_id() == Some(customer_id))
+        .ok_or_else(|| CustomError::NotFoundError(format!("Order {}", order_id)))?;
+
+    Ok(HttpResponse::Ok().json(order.without_customer()))
 }
 
 /******************************************/
 // Reteriving All Orders of a customer
 /******************************************/
 /**
- * @route   Get /protected/orders/list/all
+ * @route   GET /api/v1/orders
+ * @legacy  GET /protected/orders/list/all (deprecated alias)
  * @access  JWT Protected
  */
-#[instrument(name = "Get All Orders by customer", skip(pool, session))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders",
+    tag = "order",
+    params(OrderListQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "One page of the customer's orders", body = OrderPage),
+        (status = 400, description = "Invalid cursor or filter", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Get All Orders by customer", skip(orders, query, session))]
 pub async fn list_orders(
-    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
+    query: web::Query<OrderListQuery>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let customer_id = session.get_user_id().map_err(|_| {
+        CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
+            "User not found".to_string(),
+        ))
+    })?;
+    if customer_id.is_none() {
+        return Err(CustomError::AuthenticationError(
+            AuthError::SessionAuthenticationError("User not found".to_string()),
+        ));
+    }
+
+    let customer_id = customer_id.unwrap();
+    let query = query.into_inner();
+    let filter = OrderFilter {
+        status: query.status,
+        customer_id: Some(customer_id),
+        product_id: query.product_id,
+        created_from: query.created_from,
+        created_to: query.created_to,
+    };
+    let request = OrderPageRequest::parse(filter, query.sort, query.limit, query.cursor.as_deref())
+        .map_err(CustomError::ValidationError)?;
+    let page = orders.list_page(&request).await?;
+
+    Ok(HttpResponse::Ok().json(page.without_customers()))
+}
+
+/******************************************/
+// Cancelling an Order
+/******************************************/
+/**
+ * @route   POST /api/v1/orders/{id}/cancel
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders/{id}/cancel",
+    tag = "order",
+    params(("id" = Uuid, Path, description = "Order id")),
+    request_body = CancelOrderBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The cancelled order", body = OrderDetail),
+        (status = 400, description = "Missing or overlong reason", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No such order for this customer", body = ErrorBody),
+        (status = 409, description = "The order is no longer pending or holds an unsettled payment", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Cancel Order", skip(order_id, orders, req_cancel, session))]
+pub async fn cancel_order(
+    orders: web::Data<dyn OrderRepository>,
+    order_id: web::Path<Uuid>,
+    req_cancel: web::Json<CancelOrderBody>,
     session: TypedSession,
 ) -> Result<HttpResponse, CustomError> {
     let customer_id = session.get_user_id().map_err(|_| {
@@ -138,18 +299,335 @@ pub async fn list_orders(
     }
 
     let customer_id = customer_id.unwrap();
+    let order_id = order_id.into_inner();
+    let reason = req_cancel.validated_reason()?;
+    let owned = orders
+        .find(order_id)
+        .await?
+        .is_some_and(|order| order.customer_id == customer_id);
+    if !owned {
+        return Err(CustomError::NotFoundError(format!("Order {}", order_id)));
+    }
+    let order = cancel(orders.get_ref(), order_id, reason, None).await?;
+
+    Ok(HttpResponse::Ok().json(order.without_customer()))
+}
+
+// Shared by the customer and admin cancel routes; returns the order as it is now
+pub async fn cancel(
+    orders: &dyn OrderRepository,
+    order_id: Uuid,
+    reason: &str,
+    admin_id: Option<Uuid>,
+) -> Result<OrderDetail, CustomError> {
+    match orders.cancel(order_id, reason, admin_id).await? {
+        CancelOutcome::Cancelled => {}
+        CancelOutcome::NotFound => {
+            return Err(CustomError::NotFoundError(format!("Order {}", order_id)))
+        }
+        CancelOutcome::NotCancellable(status) => {
+            return Err(CustomError::ConflictError(format!(
+                "Order {} is {:?} and can no longer be cancelled",
+                order_id, status
+            )))
+        }
+        CancelOutcome::PaymentOutstanding(payment) => {
+            return Err(CustomError::ConflictError(format!(
+                "Order {} has a {:?} payment; void or refund it before cancelling",
+                order_id, payment
+            )))
+        }
+    }
+    METRICS
+        .order_status_transitions_total
+        .with_label_values(&["cancelled"])
+        .inc();
+    orders
+        .find_detail(order_id)
+        .await?
+        .ok_or_else(|| CustomError::NotFoundError(format!("Order {}", order_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cancel_order, create_order, get_order, list_orders, OrderStatus};
+    use crate::config::configuration::PricingSettings;
+    use crate::db_models::Product;
+    use crate::payments::PaymentStatus;
+    use crate::pricing::Pricing;
+    use crate::repository::details::OrderDetail;
+    use crate::repository::pagination::OrderPage;
+    use crate::repository::Repositories;
+    use crate::routes::customer::customer::register_customer;
+    use crate::session_store::MemorySessionStore;
+    use actix_session::SessionMiddleware;
+    use actix_web::cookie::Key;
+    use actix_web::{test, web, App};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    // Handlers wired to in-memory repositories; no database or Redis involved
+    macro_rules! order_app {
+        ($repositories:expr) => {{
+            let repositories: Repositories = $repositories;
+            test::init_service(
+                App::new()
+                    .wrap(SessionMiddleware::new(
+                        MemorySessionStore::default(),
+                        Key::generate(),
+                    ))
+                    .configure(|cfg| repositories.configure(cfg))
+                    .app_data(web::Data::new(Pricing::from_settings(
+                        &PricingSettings::default(),
+                    )))
+                    .route("/customers", web::post().to(register_customer))
+                    .route("/orders", web::post().to(create_order))
+                    .route("/orders", web::get().to(list_orders))
+                    .route("/orders/{id}", web::get().to(get_order))
+                    .route("/orders/{id}/cancel", web::post().to(cancel_order)),
+            )
+            .await
+        }};
+    }
+
+    // Registers a customer through `app` and yields their session cookie
+    macro_rules! session_cookie {
+        ($app:expr) => {{
+            let register = test::call_service(
+                &$app,
+                test::TestRequest::post()
+                    .uri("/customers")
+                    .set_json(json!({
+                        "username": "kashish",
+                        "password": "password",
+                        "email": "kk@gmail.com"
+                    }))
+                    .to_request(),
+            )
+            .await;
+            assert!(register.status().is_success());
+            register
+                .response()
+                .cookies()
+                .next()
+                .expect("Session cookie not set")
+                .into_owned()
+        }};
+    }
+
+    #[actix_web::test]
+    async fn orders_are_created_and_listed_for_the_session_customer() {
+        let repositories = Repositories::in_memory();
+        let product = Product {
+            id: Uuid::new_v4(),
+            name: "Keyboard".to_string(),
+            is_available: true,
+            price: 4999,
+            sku: None,
+            stock: None,
+        };
+        repositories.products.insert(product.clone()).await.unwrap();
+        let app = order_app!(repositories.clone());
+        let cookie = session_cookie!(app);
 
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+        let product_id = product.id;
+        let created: Value = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::post()
+                .uri("/orders")
+                .cookie(cookie.clone())
+                .set_json(json!({ "product_id": product_id }))
+                .to_request(),
+        )
+        .await;
+        let order_id: Uuid = serde_json::from_value(created["order_id"].clone()).unwrap();
+        assert_eq!(created["totals"]["grand_total"], 4999);
 
-    let order = order::orders
-        .filter(order::customer_id.eq(customer_id))
-        .select((order::id, order::product_id))
-        .load::<(Uuid, Uuid)>(&mut conn)
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
+        let listed: OrderPage = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get()
+                .uri("/orders?limit=10")
+                .cookie(cookie.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(listed.orders.len(), 1);
+        assert_eq!(listed.orders[0].id, order_id);
+        assert_eq!(listed.orders[0].product.id, product_id);
+        assert_eq!(listed.orders[0].product.name, "Keyboard");
+        assert!(listed.orders[0].customer.is_none());
+        assert!(listed.next_cursor.is_none() && listed.prev_cursor.is_none());
 
-    Ok(HttpResponse::Ok().json(order))
+        let fetched: OrderDetail = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get()
+                .uri(&format!("/orders/{}", order_id))
+                .cookie(cookie.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(fetched.id, order_id);
+        assert_eq!(fetched.product.price, 4999);
+        assert_eq!(fetched.totals.map(|totals| totals.unit_price), Some(4999));
+        assert_eq!(fetched.status, OrderStatus::Pending);
+
+        let missing = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri(&format!("/orders/{}", Uuid::new_v4()))
+                .cookie(cookie.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(missing.status().as_u16(), 404);
+
+        let unknown_product = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/orders")
+                .cookie(cookie)
+                .set_json(json!({ "product_id": Uuid::new_v4() }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(unknown_product.status().as_u16(), 404);
+    }
+
+    #[actix_web::test]
+    async fn only_pending_orders_can_be_cancelled() {
+        let (repositories, orders) = Repositories::in_memory_with_orders();
+        let product = Product {
+            id: Uuid::new_v4(),
+            name: "Keyboard".to_string(),
+            is_available: true,
+            price: 4999,
+            sku: None,
+            stock: None,
+        };
+        repositories.products.insert(product.clone()).await.unwrap();
+        let app = order_app!(repositories.clone());
+        let cookie = session_cookie!(app);
+        let mut order_ids = Vec::new();
+        for _ in 0..3 {
+            let created: Value = test::call_and_read_body_json(
+                &app,
+                test::TestRequest::post()
+                    .uri("/orders")
+                    .cookie(cookie.clone())
+                    .set_json(json!({ "product_id": product.id }))
+                    .to_request(),
+            )
+            .await;
+            order_ids.push(serde_json::from_value::<Uuid>(created["order_id"].clone()).unwrap());
+        }
+        // Nothing ships before the payment is captured
+        assert!(repositories
+            .orders
+            .update_status(order_ids[1], OrderStatus::Shipped)
+            .await
+            .is_err());
+        orders.record_payment(order_ids[1], PaymentStatus::Captured);
+        repositories
+            .orders
+            .update_status(order_ids[1], OrderStatus::Shipped)
+            .await
+            .unwrap();
+        orders.record_payment(order_ids[2], PaymentStatus::Authorized);
+        let cancel = |order_id: Uuid, reason: &str| {
+            test::TestRequest::post()
+                .uri(&format!("/orders/{}/cancel", order_id))
+                .cookie(cookie.clone())
+                .set_json(json!({ "reason": reason }))
+                .to_request()
+        };
+
+        let blank = test::call_service(&app, cancel(order_ids[0], "  ")).await;
+        assert_eq!(blank.status().as_u16(), 400);
+
+        let cancelled: OrderDetail =
+            test::call_and_read_body_json(&app, cancel(order_ids[0], "Ordered twice")).await;
+        assert_eq!(cancelled.status, OrderStatus::Cancelled);
+        let cancellation = cancelled.cancellation.expect("Cancellation not recorded");
+        assert_eq!(cancellation.reason, "Ordered twice");
+        assert!(cancellation.admin_id.is_none());
+
+        let again = test::call_service(&app, cancel(order_ids[0], "Ordered twice")).await;
+        assert_eq!(again.status().as_u16(), 409);
+        let shipped = test::call_service(&app, cancel(order_ids[1], "Too slow")).await;
+        assert_eq!(shipped.status().as_u16(), 409);
+        let unknown = test::call_service(&app, cancel(Uuid::new_v4(), "Too slow")).await;
+        assert_eq!(unknown.status().as_u16(), 404);
+
+        // A held payment has to be voided first
+        let authorized = test::call_service(&app, cancel(order_ids[2], "Too slow")).await;
+        assert_eq!(authorized.status().as_u16(), 409);
+        orders.record_payment(order_ids[2], PaymentStatus::Voided);
+        let voided = test::call_service(&app, cancel(order_ids[2], "Too slow")).await;
+        assert!(voided.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn orders_hold_stock_until_they_are_cancelled() {
+        let repositories = Repositories::in_memory();
+        let product = Product {
+            id: Uuid::new_v4(),
+            name: "Keyboard".to_string(),
+            is_available: true,
+            price: 4999,
+            sku: None,
+            stock: Some(1),
+        };
+        repositories.products.insert(product.clone()).await.unwrap();
+        let app = order_app!(repositories.clone());
+        let cookie = session_cookie!(app);
+        let order = || {
+            test::TestRequest::post()
+                .uri("/orders")
+                .cookie(cookie.clone())
+                .set_json(json!({ "product_id": product.id }))
+                .to_request()
+        };
+        let stock = || async {
+            repositories
+                .products
+                .find(product.id)
+                .await
+                .unwrap()
+                .and_then(|product| product.stock)
+        };
+
+        let created: Value = test::call_and_read_body_json(&app, order()).await;
+        let order_id: Uuid = serde_json::from_value(created["order_id"].clone()).unwrap();
+        assert_eq!(stock().await, Some(0));
+        let sold_out = test::call_service(&app, order()).await;
+        assert_eq!(sold_out.status().as_u16(), 409);
+
+        let cancelled = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri(&format!("/orders/{}/cancel", order_id))
+                .cookie(cookie.clone())
+                .set_json(json!({ "reason": "Ordered twice" }))
+                .to_request(),
+        )
+        .await;
+        assert!(cancelled.status().is_success());
+        assert_eq!(stock().await, Some(1));
+        let reordered = test::call_service(&app, order()).await;
+        assert!(reordered.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn creating_an_order_requires_a_customer_session() {
+        let app = order_app!(Repositories::in_memory());
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/orders")
+                .set_json(json!({ "product_id": Uuid::new_v4() }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
 }
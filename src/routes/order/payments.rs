@@ -0,0 +1,100 @@
+use super::returns::{customer_id, require_owned_order};
+use crate::{
+    db::PgPool,
+    errors::custom::CustomError,
+    payments::{self, PaymentProvider},
+    repository::OrderRepository,
+    session_state::TypedSession,
+};
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct PayOrderBody {
+    /// Token from the payment provider's client SDK; with the mock provider `tok_decline`
+    /// is declined and anything else approved
+    pub payment_method: String,
+}
+
+/******************************************/
+// Paying for an Order
+/******************************************/
+/**
+ * @route   POST /api/v1/orders/{id}/payments
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders/{id}/payments",
+    tag = "order",
+    params(("id" = Uuid, Path, description = "Order id")),
+    request_body = PayOrderBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 201, description = "Payment authorized; the order ships once an admin captures it", body = Payment),
+        (status = 400, description = "Missing or overlong payment method", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 402, description = "Declined by the provider; the attempt is kept as `Failed`", body = ErrorBody),
+        (status = 404, description = "No such order for this customer", body = ErrorBody),
+        (status = 409, description = "The order isn't pending or is already paid for", body = ErrorBody),
+        (status = 502, description = "The payment provider couldn't be reached", body = ErrorBody)
+    )
+)]
+#[instrument(
+    name = "Pay for order",
+    skip(pool, provider, order_id, req_pay, session)
+)]
+pub async fn pay_for_order(
+    pool: web::Data<PgPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    order_id: web::Path<Uuid>,
+    req_pay: web::Json<PayOrderBody>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let customer_id = customer_id(&session)?;
+    let payment = payments::authorize(
+        &pool,
+        provider.get_ref(),
+        customer_id,
+        order_id.into_inner(),
+        &req_pay.payment_method,
+    )
+    .await?;
+    Ok(HttpResponse::Created().json(payment))
+}
+
+/******************************************/
+// Listing an Order's Payments
+/******************************************/
+/**
+ * @route   GET /api/v1/orders/{id}/payments
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}/payments",
+    tag = "order",
+    params(("id" = Uuid, Path, description = "Order id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Every payment attempt for the order, oldest first", body = [Payment]),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No such order for this customer", body = ErrorBody)
+    )
+)]
+#[instrument(name = "List order payments", skip(pool, orders, order_id, session))]
+pub async fn list_order_payments(
+    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
+    order_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let customer_id = customer_id(&session)?;
+    let order_id = order_id.into_inner();
+    require_owned_order(orders.get_ref(), order_id, customer_id).await?;
+    let payments = payments::list_for_order(&pool, order_id).await?;
+    Ok(HttpResponse::Ok().json(payments))
+}
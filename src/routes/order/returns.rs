@@ -0,0 +1,151 @@
+use crate::{
+    db::PgPool,
+    errors::custom::{AuthError, CustomError},
+    repository::OrderRepository,
+    returns::{self, ReturnedItem},
+    session_state::TypedSession,
+};
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Deserialize, ToSchema)]
+pub struct RequestReturnBody {
+    /// The products being sent back, each with its own reason
+    pub items: Vec<ReturnedItem>,
+}
+
+pub(super) fn customer_id(session: &TypedSession) -> Result<Uuid, CustomError> {
+    session
+        .get_user_id()
+        .map_err(|_| {
+            CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
+                "User not found".to_string(),
+            ))
+        })?
+        .ok_or_else(|| {
+            CustomError::AuthenticationError(AuthError::SessionAuthenticationError(
+                "User not found".to_string(),
+            ))
+        })
+}
+
+// Other customers' orders are reported as missing rather than forbidden
+pub(super) async fn require_owned_order(
+    orders: &dyn OrderRepository,
+    order_id: Uuid,
+    customer_id: Uuid,
+) -> Result<(), CustomError> {
+    match orders.find(order_id).await? {
+        Some(order) if order.customer_id == customer_id => Ok(()),
+        _ => Err(CustomError::NotFoundError(format!("Order {}", order_id))),
+    }
+}
+
+/******************************************/
+// Requesting a Return
+/******************************************/
+/**
+ * @route   POST /api/v1/orders/{id}/returns
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders/{id}/returns",
+    tag = "order",
+    params(("id" = Uuid, Path, description = "Order id")),
+    request_body = RequestReturnBody,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 201, description = "Return requested; an admin approves or rejects it", body = ReturnDetail),
+        (status = 400, description = "No items, a product not on the order, or a missing reason", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No such order for this customer", body = ErrorBody),
+        (status = 409, description = "The order isn't delivered or already has a return in progress", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Request return", skip(pool, order_id, req_return, session))]
+pub async fn request_return(
+    pool: web::Data<PgPool>,
+    order_id: web::Path<Uuid>,
+    req_return: web::Json<RequestReturnBody>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let customer_id = customer_id(&session)?;
+    let detail = returns::request_return(
+        &pool,
+        customer_id,
+        order_id.into_inner(),
+        req_return.into_inner().items,
+    )
+    .await?;
+    Ok(HttpResponse::Created().json(detail))
+}
+
+/******************************************/
+// Listing an Order's Returns
+/******************************************/
+/**
+ * @route   GET /api/v1/orders/{id}/returns
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}/returns",
+    tag = "order",
+    params(("id" = Uuid, Path, description = "Order id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Returns for the order, oldest first", body = [ReturnDetail]),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No such order for this customer", body = ErrorBody)
+    )
+)]
+#[instrument(name = "List order returns", skip(pool, orders, order_id, session))]
+pub async fn list_order_returns(
+    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
+    order_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let customer_id = customer_id(&session)?;
+    let order_id = order_id.into_inner();
+    require_owned_order(orders.get_ref(), order_id, customer_id).await?;
+    let details = returns::list_for_order(&pool, order_id).await?;
+    Ok(HttpResponse::Ok().json(details))
+}
+
+/******************************************/
+// Order Timeline
+/******************************************/
+/**
+ * @route   GET /api/v1/orders/{id}/timeline
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}/timeline",
+    tag = "order",
+    params(("id" = Uuid, Path, description = "Order id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Each step of the order and its returns, oldest first", body = [TimelineEntry]),
+        (status = 401, description = "Not logged in", body = ErrorBody),
+        (status = 404, description = "No such order for this customer", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Get order timeline", skip(pool, orders, order_id, session))]
+pub async fn order_timeline(
+    pool: web::Data<PgPool>,
+    orders: web::Data<dyn OrderRepository>,
+    order_id: web::Path<Uuid>,
+    session: TypedSession,
+) -> Result<HttpResponse, CustomError> {
+    let customer_id = customer_id(&session)?;
+    let order_id = order_id.into_inner();
+    require_owned_order(orders.get_ref(), order_id, customer_id).await?;
+    let timeline = returns::order_timeline(&pool, order_id).await?;
+    Ok(HttpResponse::Ok().json(timeline))
+}
@@ -0,0 +1,69 @@
+use crate::config::configuration::PaymentSettings;
+use crate::db::PgPool;
+use crate::errors::custom::CustomError;
+use crate::payments::webhook::{self, PaymentWebhookEvent, WebhookOutcome};
+use crate::payments::PaymentProvider;
+use crate::webhooks::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct WebhookReceipt {
+    pub event_id: String,
+    pub outcome: WebhookOutcome,
+}
+
+/******************************************/
+// Payment Provider Callback Route
+/******************************************/
+/**
+ * @route   POST /api/v1/payments/webhook
+ * @access  Public, HMAC signed by the payment provider
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/payments/webhook",
+    tag = "payments",
+    request_body = PaymentWebhookEvent,
+    params(
+        ("X-Webhook-Timestamp" = i64, Header, description = "Unix seconds when the provider signed the event"),
+        ("X-Webhook-Signature" = String, Header, description = "`sha256=<hex>` HMAC of `{timestamp}.{body}`")
+    ),
+    responses(
+        (status = 200, description = "Received; also returned for redeliveries", body = WebhookReceipt),
+        (status = 400, description = "Body isn't a payment event", body = ErrorBody),
+        (status = 401, description = "Bad or missing signature, or a stale timestamp", body = ErrorBody),
+        (status = 404, description = "No payment with that reference yet; the provider retries", body = ErrorBody)
+    )
+)]
+#[instrument(name = "Payment webhook", skip(req, body, pool, provider, settings))]
+pub async fn payment_webhook(
+    req: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    settings: web::Data<PaymentSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    webhook::check_signature(
+        &settings,
+        header(TIMESTAMP_HEADER),
+        header(SIGNATURE_HEADER),
+        &body,
+        chrono::Utc::now().timestamp(),
+    )?;
+    let payload: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|err| CustomError::ValidationError(format!("Invalid payment event: {}", err)))?;
+    let event: PaymentWebhookEvent = serde_json::from_value(payload.clone())
+        .map_err(|err| CustomError::ValidationError(format!("Invalid payment event: {}", err)))?;
+    let event_id = event.id.clone();
+    let outcome = webhook::receive(&pool, provider.name(), event, payload).await?;
+    tracing::info!("Payment event {} {:?}", event_id, outcome);
+    Ok(HttpResponse::Ok().json(WebhookReceipt { event_id, outcome }))
+}
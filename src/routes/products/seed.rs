@@ -1,45 +1,252 @@
-use crate::db::PgPool;
+use crate::db_models::Product;
 use crate::errors::custom::{CustomError, DbError};
-use crate::schema::products::dsl as product_dsl;
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use crate::repository::ProductRepository;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
 use uuid::Uuid;
+
+// One product row in a fixture file; `sku` is the stable key rows are matched on
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ProductFixture {
+    pub sku: String,
+    pub name: String,
+    pub price: i32,
+    #[serde(default = "default_available")]
+    pub is_available: bool,
+    // Units on hand when the product is first seeded; left out, stock isn't tracked
+    #[serde(default)]
+    pub stock: Option<i32>,
+}
+
+fn default_available() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct TomlFixture {
+    products: Vec<ProductFixture>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SeedReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
 /******************************************/
-// Adding seed data to products table
+// Parsing product fixtures
 /******************************************/
-pub async fn seed_products(pool: PgPool) -> Result<(), CustomError> {
-    let data = vec![
-        (Uuid::new_v4(), "Laptop".to_string(), true, 50000),
-        (Uuid::new_v4(), "Smart Phone".to_string(), true, 20000),
-        (Uuid::new_v4(), "Dress".to_string(), true, 5000),
-        (Uuid::new_v4(), "Bottle".to_string(), true, 1000),
-        (Uuid::new_v4(), "Cap".to_string(), true, 500),
-    ];
-    let mut conn = pool
-        .get()
-        .await
-        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
-    for (id, name, is_available, price) in data {
-        let result = diesel::insert_into(product_dsl::products)
-            .values((
-                product_dsl::id.eq(id),
-                product_dsl::name.eq(name),
-                product_dsl::is_available.eq(is_available),
-                product_dsl::price.eq(price),
-            ))
-            .execute(&mut conn)
-            .await
-            .map_err(|err| {
-                CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string()))
-            })?;
-
-        if result == 0 {
-            return Err(CustomError::DatabaseError(DbError::UpdationError(
-                "Failed data insert data in db".to_string(),
-            )));
+// TOML uses `[[products]]` tables, JSON a top-level array and CSV a
+// `sku,name,price,is_available` header with an optional `stock` column; the format follows
+// the file extension.
+pub fn parse_fixtures(path: &Path) -> Result<Vec<ProductFixture>, CustomError> {
+    let contents = std::fs::read_to_string(path).map_err(|err| {
+        CustomError::FixtureError(format!("Failed to read {}: {}", path.display(), err))
+    })?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let fixtures = match extension.as_str() {
+        "toml" => toml::from_str::<TomlFixture>(&contents)
+            .map(|fixture| fixture.products)
+            .map_err(|err| err.to_string()),
+        "json" => {
+            serde_json::from_str::<Vec<ProductFixture>>(&contents).map_err(|err| err.to_string())
         }
+        "csv" => csv::Reader::from_reader(contents.as_bytes())
+            .deserialize::<ProductFixture>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.to_string()),
+        other => Err(format!("Unsupported fixture format '{}'", other)),
     }
+    .map_err(|err| CustomError::FixtureError(format!("{}: {}", path.display(), err)))?;
 
-    println!("successfully added products");
+    validate_fixtures(&fixtures)?;
+    Ok(fixtures)
+}
+
+fn validate_fixtures(fixtures: &[ProductFixture]) -> Result<(), CustomError> {
+    let mut seen = HashSet::new();
+    for fixture in fixtures {
+        if fixture.sku.trim().is_empty() || fixture.name.trim().is_empty() {
+            return Err(CustomError::FixtureError(
+                "Every product needs a sku and a name".to_string(),
+            ));
+        }
+        if fixture.price < 0 {
+            return Err(CustomError::FixtureError(format!(
+                "{} has a negative price",
+                fixture.sku
+            )));
+        }
+        if fixture.stock.is_some_and(|stock| stock < 0) {
+            return Err(CustomError::FixtureError(format!(
+                "{} has a negative stock",
+                fixture.sku
+            )));
+        }
+        if !seen.insert(fixture.sku.as_str()) {
+            return Err(CustomError::FixtureError(format!(
+                "{} appears more than once",
+                fixture.sku
+            )));
+        }
+    }
     Ok(())
 }
+
+/******************************************/
+// Adding seed data to products table
+/******************************************/
+// Safe to re-run: unchanged rows are skipped and changed rows updated in place,
+// so product ids (and the orders pointing at them) stay stable. Stock is only set on
+// insert; after that orders move it, and re-seeding must not undo their reservations.
+pub async fn seed_products(
+    products: &dyn ProductRepository,
+    fixtures: &[ProductFixture],
+) -> Result<SeedReport, CustomError> {
+    let mut report = SeedReport::default();
+
+    for fixture in fixtures {
+        match products.find_by_sku(&fixture.sku).await? {
+            None => {
+                products
+                    .insert(Product {
+                        id: Uuid::new_v4(),
+                        name: fixture.name.clone(),
+                        is_available: fixture.is_available,
+                        price: fixture.price,
+                        sku: Some(fixture.sku.clone()),
+                        stock: fixture.stock,
+                    })
+                    .await?;
+                report.inserted += 1;
+            }
+            Some(existing)
+                if existing.name == fixture.name
+                    && existing.price == fixture.price
+                    && existing.is_available == fixture.is_available =>
+            {
+                report.skipped += 1;
+            }
+            Some(existing) => {
+                let updated = products
+                    .update(Product {
+                        name: fixture.name.clone(),
+                        is_available: fixture.is_available,
+                        price: fixture.price,
+                        ..existing
+                    })
+                    .await?;
+                if !updated {
+                    return Err(CustomError::DatabaseError(DbError::UpdationError(format!(
+                        "Product {} disappeared while seeding",
+                        fixture.sku
+                    ))));
+                }
+                report.updated += 1;
+            }
+        }
+    }
+
+    tracing::info!(
+        inserted = report.inserted,
+        updated = report.updated,
+        skipped = report.skipped,
+        "Seeded products"
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_fixtures, ProductFixture};
+    use claim::assert_err;
+    use std::path::PathBuf;
+
+    fn write_fixture(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", uuid::Uuid::new_v4(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn laptop() -> ProductFixture {
+        ProductFixture {
+            sku: "LAPTOP-001".to_string(),
+            name: "Laptop".to_string(),
+            price: 50000,
+            is_available: true,
+            stock: None,
+        }
+    }
+
+    #[test]
+    fn toml_json_and_csv_fixtures_parse_the_same() {
+        let toml = write_fixture(
+            "products.toml",
+            "[[products]]\nsku = \"LAPTOP-001\"\nname = \"Laptop\"\nprice = 50000\n",
+        );
+        let json = write_fixture(
+            "products.json",
+            r#"[{"sku": "LAPTOP-001", "name": "Laptop", "price": 50000}]"#,
+        );
+        let csv = write_fixture(
+            "products.csv",
+            "sku,name,price,is_available\nLAPTOP-001,Laptop,50000,true\n",
+        );
+
+        for path in [toml, json, csv] {
+            assert_eq!(parse_fixtures(&path).unwrap(), vec![laptop()]);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn stock_is_optional_in_every_format() {
+        let toml = write_fixture(
+            "products.toml",
+            "[[products]]\nsku = \"LAPTOP-001\"\nname = \"Laptop\"\nprice = 50000\nstock = 3\n",
+        );
+        let csv = write_fixture(
+            "products.csv",
+            "sku,name,price,is_available,stock\nLAPTOP-001,Laptop,50000,true,3\n",
+        );
+        let negative = write_fixture(
+            "products.json",
+            r#"[{"sku": "LAPTOP-001", "name": "Laptop", "price": 50000, "stock": -1}]"#,
+        );
+
+        for path in [toml, csv] {
+            let stocked = ProductFixture {
+                stock: Some(3),
+                ..laptop()
+            };
+            assert_eq!(parse_fixtures(&path).unwrap(), vec![stocked]);
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_err!(parse_fixtures(&negative));
+        std::fs::remove_file(negative).unwrap();
+    }
+
+    #[test]
+    fn duplicate_skus_are_rejected() {
+        let path = write_fixture(
+            "products.json",
+            r#"[{"sku": "A", "name": "One", "price": 1}, {"sku": "A", "name": "Two", "price": 2}]"#,
+        );
+        assert_err!(parse_fixtures(&path));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unknown_extensions_are_rejected() {
+        let path = write_fixture("products.yaml", "products: []");
+        assert_err!(parse_fixtures(&path));
+        std::fs::remove_file(path).unwrap();
+    }
+}
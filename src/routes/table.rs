@@ -0,0 +1,197 @@
+use crate::metrics::metrics_endpoint;
+use crate::routes::{
+    admin::admin::{
+        admin_cancel_order, fetch_all_orders, fetch_order, login_admin, logout_admin,
+        register_admin, update_order_status, update_status,
+    },
+    admin::jobs::{get_job, list_jobs, requeue_job},
+    admin::live_orders::live_orders,
+    admin::payments::{admin_order_payments, capture_payment, refund_payment, void_payment},
+    admin::returns::{admin_order_timeline, get_return, list_returns, update_return},
+    admin::webhooks::{delete_webhook, list_webhook_deliveries, list_webhooks, register_webhook},
+    customer::customer::{
+        login_customer, logout_customer, register_customer, update_customer, view_customer,
+    },
+    health_check::{health_check, readiness_check},
+    order::events::order_events,
+    order::order::{cancel_order, create_order, get_order, list_orders},
+    order::payments::{list_order_payments, pay_for_order},
+    order::returns::{list_order_returns, order_timeline, request_return},
+    payments::payment_webhook,
+};
+use actix_web::{web, Route, Scope};
+
+/******************************************/
+// Route table
+/******************************************/
+// Every route the server mounts, grouped by prefix and the middleware they share.
+// `run_server` registers these groups and nothing else, and tests/api/openapi.rs holds the
+// OpenAPI document to the same groups, so a route can't be served without being documented.
+pub struct Endpoint {
+    // Lowercase, as OpenAPI spells it
+    pub method: &'static str,
+    pub path: &'static str,
+    route: fn() -> Route,
+}
+
+macro_rules! endpoint {
+    ($method:ident, $path:literal, $handler:path) => {
+        Endpoint {
+            method: stringify!($method),
+            path: $path,
+            route: || web::$method().to($handler),
+        }
+    };
+}
+
+pub struct RouteGroup {
+    pub prefix: &'static str,
+    pub endpoints: &'static [Endpoint],
+}
+
+impl RouteGroup {
+    // Mounts the routes one by one under their full paths, for groups without middleware of
+    // their own; unlike a scope, this leaves longer prefixes free for the groups that follow
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        for endpoint in self.endpoints {
+            cfg.route(
+                &format!("{}{}", self.prefix, endpoint.path),
+                (endpoint.route)(),
+            );
+        }
+    }
+
+    // The routes as a scope on `prefix`, for the caller to wrap in the group's middleware
+    pub fn scope(&self) -> Scope {
+        let mut scope = web::scope(self.prefix);
+        for endpoint in self.endpoints {
+            scope = scope.route(endpoint.path, (endpoint.route)());
+        }
+        scope
+    }
+
+    // `(method, full path)` of every route in the group
+    pub fn routes(&self) -> impl Iterator<Item = (&'static str, String)> + '_ {
+        self.endpoints
+            .iter()
+            .map(|endpoint| (endpoint.method, format!("{}{}", self.prefix, endpoint.path)))
+    }
+}
+
+pub const PROBES: RouteGroup = RouteGroup {
+    prefix: "/health_check",
+    endpoints: &[
+        endpoint!(get, "", health_check),
+        endpoint!(get, "/ready", readiness_check),
+    ],
+};
+
+// Only mounted while `metrics.enabled` is set
+pub const METRICS: RouteGroup = RouteGroup {
+    prefix: "",
+    endpoints: &[endpoint!(get, "/metrics", metrics_endpoint)],
+};
+
+// `/api/v1` routes open to anyone; mounted ahead of the scopes below, so `/admin/sessions`
+// isn't taken by the `/admin` scope
+pub const API_PUBLIC: RouteGroup = RouteGroup {
+    prefix: "/api/v1",
+    endpoints: &[
+        endpoint!(post, "/customers", register_customer),
+        endpoint!(post, "/sessions", login_customer),
+        endpoint!(post, "/admins", register_admin),
+        endpoint!(post, "/admin/sessions", login_admin),
+        // Signed by the provider instead of carrying a JWT
+        endpoint!(post, "/payments/webhook", payment_webhook),
+    ],
+};
+
+pub const API_ME: RouteGroup = RouteGroup {
+    prefix: "/api/v1/me",
+    endpoints: &[
+        endpoint!(get, "", view_customer),
+        endpoint!(patch, "", update_customer),
+        endpoint!(delete, "/session", logout_customer),
+    ],
+};
+
+pub const API_ORDERS: RouteGroup = RouteGroup {
+    prefix: "/api/v1/orders",
+    endpoints: &[
+        endpoint!(post, "", create_order),
+        endpoint!(get, "", list_orders),
+        // Before `/{id}`, which would otherwise match it
+        endpoint!(get, "/events", order_events),
+        endpoint!(get, "/{id}", get_order),
+        endpoint!(post, "/{id}/cancel", cancel_order),
+        endpoint!(post, "/{id}/payments", pay_for_order),
+        endpoint!(get, "/{id}/payments", list_order_payments),
+        endpoint!(post, "/{id}/returns", request_return),
+        endpoint!(get, "/{id}/returns", list_order_returns),
+        endpoint!(get, "/{id}/timeline", order_timeline),
+    ],
+};
+
+pub const API_ADMIN: RouteGroup = RouteGroup {
+    prefix: "/api/v1/admin",
+    endpoints: &[
+        endpoint!(get, "/orders", fetch_all_orders),
+        endpoint!(get, "/orders/live", live_orders),
+        endpoint!(get, "/orders/{id}", fetch_order),
+        endpoint!(patch, "/orders/{id}", update_order_status),
+        endpoint!(post, "/orders/{id}/cancel", admin_cancel_order),
+        endpoint!(get, "/orders/{id}/timeline", admin_order_timeline),
+        endpoint!(get, "/orders/{id}/payments", admin_order_payments),
+        endpoint!(post, "/payments/{id}/capture", capture_payment),
+        endpoint!(post, "/payments/{id}/void", void_payment),
+        endpoint!(post, "/payments/{id}/refund", refund_payment),
+        endpoint!(get, "/returns", list_returns),
+        endpoint!(get, "/returns/{id}", get_return),
+        endpoint!(patch, "/returns/{id}", update_return),
+        endpoint!(get, "/jobs", list_jobs),
+        endpoint!(get, "/jobs/{id}", get_job),
+        endpoint!(post, "/jobs/{id}/requeue", requeue_job),
+        endpoint!(post, "/webhooks", register_webhook),
+        endpoint!(get, "/webhooks", list_webhooks),
+        endpoint!(delete, "/webhooks/{id}", delete_webhook),
+        endpoint!(get, "/webhooks/{id}/deliveries", list_webhook_deliveries),
+        endpoint!(delete, "/session", logout_admin),
+    ],
+};
+
+// Deprecated aliases, see `routes::legacy`
+pub const LEGACY_PUBLIC: RouteGroup = RouteGroup {
+    prefix: "",
+    endpoints: &[
+        endpoint!(post, "/register", register_customer),
+        endpoint!(post, "/login", login_customer),
+        endpoint!(post, "/admin/register", register_admin),
+        endpoint!(post, "/admin/login", login_admin),
+    ],
+};
+
+pub const LEGACY_PROTECTED: RouteGroup = RouteGroup {
+    prefix: "/protected",
+    endpoints: &[
+        endpoint!(post, "/logout", logout_customer),
+        endpoint!(post, "/update", update_customer),
+        endpoint!(get, "/view", view_customer),
+        endpoint!(post, "/orders/new", create_order),
+        endpoint!(get, "/orders/{id}/view", get_order),
+        endpoint!(get, "/orders/list/all", list_orders),
+        endpoint!(post, "/admin/update_status", update_status),
+        endpoint!(post, "/admin/logout", logout_admin),
+        endpoint!(get, "/admin/fetch_all_orders", fetch_all_orders),
+    ],
+};
+
+pub const ROUTE_GROUPS: &[&RouteGroup] = &[
+    &PROBES,
+    &METRICS,
+    &API_PUBLIC,
+    &API_ME,
+    &API_ORDERS,
+    &API_ADMIN,
+    &LEGACY_PUBLIC,
+    &LEGACY_PROTECTED,
+];
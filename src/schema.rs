@@ -4,6 +4,22 @@ pub mod sql_types {
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "order_status"))]
     pub struct OrderStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "job_status"))]
+    pub struct JobStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "payment_status"))]
+    pub struct PaymentStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "return_status"))]
+    pub struct ReturnStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "webhook_delivery_status"))]
+    pub struct WebhookDeliveryStatus;
 }
 
 diesel::table! {
@@ -24,6 +40,63 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    idempotency_keys (owner, idempotency_key) {
+        owner -> Varchar,
+        idempotency_key -> Varchar,
+        fingerprint -> Varchar,
+        status_code -> Nullable<Int4>,
+        content_type -> Nullable<Varchar>,
+        body -> Nullable<Bytea>,
+        locked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::JobStatus;
+
+    jobs (id) {
+        id -> Uuid,
+        job_type -> Varchar,
+        payload -> Jsonb,
+        status -> JobStatus,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamp,
+        locked_at -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        unique_key -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    order_cancellations (order_id) {
+        order_id -> Uuid,
+        reason -> Text,
+        admin_id -> Nullable<Uuid>,
+        cancelled_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    order_totals (order_id) {
+        order_id -> Uuid,
+        unit_price -> Int4,
+        subtotal -> Int4,
+        discount -> Int4,
+        shipping -> Int4,
+        tax -> Int4,
+        grand_total -> Int4,
+        tax_region -> Nullable<Varchar>,
+        tax_rate_bps -> Int4,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::OrderStatus;
@@ -37,16 +110,154 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    outbox_events (id) {
+        id -> Uuid,
+        event_type -> Varchar,
+        aggregate_id -> Uuid,
+        payload -> Jsonb,
+        created_at -> Timestamp,
+        dispatched_at -> Nullable<Timestamp>,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        seq -> Int8,
+    }
+}
+
+diesel::table! {
+    payment_events (provider, event_id) {
+        provider -> Varchar,
+        event_id -> Varchar,
+        event_type -> Varchar,
+        provider_reference -> Varchar,
+        payload -> Jsonb,
+        outcome -> Varchar,
+        received_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::PaymentStatus;
+
+    payments (id) {
+        id -> Uuid,
+        order_id -> Uuid,
+        provider -> Varchar,
+        provider_reference -> Nullable<Varchar>,
+        status -> PaymentStatus,
+        amount -> Int4,
+        refunded_amount -> Int4,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     products (id) {
         id -> Uuid,
         name -> Varchar,
         is_available -> Bool,
         price -> Int4,
+        sku -> Nullable<Varchar>,
+        stock -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    refunds (id) {
+        id -> Uuid,
+        return_id -> Uuid,
+        order_id -> Uuid,
+        amount -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    return_items (return_id, product_id) {
+        return_id -> Uuid,
+        product_id -> Uuid,
+        reason -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ReturnStatus;
+
+    returns (id) {
+        id -> Uuid,
+        order_id -> Uuid,
+        customer_id -> Uuid,
+        status -> ReturnStatus,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::WebhookDeliveryStatus;
+
+    webhook_deliveries (id) {
+        id -> Uuid,
+        endpoint_id -> Uuid,
+        event_id -> Uuid,
+        event_type -> Varchar,
+        payload -> Jsonb,
+        status -> WebhookDeliveryStatus,
+        attempts -> Int4,
+        response_status -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    webhook_endpoints (id) {
+        id -> Uuid,
+        url -> Varchar,
+        secret -> Varchar,
+        event_types -> Array<Text>,
+        is_active -> Bool,
+        created_at -> Timestamp,
     }
 }
 
+diesel::joinable!(order_cancellations -> admins (admin_id));
+diesel::joinable!(order_cancellations -> orders (order_id));
+diesel::joinable!(order_totals -> orders (order_id));
 diesel::joinable!(orders -> customers (customer_id));
 diesel::joinable!(orders -> products (product_id));
+diesel::joinable!(payments -> orders (order_id));
+diesel::joinable!(refunds -> orders (order_id));
+diesel::joinable!(refunds -> returns (return_id));
+diesel::joinable!(return_items -> products (product_id));
+diesel::joinable!(return_items -> returns (return_id));
+diesel::joinable!(returns -> customers (customer_id));
+diesel::joinable!(returns -> orders (order_id));
+diesel::joinable!(webhook_deliveries -> webhook_endpoints (endpoint_id));
 
-diesel::allow_tables_to_appear_in_same_query!(admins, customers, orders, products,);
+diesel::allow_tables_to_appear_in_same_query!(
+    admins,
+    customers,
+    idempotency_keys,
+    jobs,
+    order_cancellations,
+    order_totals,
+    orders,
+    outbox_events,
+    payment_events,
+    payments,
+    products,
+    refunds,
+    return_items,
+    returns,
+    webhook_deliveries,
+    webhook_endpoints,
+);
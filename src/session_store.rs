@@ -0,0 +1,232 @@
+use crate::config::configuration::{SessionBackend, Settings};
+use crate::startup::init_redis;
+use actix_session::storage::{
+    generate_session_key, CookieSessionStore, LoadError, RedisSessionStore, SaveError, SessionKey,
+    SessionStore, UpdateError,
+};
+use actix_web::cookie::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+type SessionState = HashMap<String, String>;
+
+// How often writes also drop every expired session
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/******************************************/
+// In-process session store
+/******************************************/
+// Keeps sessions in a map owned by the server process. Nothing survives a restart
+// and instances don't share state, so it is meant for development and tests only.
+// Expired sessions are dropped when loaded, and swept on writes at most once per
+// `SWEEP_INTERVAL`, so the ones nobody comes back for don't pile up.
+#[derive(Clone)]
+pub struct MemorySessionStore {
+    sessions: Arc<RwLock<HashMap<String, (SessionState, Instant)>>>,
+    last_sweep: Arc<Mutex<Instant>>,
+}
+
+impl Default for MemorySessionStore {
+    fn default() -> Self {
+        Self {
+            sessions: Arc::default(),
+            last_sweep: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl MemorySessionStore {
+    fn expires_at(ttl: &Duration) -> Instant {
+        Instant::now() + std::time::Duration::from_secs(ttl.whole_seconds().max(0) as u64)
+    }
+
+    // Drops every expired session; returns how many went
+    pub fn purge_expired(&self) -> usize {
+        let mut sessions = self.sessions.write().expect("Session store lock poisoned");
+        let before = sessions.len();
+        let now = Instant::now();
+        sessions.retain(|_, (_, expires_at)| *expires_at > now);
+        before - sessions.len()
+    }
+
+    fn sweep_if_due(&self) {
+        {
+            let mut last_sweep = self.last_sweep.lock().expect("Session store lock poisoned");
+            if last_sweep.elapsed() < SWEEP_INTERVAL {
+                return;
+            }
+            *last_sweep = Instant::now();
+        }
+        self.purge_expired();
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let mut sessions = self.sessions.write().expect("Session store lock poisoned");
+        match sessions.get(session_key.as_ref()) {
+            Some((state, expires_at)) if *expires_at > Instant::now() => Ok(Some(state.clone())),
+            Some(_) => {
+                sessions.remove(session_key.as_ref());
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        self.sweep_if_due();
+        let session_key = generate_session_key();
+        self.sessions
+            .write()
+            .expect("Session store lock poisoned")
+            .insert(
+                session_key.as_ref().to_string(),
+                (session_state, Self::expires_at(ttl)),
+            );
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        self.sweep_if_due();
+        self.sessions
+            .write()
+            .expect("Session store lock poisoned")
+            .insert(
+                session_key.as_ref().to_string(),
+                (session_state, Self::expires_at(ttl)),
+            );
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        if let Some((_, expires_at)) = self
+            .sessions
+            .write()
+            .expect("Session store lock poisoned")
+            .get_mut(session_key.as_ref())
+        {
+            *expires_at = Self::expires_at(ttl);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        self.sessions
+            .write()
+            .expect("Session store lock poisoned")
+            .remove(session_key.as_ref());
+        Ok(())
+    }
+}
+
+/******************************************/
+// Store selected through `session.backend`
+/******************************************/
+// `SessionMiddleware` is generic over a single store type, so dispatch at runtime. The Redis
+// store is boxed; it is much larger than the others.
+#[derive(Clone)]
+pub enum AppSessionStore {
+    Redis(Box<RedisSessionStore>),
+    Cookie(Arc<CookieSessionStore>),
+    Memory(MemorySessionStore),
+}
+
+impl AppSessionStore {
+    pub async fn from_settings(config: &Settings) -> Result<Self, std::io::Error> {
+        match config.session.backend {
+            SessionBackend::Redis => Ok(Self::Redis(Box::new(
+                init_redis(config.redis.uri.clone()).await?,
+            ))),
+            SessionBackend::Cookie => Ok(Self::Cookie(Arc::new(CookieSessionStore::default()))),
+            SessionBackend::Memory => Ok(Self::Memory(MemorySessionStore::default())),
+        }
+    }
+}
+
+impl SessionStore for AppSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        match self {
+            Self::Redis(store) => store.load(session_key).await,
+            Self::Cookie(store) => store.load(session_key).await,
+            Self::Memory(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            Self::Redis(store) => store.save(session_state, ttl).await,
+            Self::Cookie(store) => store.save(session_state, ttl).await,
+            Self::Memory(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            Self::Redis(store) => store.update(session_key, session_state, ttl).await,
+            Self::Cookie(store) => store.update(session_key, session_state, ttl).await,
+            Self::Memory(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        match self {
+            Self::Redis(store) => store.update_ttl(session_key, ttl).await,
+            Self::Cookie(store) => store.update_ttl(session_key, ttl).await,
+            Self::Memory(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        match self {
+            Self::Redis(store) => store.delete(session_key).await,
+            Self::Cookie(store) => store.delete(session_key).await,
+            Self::Memory(store) => store.delete(session_key).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemorySessionStore;
+    use actix_session::storage::SessionStore;
+    use actix_web::cookie::time::Duration;
+    use std::collections::HashMap;
+
+    #[actix_web::test]
+    async fn expired_sessions_are_purged() {
+        let store = MemorySessionStore::default();
+        let expired = store
+            .save(HashMap::new(), &Duration::seconds(0))
+            .await
+            .unwrap();
+        let live = store
+            .save(HashMap::new(), &Duration::hours(1))
+            .await
+            .unwrap();
+
+        assert_eq!(store.purge_expired(), 1);
+        assert_eq!(store.purge_expired(), 0);
+        assert!(store.load(&expired).await.unwrap().is_none());
+        assert!(store.load(&live).await.unwrap().is_some());
+    }
+}
@@ -1,20 +1,30 @@
+use crate::config::configuration::{SessionBackend, Settings};
 use crate::db::PgPool;
-use crate::middleware::jwt_auth_middleware;
-use crate::routes::{
-    admin::admin::{fetch_all_orders, login_admin, logout_admin, register_admin, update_status},
-    customer::customer::{
-        login_customer, logout_customer, register_customer, update_customer, view_customer,
-    },
-    health_check::health_check,
-    order::order::{create_order, get_order, list_orders},
-};
+use crate::idempotency::{idempotency_middleware, spawn_sweeper};
+use crate::jobs::{worker::spawn_workers, JobRegistry};
+use crate::live::{spawn_listener, LiveEvents};
+use crate::mailer::notifications::{Notification, NotificationHandler, NotificationSink};
+use crate::metrics::metrics_middleware;
+use crate::middleware::{deprecation_middleware, jwt_auth_middleware, request_id_middleware};
+use crate::openapi::ApiDoc;
+use crate::outbox::dispatcher::{spawn_dispatcher, EventSink, LogSink};
+use crate::pricing::Pricing;
+use crate::repository::Repositories;
+use crate::routes::table;
+use crate::session_store::AppSessionStore;
+use crate::telemetry::RequestIdRootSpanBuilder;
+use crate::webhooks::{DeliverWebhook, DeliverWebhookHandler, WebhookSink};
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
+use actix_web::middleware::{from_fn, Condition};
 use actix_web::{dev::Server, web, App, HttpServer};
-use actix_web_lab::middleware::from_fn;
 use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing_actix_web::TracingLogger;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 /******************************************/
 // Initializing Redis connection
@@ -23,7 +33,17 @@ pub async fn init_redis(redis_uri: String) -> Result<RedisSessionStore, std::io:
     // let redis_uri = env::var("REDIS_URI").expect("Failed to get redis uri");
     RedisSessionStore::new(redis_uri).await.map_err(|e| {
         eprintln!("Failed to create Redis session store: {:?}", e);
-        std::io::Error::new(std::io::ErrorKind::Other, "Redis connection failed")
+        std::io::Error::other("Redis connection failed")
+    })
+}
+
+/******************************************/
+// Redis client used by the readiness probe
+/******************************************/
+pub fn init_redis_client(redis_uri: &str) -> Result<redis::Client, std::io::Error> {
+    redis::Client::open(redis_uri).map_err(|e| {
+        eprintln!("Failed to create Redis client: {:?}", e);
+        std::io::Error::other("Invalid Redis uri")
     })
 }
 
@@ -39,7 +59,7 @@ pub struct Application {
 }
 
 impl Application {
-    pub async fn build(port: u16, pool: PgPool, redis_uri: String) -> Result<Self, std::io::Error> {
+    pub async fn build(port: u16, pool: PgPool, config: Settings) -> Result<Self, std::io::Error> {
         let listener = if port == 0 {
             TcpListener::bind("127.0.0.1:0")?
         } else {
@@ -49,7 +69,27 @@ impl Application {
 
         let actual_port = listener.local_addr()?.port();
 
-        let server = run_server(listener, pool.clone(), redis_uri).await?;
+        let mailer = crate::mailer::from_settings(&config.email)?;
+        let registry = JobRegistry::default()
+            .register::<DeliverWebhook, _>(DeliverWebhookHandler::new(pool.clone()))
+            .register::<Notification, _>(NotificationHandler::new(
+                Repositories::postgres(pool.clone()),
+                mailer,
+            ));
+        spawn_workers(pool.clone(), registry, &config.jobs);
+        let sinks: Vec<Arc<dyn EventSink>> = vec![
+            Arc::new(LogSink),
+            Arc::new(WebhookSink::new(pool.clone())),
+            Arc::new(NotificationSink::new(pool.clone())),
+        ];
+        spawn_dispatcher(pool.clone(), sinks, &config.outbox);
+        spawn_sweeper(pool.clone(), &config.idempotency);
+        let live = LiveEvents::new(Duration::from_secs(config.live.heartbeat_secs));
+        if config.live.enabled {
+            spawn_listener(config.database.url.clone(), live.clone());
+        }
+
+        let server = run_server(listener, pool.clone(), config, live).await?;
         Ok(Self {
             port: actual_port,
             server,
@@ -69,35 +109,76 @@ impl Application {
 pub async fn run_server(
     listener: TcpListener,
     pool: PgPool,
-    redis_uri: String,
+    config: Settings,
+    live: LiveEvents,
 ) -> Result<Server, std::io::Error> {
-    let redis_store = init_redis(redis_uri).await?;
+    // Redis is only a dependency (and a readiness check) when it backs the sessions
+    let redis_client = match config.session.backend {
+        SessionBackend::Redis => Some(init_redis_client(&config.redis.uri)?),
+        SessionBackend::Cookie | SessionBackend::Memory => None,
+    };
+    let session_store = AppSessionStore::from_settings(&config).await?;
+    let repositories = Repositories::postgres(pool.clone());
+    let payment_provider = crate::payments::from_settings(&config.payments);
+    let payment_settings = config.payments.clone();
+    let idempotency_settings = config.idempotency.clone();
+    let pricing = Pricing::from_settings(&config.pricing);
+    let metrics_enabled = config.metrics.enabled;
     let secret_key = generate_secret_key();
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(TracingLogger::default())
+            .wrap(from_fn(deprecation_middleware))
+            .wrap(Condition::new(metrics_enabled, from_fn(metrics_middleware)))
+            .wrap(TracingLogger::<RequestIdRootSpanBuilder>::new())
+            .wrap(from_fn(request_id_middleware))
             .wrap(SessionMiddleware::new(
-                redis_store.clone(),
+                session_store.clone(),
                 secret_key.clone(),
             ))
             .app_data(web::Data::new(pool.clone()))
-            .route("/register", web::post().to(register_customer))
-            .route("/login", web::post().to(login_customer))
-            .route("/admin/register", web::post().to(register_admin))
-            .route("/admin/login", web::post().to(login_admin))
-            .route("/health_check", web::get().to(health_check))
+            .app_data(web::Data::new(live.clone()))
+            .app_data(web::Data::from(payment_provider.clone()))
+            .app_data(web::Data::new(payment_settings.clone()))
+            .app_data(web::Data::new(idempotency_settings.clone()))
+            .app_data(web::Data::new(pricing.clone()))
+            .configure(|cfg| repositories.configure(cfg))
+            .configure(|cfg| {
+                if let Some(redis_client) = &redis_client {
+                    cfg.app_data(web::Data::new(redis_client.clone()));
+                }
+            })
+            .configure(|cfg| table::PROBES.configure(cfg))
+            .service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/openapi.json", ApiDoc::openapi()))
+            .configure(|cfg| {
+                if metrics_enabled {
+                    table::METRICS.configure(cfg);
+                }
+            })
+            .configure(|cfg| table::API_PUBLIC.configure(cfg))
+            .service(
+                table::API_ME
+                    .scope()
+                    .wrap(from_fn(idempotency_middleware))
+                    .wrap(from_fn(jwt_auth_middleware)),
+            )
+            .service(
+                table::API_ORDERS
+                    .scope()
+                    .wrap(from_fn(idempotency_middleware))
+                    .wrap(from_fn(jwt_auth_middleware)),
+            )
+            .service(
+                table::API_ADMIN
+                    .scope()
+                    .wrap(from_fn(idempotency_middleware))
+                    .wrap(from_fn(jwt_auth_middleware)),
+            )
+            .configure(|cfg| table::LEGACY_PUBLIC.configure(cfg))
             .service(
-                web::scope("/protected")
-                    .wrap(from_fn(jwt_auth_middleware))
-                    .route("/logout", web::post().to(logout_customer))
-                    .route("/update", web::post().to(update_customer))
-                    .route("/view", web::get().to(view_customer))
-                    .route("/orders/new", web::post().to(create_order))
-                    .route("/orders/{id}/view", web::get().to(get_order))
-                    .route("/orders/list/all", web::get().to(list_orders))
-                    .route("/admin/update_status", web::post().to(update_status))
-                    .route("/admin/logout", web::post().to(logout_admin))
-                    .route("/admin/fetch_all_orders", web::get().to(fetch_all_orders)),
+                table::LEGACY_PROTECTED
+                    .scope()
+                    .wrap(from_fn(idempotency_middleware))
+                    .wrap(from_fn(jwt_auth_middleware)),
             )
     })
     .listen(listener)?
@@ -1,5 +1,17 @@
+use crate::config::configuration::TelemetrySettings;
+use crate::middleware::RequestId;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{Error, HttpMessage};
+use opentelemetry::trace::TraceError;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Config, Sampler, Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
 use tracing::subscriber::set_global_default;
-use tracing::Subscriber;
+use tracing::{Span, Subscriber};
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer; // to see actix_Web logger logs as well
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
@@ -12,16 +24,31 @@ pub fn get_subscriber<Sink>(
     env_filter: String,
     sink: Sink,
 ) -> impl Subscriber + Sync + Send
+where
+    Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    get_subscriber_with_tracer(name, env_filter, sink, None)
+}
+
+// Same as `get_subscriber`, plus an OpenTelemetry layer when a tracer is given
+pub fn get_subscriber_with_tracer<Sink>(
+    name: String,
+    env_filter: String,
+    sink: Sink,
+    tracer: Option<Tracer>,
+) -> impl Subscriber + Sync + Send
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    let otel_layer = tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
     Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
         .with(formatting_layer)
+        .with(otel_layer)
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Sync + Send) {
@@ -29,6 +56,63 @@ pub fn init_subscriber(subscriber: impl Subscriber + Sync + Send) {
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
 
+/******************************************/
+// OTLP trace exporter
+/******************************************/
+// Always installs the W3C propagator so incoming `traceparent` headers are honoured by
+// `TracingLogger`; the exporter itself is only built when `telemetry.otlp_enabled` is set.
+pub fn init_tracer_provider(
+    service_name: &str,
+    settings: &TelemetrySettings,
+) -> Result<Option<TracerProvider>, TraceError> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    if !settings.otlp_enabled {
+        return Ok(None);
+    }
+
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(settings.otlp_endpoint.clone()),
+        )
+        .with_trace_config(
+            Config::default()
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    settings.sample_ratio,
+                ))))
+                .with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    service_name.to_string(),
+                )])),
+        )
+        .install_batch(runtime::Tokio)?;
+    global::set_tracer_provider(provider.clone());
+    Ok(Some(provider))
+}
+
+/******************************************/
+// Root span carrying our request id
+/******************************************/
+// `TracingLogger` always mints its own `request_id`; overwrite it with the id set by
+// `request_id_middleware` so logs, traces and responses share a single value.
+pub struct RequestIdRootSpanBuilder;
+
+impl RootSpanBuilder for RequestIdRootSpanBuilder {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let span = tracing_actix_web::root_span!(request);
+        if let Some(request_id) = request.extensions().get::<RequestId>() {
+            span.record("request_id", tracing::field::display(request_id));
+        }
+        span
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
 // Copied trait bounds and signature from `spawn_blocking`
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
 where
@@ -38,3 +122,80 @@ where
     let current_span = tracing::Span::current();
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::get_subscriber_with_tracer;
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use std::collections::HashMap;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    fn in_memory_provider() -> (InMemorySpanExporter, TracerProvider) {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        (exporter, provider)
+    }
+
+    #[test]
+    fn instrumented_spans_are_exported() {
+        let (exporter, provider) = in_memory_provider();
+        let subscriber = get_subscriber_with_tracer(
+            "test".into(),
+            "info".into(),
+            std::io::sink,
+            Some(provider.tracer("test")),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("Create new Order");
+            let _guard = span.enter();
+            tracing::info_span!("Verify password").in_scope(|| {});
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let parent = spans.iter().find(|s| s.name == "Create new Order").unwrap();
+        let child = spans.iter().find(|s| s.name == "Verify password").unwrap();
+        assert_eq!(child.parent_span_id, parent.span_context.span_id());
+        assert_eq!(
+            child.span_context.trace_id(),
+            parent.span_context.trace_id()
+        );
+    }
+
+    #[test]
+    fn traceparent_header_becomes_the_span_parent() {
+        let (exporter, provider) = in_memory_provider();
+        let subscriber = get_subscriber_with_tracer(
+            "test".into(),
+            "info".into(),
+            std::io::sink,
+            Some(provider.tracer("test")),
+        );
+        let mut headers = HashMap::new();
+        headers.insert(
+            "traceparent".to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+        );
+        let remote_context = TraceContextPropagator::new().extract(&headers);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("HTTP request");
+            span.set_parent(remote_context);
+            let _guard = span.enter();
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = spans.iter().find(|s| s.name == "HTTP request").unwrap();
+        assert_eq!(
+            span.span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(span.parent_span_id.to_string(), "00f067aa0ba902b7");
+    }
+}
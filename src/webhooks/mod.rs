@@ -0,0 +1,299 @@
+pub mod signature;
+
+use crate::db::PgPool;
+use crate::db_models::{WebhookDelivery, WebhookEndpoint};
+use crate::errors::custom::{CustomError, DbError};
+use crate::jobs::{self, Job, JobHandler};
+use crate::outbox::dispatcher::EventSink;
+use crate::outbox::EventEnvelope;
+use crate::schema::webhook_deliveries::dsl as delivery_dsl;
+use crate::schema::webhook_endpoints::dsl as endpoint_dsl;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const ID_HEADER: &str = "X-Webhook-Id";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, diesel_derive_enum::DbEnum, Serialize, Deserialize, ToSchema,
+)]
+#[ExistingTypePath = "crate::schema::sql_types::WebhookDeliveryStatus"]
+pub enum DeliveryStatus {
+    Pending,
+    Succeeded,
+    // The last attempt failed; the delivery job retries it until it runs out of attempts
+    Failed,
+}
+
+/******************************************/
+// Registering endpoints
+/******************************************/
+pub async fn create_endpoint(
+    pool: &PgPool,
+    url: &str,
+    secret: &str,
+    event_types: Vec<String>,
+) -> Result<WebhookEndpoint, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    diesel::insert_into(endpoint_dsl::webhook_endpoints)
+        .values((
+            endpoint_dsl::id.eq(Uuid::new_v4()),
+            endpoint_dsl::url.eq(url),
+            endpoint_dsl::secret.eq(secret),
+            endpoint_dsl::event_types.eq(event_types),
+            endpoint_dsl::is_active.eq(true),
+            endpoint_dsl::created_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result::<WebhookEndpoint>(&mut conn)
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::InsertionError(err.to_string())))
+}
+
+pub async fn list_endpoints(pool: &PgPool) -> Result<Vec<WebhookEndpoint>, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    endpoint_dsl::webhook_endpoints
+        .order(endpoint_dsl::created_at.asc())
+        .load::<WebhookEndpoint>(&mut conn)
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))
+}
+
+// Also removes the endpoint's delivery log; returns false when there was no such endpoint
+pub async fn delete_endpoint(pool: &PgPool, endpoint_id: Uuid) -> Result<bool, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    let deleted = diesel::delete(endpoint_dsl::webhook_endpoints.find(endpoint_id))
+        .execute(&mut conn)
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))?;
+    Ok(deleted > 0)
+}
+
+// Newest first
+pub async fn list_deliveries(
+    pool: &PgPool,
+    endpoint_id: Uuid,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::ConnectionError(err.to_string())))?;
+    delivery_dsl::webhook_deliveries
+        .filter(delivery_dsl::endpoint_id.eq(endpoint_id))
+        .order(delivery_dsl::created_at.desc())
+        .limit(limit)
+        .load::<WebhookDelivery>(&mut conn)
+        .await
+        .map_err(|err| CustomError::DatabaseError(DbError::QueryBuilderError(err.to_string())))
+}
+
+/******************************************/
+// Fanning events out to endpoints
+/******************************************/
+// Outbox sink that logs one delivery per subscribed endpoint and queues a job to send it.
+// Redelivered outbox events hit the (endpoint_id, event_id) constraint and are skipped.
+pub struct WebhookSink {
+    pool: PgPool,
+}
+
+impl WebhookSink {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn deliver(&self, event: &EventEnvelope) -> Result<(), String> {
+        let event_type = event.event.event_type();
+        let payload = serde_json::to_value(event).map_err(|err| err.to_string())?;
+        let mut conn = self.pool.get().await.map_err(|err| err.to_string())?;
+
+        conn.transaction::<_, CustomError, _>(|conn| {
+            async move {
+                let endpoints = endpoint_dsl::webhook_endpoints
+                    .filter(endpoint_dsl::is_active.eq(true))
+                    .load::<WebhookEndpoint>(conn)
+                    .await?;
+                let now = chrono::Utc::now().naive_utc();
+                for endpoint in endpoints.iter().filter(|endpoint| {
+                    endpoint.event_types.is_empty()
+                        || endpoint.event_types.iter().any(|t| t == event_type)
+                }) {
+                    let delivery_id = Uuid::new_v4();
+                    let inserted = diesel::insert_into(delivery_dsl::webhook_deliveries)
+                        .values((
+                            delivery_dsl::id.eq(delivery_id),
+                            delivery_dsl::endpoint_id.eq(endpoint.id),
+                            delivery_dsl::event_id.eq(event.id),
+                            delivery_dsl::event_type.eq(event_type),
+                            delivery_dsl::payload.eq(&payload),
+                            delivery_dsl::status.eq(DeliveryStatus::Pending),
+                            delivery_dsl::created_at.eq(now),
+                            delivery_dsl::updated_at.eq(now),
+                        ))
+                        .on_conflict((delivery_dsl::endpoint_id, delivery_dsl::event_id))
+                        .do_nothing()
+                        .execute(conn)
+                        .await?;
+                    if inserted > 0 {
+                        jobs::enqueue_with(conn, &DeliverWebhook { delivery_id }, now).await?;
+                    }
+                }
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|err| err.to_string())
+    }
+}
+
+/******************************************/
+// Sending one delivery
+/******************************************/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliverWebhook {
+    pub delivery_id: Uuid,
+}
+
+impl Job for DeliverWebhook {
+    const JOB_TYPE: &'static str = "webhooks.deliver";
+    // 5s doubling up to the hourly cap: about two and a half hours before it is dead-lettered
+    const MAX_ATTEMPTS: i32 = 12;
+}
+
+pub struct DeliverWebhookHandler {
+    pool: PgPool,
+    client: reqwest::Client,
+}
+
+impl DeliverWebhookHandler {
+    pub fn new(pool: PgPool) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .expect("Failed to build webhook HTTP client");
+        Self { pool, client }
+    }
+
+    async fn load(
+        &self,
+        delivery_id: Uuid,
+    ) -> Result<Option<(WebhookDelivery, WebhookEndpoint)>, CustomError> {
+        let mut conn =
+            self.pool.get().await.map_err(|err| {
+                CustomError::DatabaseError(DbError::ConnectionError(err.to_string()))
+            })?;
+        Ok(delivery_dsl::webhook_deliveries
+            .inner_join(endpoint_dsl::webhook_endpoints)
+            .filter(delivery_dsl::id.eq(delivery_id))
+            .first::<(WebhookDelivery, WebhookEndpoint)>(&mut conn)
+            .await
+            .optional()?)
+    }
+
+    async fn record_attempt(
+        &self,
+        delivery: &WebhookDelivery,
+        status: DeliveryStatus,
+        response_status: Option<i32>,
+        error: Option<&str>,
+    ) -> Result<(), CustomError> {
+        let mut conn =
+            self.pool.get().await.map_err(|err| {
+                CustomError::DatabaseError(DbError::ConnectionError(err.to_string()))
+            })?;
+        diesel::update(delivery_dsl::webhook_deliveries.find(delivery.id))
+            .set((
+                delivery_dsl::status.eq(status),
+                delivery_dsl::attempts.eq(delivery_dsl::attempts + 1),
+                delivery_dsl::response_status.eq(response_status),
+                delivery_dsl::last_error.eq(error),
+                delivery_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(|err| CustomError::DatabaseError(DbError::UpdationError(err.to_string())))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler<DeliverWebhook> for DeliverWebhookHandler {
+    async fn handle(&self, job: DeliverWebhook) -> Result<(), String> {
+        // Deleted endpoints take their deliveries with them; nothing left to send
+        let Some((delivery, endpoint)) = self
+            .load(job.delivery_id)
+            .await
+            .map_err(|err| err.to_string())?
+        else {
+            return Ok(());
+        };
+        if !endpoint.is_active {
+            return Ok(());
+        }
+
+        let body = serde_json::to_vec(&delivery.payload).map_err(|err| err.to_string())?;
+        let timestamp = chrono::Utc::now().timestamp();
+        let response = self
+            .client
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(ID_HEADER, delivery.event_id.to_string())
+            .header(EVENT_HEADER, &delivery.event_type)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                SIGNATURE_HEADER,
+                signature::sign(&endpoint.secret, timestamp, &body),
+            )
+            .body(body)
+            .send()
+            .await;
+
+        let (status, response_status, error) = match response {
+            Ok(response) if response.status().is_success() => (
+                DeliveryStatus::Succeeded,
+                Some(response.status().as_u16() as i32),
+                None,
+            ),
+            Ok(response) => (
+                DeliveryStatus::Failed,
+                Some(response.status().as_u16() as i32),
+                Some(format!("Receiver responded with {}", response.status())),
+            ),
+            Err(err) => (DeliveryStatus::Failed, None, Some(err.to_string())),
+        };
+        self.record_attempt(&delivery, status, response_status, error.as_deref())
+            .await
+            .map_err(|err| err.to_string())?;
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
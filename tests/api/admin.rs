@@ -1,5 +1,6 @@
-use crate::helper::{seed_products, spawn_app};
+use crate::helper::{new_order, pay_for_order, seed_products, spawn_app};
 use ecommerce::db::drop_database;
+use ecommerce::repository::Repositories;
 use ecommerce::routes::order::order::OrderStatus;
 use serde_json::{self, Value};
 use std::time::Duration;
@@ -12,7 +13,7 @@ async fn admin_login_success() {
     // Step: 1: Admin login and getting jwt token
     let response = app
         .api_client
-        .post(&format!("{}/admin/login", &app.address))
+        .post(format!("{}/admin/login", &app.address))
         .json(&serde_json::json!({
             "username": app.test_user.username,
             "password": app.test_user.password
@@ -79,7 +80,13 @@ async fn order_creation_get_and_list() {
         .expect("Token not found");
     tokio::time::sleep(Duration::from_secs(12)).await;
 
-    // Step: 5= Updating order status
+    // Step: 5= Updating order status, once the order is paid for
+    pay_for_order(
+        &app.db_pool,
+        app.test_user.user_id,
+        Uuid::parse_str(order_id).unwrap(),
+    )
+    .await;
     let update_status_body = serde_json::json!({
         "order_id": order_id,
         "status": OrderStatus::Shipped
@@ -131,3 +138,91 @@ async fn admin_logout_check() {
     assert_eq!(update_status_response.status().as_u16(), 401);
     drop_database(&app.database_name, app.test_db_url).await;
 }
+
+#[tokio::test]
+async fn order_status_only_moves_forward() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let order = new_order(&app.db_pool, app.test_user.user_id).await;
+    repositories.orders.insert(order.clone()).await.unwrap();
+    pay_for_order(&app.db_pool, app.test_user.user_id, order.id).await;
+    let cancelled = new_order(&app.db_pool, app.test_user.user_id).await;
+    repositories.orders.insert(cancelled.clone()).await.unwrap();
+    repositories
+        .orders
+        .cancel(cancelled.id, "Changed my mind", None)
+        .await
+        .unwrap();
+
+    let admin_login_response: Value = app
+        .login_admin(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let admin_token = admin_login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let set_status = |order_id: Uuid, status: OrderStatus| {
+        let request = app
+            .api_client
+            .patch(format!("{}/api/v1/admin/orders/{}", &app.address, order_id))
+            .bearer_auth(&admin_token)
+            .json(&serde_json::json!({ "status": status }));
+        async move {
+            request
+                .send()
+                .await
+                .expect("Failed to update order status")
+                .status()
+                .as_u16()
+        }
+    };
+
+    // Step: 1= Forward moves go through, backward ones are refused
+    let shipped = set_status(order.id, OrderStatus::Shipped).await;
+    let shipped_to_pending = set_status(order.id, OrderStatus::Pending).await;
+    let delivered = set_status(order.id, OrderStatus::Delivered).await;
+    let delivered_again = set_status(order.id, OrderStatus::Delivered).await;
+    let delivered_to_shipped = set_status(order.id, OrderStatus::Shipped).await;
+    let delivered_to_pending = set_status(order.id, OrderStatus::Pending).await;
+    let cancelled_to_pending = set_status(cancelled.id, OrderStatus::Pending).await;
+    let final_status = repositories
+        .orders
+        .find(order.id)
+        .await
+        .unwrap()
+        .map(|order| order.status);
+
+    // Step: 2= Unknown orders are not found, on the legacy route too
+    let unknown = set_status(Uuid::new_v4(), OrderStatus::Shipped).await;
+    let unknown_legacy = app
+        .update_order_status(
+            serde_json::json!({
+                "order_id": Uuid::new_v4(),
+                "status": OrderStatus::Shipped
+            }),
+            admin_token.clone(),
+        )
+        .await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(shipped, 200);
+    assert_eq!(shipped_to_pending, 409);
+    assert_eq!(delivered, 200);
+    // Setting the status it already has is a no-op
+    assert_eq!(delivered_again, 200);
+    assert_eq!(delivered_to_shipped, 409);
+    assert_eq!(delivered_to_pending, 409);
+    assert_eq!(cancelled_to_pending, 409);
+    assert_eq!(final_status, Some(OrderStatus::Delivered));
+    assert_eq!(unknown, 404);
+    assert_eq!(unknown_legacy.status().as_u16(), 404);
+}
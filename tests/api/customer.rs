@@ -75,7 +75,7 @@ pub async fn missing_inputs_should_return_400() {
     for (invalid_body, error_message) in test_cases {
         let register_response = app
             .api_client
-            .post(&format!("{}/register", &app.address))
+            .post(format!("{}/register", &app.address))
             .json(invalid_body)
             .send()
             .await
@@ -1,13 +1,17 @@
-use crate::helper::spawn_app;
+use crate::helper::{spawn_app, spawn_app_with};
+use actix_web::{test, web, App};
+use ecommerce::config::configuration::SessionBackend;
 use ecommerce::db::drop_database;
+use ecommerce::routes::health_check::readiness_check;
 use reqwest::Client;
+use serde_json::Value;
 
 #[tokio::test]
 async fn health_check_works() {
     let app = spawn_app().await;
     let client = Client::new();
     let response = client
-        .get(&format!("{}/health_check", &app.address))
+        .get(format!("{}/health_check", &app.address))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -17,3 +21,78 @@ async fn health_check_works() {
     assert_eq!(Some(0), response.content_length());
     drop_database(&app.database_name, app.test_db_url).await;
 }
+
+// Needs the Redis server at `redis.uri`; run with `cargo test -- --ignored`
+#[tokio::test]
+#[ignore = "needs a running Redis server"]
+async fn readiness_check_reports_dependencies() {
+    let app = spawn_app_with(|config| config.session.backend = SessionBackend::Redis).await;
+    let client = Client::new();
+    let response = client
+        .get(format!("{}/health_check/ready", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(body["git_sha"].is_string());
+    assert_eq!(body["checks"]["postgres"]["healthy"], true);
+    assert_eq!(body["checks"]["redis"]["healthy"], true);
+    assert!(body["checks"]["postgres"]["latency_ms"].is_u64());
+    assert!(body["checks"]["redis"]["latency_ms"].is_u64());
+    drop_database(&app.database_name, app.test_db_url).await;
+}
+
+#[tokio::test]
+async fn readiness_check_skips_redis_when_sessions_are_kept_elsewhere() {
+    let app = spawn_app().await;
+    let client = Client::new();
+    let response = client
+        .get(format!("{}/health_check/ready", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["checks"]["postgres"]["healthy"], true);
+    // The harness uses the in-memory session store, so Redis isn't a dependency
+    assert!(body["checks"]["redis"].is_null());
+    drop_database(&app.database_name, app.test_db_url).await;
+}
+
+#[actix_web::test]
+async fn readiness_check_fails_when_redis_is_unreachable() {
+    let app = spawn_app().await;
+    // Nothing listens on port 1; the client only connects when the probe runs
+    let redis_client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    let service = test::init_service(
+        App::new()
+            .app_data(web::Data::new(app.db_pool.clone()))
+            .app_data(web::Data::new(redis_client))
+            .route("/health_check/ready", web::get().to(readiness_check)),
+    )
+    .await;
+
+    let response = test::call_service(
+        &service,
+        test::TestRequest::get()
+            .uri("/health_check/ready")
+            .to_request(),
+    )
+    .await;
+    let status = response.status().as_u16();
+    let body: Value = test::read_body_json(response).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(status, 503);
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["checks"]["postgres"]["healthy"], true);
+    assert_eq!(body["checks"]["redis"]["healthy"], false);
+    assert!(body["checks"]["redis"]["error"].is_string());
+    assert!(body["checks"]["redis"]["latency_ms"].is_u64());
+}
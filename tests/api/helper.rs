@@ -1,18 +1,18 @@
 use diesel::prelude::*;
-use diesel::sql_types::Json;
-use diesel_async_migrations::{embed_migrations, EmbeddedMigrations};
-pub const MIGRATIONS: EmbeddedMigrations = diesel_async_migrations::embed_migrations!("migrations");
 
 use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
 use diesel_async::pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager};
 use diesel_async::RunQueryDsl;
-use diesel_async::{AsyncConnection, AsyncPgConnection};
-use ecommerce::config::configuration;
+use ecommerce::config::configuration::{self, MailerBackend, SessionBackend};
 use ecommerce::db::create_database;
 use ecommerce::db::PgPool;
-use ecommerce::schema::admins::{self, dsl as admin_dsl};
-use ecommerce::schema::customers::{self, dsl as customer_dsl};
-use ecommerce::schema::products::{self, dsl as product_dsl};
+use ecommerce::db_models::{Order, Payment, Product};
+use ecommerce::payments::{self, mock::MockPaymentProvider};
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use ecommerce::schema::admins::dsl as admin_dsl;
+use ecommerce::schema::customers::dsl as customer_dsl;
+use ecommerce::schema::products::dsl as product_dsl;
 use ecommerce::startup::Application;
 use ecommerce::telemetry::{get_subscriber, init_subscriber};
 use once_cell::sync::Lazy;
@@ -20,6 +20,9 @@ use serde_json::Value;
 use tokio;
 use uuid::Uuid;
 
+// Signs payment provider callbacks in tests
+pub const PAYMENT_WEBHOOK_SECRET: &str = "whsec_test_payments";
+
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
@@ -94,7 +97,7 @@ pub struct TestApp {
 impl TestApp {
     pub async fn login_customer(&self, body: Value) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(&body)
             .send()
             .await
@@ -103,7 +106,7 @@ impl TestApp {
 
     pub async fn update_customer(&self, body: Value, token: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/protected/update", &self.address))
+            .post(format!("{}/protected/update", &self.address))
             .bearer_auth(token)
             .json(&body)
             .send()
@@ -113,7 +116,7 @@ impl TestApp {
 
     pub async fn view_customer(&self, token: String) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/protected/view", &self.address))
+            .get(format!("{}/protected/view", &self.address))
             .bearer_auth(token)
             .send()
             .await
@@ -122,7 +125,7 @@ impl TestApp {
 
     pub async fn create_order(&self, body: Value, token: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/protected/orders/new", &self.address))
+            .post(format!("{}/protected/orders/new", &self.address))
             .bearer_auth(token)
             .json(&body)
             .send()
@@ -132,7 +135,7 @@ impl TestApp {
 
     pub async fn get_order(&self, order_id: &str, token: String) -> reqwest::Response {
         self.api_client
-            .get(&format!(
+            .get(format!(
                 "{}/protected/orders/{}/view",
                 &self.address, &order_id
             ))
@@ -144,7 +147,7 @@ impl TestApp {
 
     pub async fn get_all_orders(&self, token: String) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/protected/orders/list/all", &self.address))
+            .get(format!("{}/protected/orders/list/all", &self.address))
             .bearer_auth(token)
             .send()
             .await
@@ -153,7 +156,7 @@ impl TestApp {
 
     pub async fn login_admin(&self, body: Value) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/login", &self.address))
+            .post(format!("{}/admin/login", &self.address))
             .json(&body)
             .send()
             .await
@@ -162,7 +165,7 @@ impl TestApp {
 
     pub async fn update_order_status(&self, body: Value, token: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/protected/admin/update_status", &self.address))
+            .post(format!("{}/protected/admin/update_status", &self.address))
             .bearer_auth(token)
             .json(&body)
             .send()
@@ -172,7 +175,7 @@ impl TestApp {
 
     pub async fn logout_customer(&self, token: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/protected/logout", &self.address))
+            .post(format!("{}/protected/logout", &self.address))
             .bearer_auth(token)
             .send()
             .await
@@ -181,7 +184,7 @@ impl TestApp {
 
     pub async fn logout_admin(&self, token: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/protected/admin/logout", &self.address))
+            .post(format!("{}/protected/admin/logout", &self.address))
             .bearer_auth(token)
             .send()
             .await
@@ -190,28 +193,39 @@ impl TestApp {
 }
 
 pub async fn run_migrations(url: impl AsRef<str>) -> Result<(), std::io::Error> {
-    // Establish a connection
-    let mut conn = AsyncPgConnection::establish(url.as_ref())
-        .await
-        .expect("Failed to run migrations");
-
-    // Run pending migrations
-    MIGRATIONS
-        .run_pending_migrations(&mut conn)
+    // Same embedded migrations and advisory lock the binary uses
+    ecommerce::migrate::run_migrations(url.as_ref())
         .await
         .expect("Failed to run migrations");
 
     Ok(())
 }
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+// `configure` runs on the test settings below, just before the app is built
+pub async fn spawn_app_with(configure: impl FnOnce(&mut configuration::Settings)) -> TestApp {
     // To Ensure that the tracing stack is only initialized once
     Lazy::force(&TRACING);
 
     let database_name = Uuid::new_v4().to_string();
-    let config = configuration::Settings::new().expect("Failed to load configurations");
+    let mut config = configuration::Settings::new().expect("Failed to load configurations");
+    config.metrics.enabled = true;
+    config.session.backend = SessionBackend::Memory;
+    // Tests drive the job queue and the outbox themselves (`run_next`, `dispatch_pending`)
+    config.jobs.workers = 0;
+    config.outbox.enabled = false;
+    config.email.backend = MailerBackend::Memory;
+    config.payments.webhook_secret = PAYMENT_WEBHOOK_SECRET.to_string();
+    // Orders placed without a region stay untaxed, so their totals equal the product price
+    config.pricing.tax_rates.insert("us-ca".to_string(), 725);
+    configure(&mut config);
     create_database(&database_name, config.database.test_url.clone()).await;
 
     let new_database_url = format!("{}/{}", config.database.test_url, database_name);
+    // The live event listener opens its own connection to the test database
+    config.database.url = new_database_url.clone();
 
     //building pool
     let manager = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(
@@ -231,12 +245,12 @@ pub async fn spawn_app() -> TestApp {
         eprintln!("Error running migrations: {}", err);
     }
 
-    let application = Application::build(0, pool.clone(), config.redis.uri)
+    let application = Application::build(0, pool.clone(), config.clone())
         .await
         .expect("Failed to build application");
     let application_port = application.port();
     let address = format!("http://127.0.0.1:{}", application_port);
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
 
     let client = reqwest::Client::builder()
         .cookie_store(true)
@@ -282,3 +296,39 @@ pub async fn seed_products(pool: PgPool) -> Result<(), diesel::result::Error> {
     println!("successfully added products");
     Ok(())
 }
+
+// Authorizes and captures the order's payment with the mock provider, so it can be shipped
+pub async fn pay_for_order(pool: &PgPool, customer_id: Uuid, order_id: Uuid) -> Payment {
+    let provider = MockPaymentProvider;
+    let payment = payments::authorize(pool, &provider, customer_id, order_id, "tok_visa")
+        .await
+        .expect("Failed to authorize payment");
+    payments::capture(pool, &provider, payment.id)
+        .await
+        .expect("Failed to capture payment")
+}
+
+// A pending order, not yet inserted, for a product created just for it, so tests don't lean
+// on the seeded catalogue
+pub async fn new_order(pool: &PgPool, customer_id: Uuid) -> Order {
+    let product = Product {
+        id: Uuid::new_v4(),
+        name: "Test Product".to_string(),
+        is_available: true,
+        price: 50000,
+        sku: None,
+        stock: None,
+    };
+    Repositories::postgres(pool.clone())
+        .products
+        .insert(product.clone())
+        .await
+        .expect("Failed to insert product");
+    Order {
+        id: Uuid::new_v4(),
+        customer_id,
+        status: OrderStatus::Pending,
+        created_at: chrono::Utc::now().naive_utc(),
+        product_id: product.id,
+    }
+}
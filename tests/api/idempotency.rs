@@ -0,0 +1,248 @@
+use crate::helper::{seed_products, spawn_app, TestApp};
+use actix_web::http::Method;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use ecommerce::db::drop_database;
+use ecommerce::db_models::IdempotencyKey;
+use ecommerce::idempotency::{self, IDEMPOTENCY_KEY_HEADER, REPLAYED_HEADER};
+use ecommerce::payments;
+use ecommerce::repository::Repositories;
+use ecommerce::schema::idempotency_keys::dsl as key_dsl;
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+
+const LAPTOP_ID: &str = "5fcd7d83-7adf-4d4d-931a-68b9678009db";
+// Never seeded; the mismatch is caught before the handler looks it up
+const OTHER_PRODUCT_ID: &str = "6a2a46ec-7a58-4cd1-a5a9-4a4b1ab5e2b5";
+
+async fn post_with_key(
+    app: &TestApp,
+    path: &str,
+    token: &str,
+    key: &str,
+    body: Value,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}{}", &app.address, path))
+        .bearer_auth(token)
+        .header(IDEMPOTENCY_KEY_HEADER, key)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+#[tokio::test]
+async fn retried_requests_replay_the_first_response() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let body = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let login_response: Value = app.login_customer(body).await.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    // Step: 1= The same order sent twice under one key is only placed once
+    let order = serde_json::json!({ "product_id": LAPTOP_ID });
+    let first = post_with_key(
+        &app,
+        "/protected/orders/new",
+        &token,
+        "order-1",
+        order.clone(),
+    )
+    .await;
+    let first_status = first.status().as_u16();
+    let first_replayed = first.headers().contains_key(REPLAYED_HEADER);
+    let first: Value = first.json().await.unwrap();
+    let retry = post_with_key(&app, "/protected/orders/new", &token, "order-1", order).await;
+    let retry_status = retry.status().as_u16();
+    let retry_replayed = retry
+        .headers()
+        .get(REPLAYED_HEADER)
+        .map(|value| value.to_str().unwrap().to_string());
+    let retry: Value = retry.json().await.unwrap();
+
+    // Step: 2= Reusing the key for another product is refused
+    let reused = post_with_key(
+        &app,
+        "/protected/orders/new",
+        &token,
+        "order-1",
+        serde_json::json!({ "product_id": OTHER_PRODUCT_ID }),
+    )
+    .await;
+    let orders = Repositories::postgres(app.db_pool.clone())
+        .orders
+        .list(None)
+        .await
+        .unwrap();
+
+    // Step: 3= A retried payment is authorized once instead of hitting "already paid"
+    let order_id = first["order_id"].as_str().unwrap().to_string();
+    let payment_path = format!("/api/v1/orders/{}/payments", order_id);
+    let payment = serde_json::json!({ "payment_method": "tok_visa" });
+    let paid = post_with_key(&app, &payment_path, &token, "pay-1", payment.clone()).await;
+    let paid_status = paid.status().as_u16();
+    let paid: Value = paid.json().await.unwrap();
+    let paid_again = post_with_key(&app, &payment_path, &token, "pay-1", payment).await;
+    let paid_again_status = paid_again.status().as_u16();
+    let paid_again: Value = paid_again.json().await.unwrap();
+    let attempts = payments::list_for_order(&app.db_pool, Uuid::parse_str(&order_id).unwrap())
+        .await
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(first_status, 200);
+    assert!(!first_replayed);
+    assert_eq!(retry_status, 200);
+    assert_eq!(retry_replayed.as_deref(), Some("true"));
+    assert_eq!(retry["order_id"], first["order_id"]);
+    assert_eq!(reused.status().as_u16(), 422);
+    assert_eq!(orders.len(), 1);
+    assert_eq!(paid_status, 201);
+    assert_eq!(paid_again_status, 201);
+    assert_eq!(paid_again["id"], paid["id"]);
+    assert_eq!(attempts.len(), 1);
+}
+
+// Stands in for a request that claimed `key` at `locked_at` and hasn't finished
+async fn insert_in_progress_key(
+    app: &TestApp,
+    owner: &str,
+    key: &str,
+    fingerprint: &str,
+    locked_at: chrono::NaiveDateTime,
+) {
+    let mut conn = app.db_pool.get().await.unwrap();
+    diesel::insert_into(key_dsl::idempotency_keys)
+        .values((
+            key_dsl::owner.eq(owner),
+            key_dsl::idempotency_key.eq(key),
+            key_dsl::fingerprint.eq(fingerprint),
+            key_dsl::locked_at.eq(locked_at),
+            key_dsl::created_at.eq(locked_at),
+            key_dsl::expires_at.eq(locked_at + chrono::Duration::hours(24)),
+        ))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn keys_abandoned_mid_request_are_taken_over() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let body = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let login_response: Value = app.login_customer(body).await.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let owner = app.test_user.user_id.to_string();
+    let order = serde_json::json!({ "product_id": LAPTOP_ID });
+    let order_fingerprint = idempotency::fingerprint(
+        &Method::POST,
+        "/api/v1/orders",
+        &serde_json::to_vec(&order).unwrap(),
+    );
+    let now = chrono::Utc::now().naive_utc();
+    insert_in_progress_key(&app, &owner, "running", &order_fingerprint, now).await;
+    insert_in_progress_key(
+        &app,
+        &owner,
+        "abandoned",
+        &order_fingerprint,
+        now - chrono::Duration::minutes(5),
+    )
+    .await;
+
+    // Step: 1= A request inside its lease still holds the key off
+    let running = post_with_key(&app, "/api/v1/orders", &token, "running", order.clone()).await;
+
+    // Step: 2= One that outlived its lease died; the retry runs and its response is kept
+    let retry = post_with_key(&app, "/api/v1/orders", &token, "abandoned", order.clone()).await;
+    let retry_status = retry.status().as_u16();
+    let retry_replayed = retry.headers().contains_key(REPLAYED_HEADER);
+    let replay = post_with_key(&app, "/api/v1/orders", &token, "abandoned", order).await;
+    let replay_replayed = replay.headers().contains_key(REPLAYED_HEADER);
+    let mut conn = app.db_pool.get().await.unwrap();
+    let stored = key_dsl::idempotency_keys
+        .find((owner.as_str(), "abandoned"))
+        .first::<IdempotencyKey>(&mut conn)
+        .await
+        .unwrap();
+
+    drop(conn);
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(running.status().as_u16(), 409);
+    assert_eq!(retry_status, 200);
+    assert!(!retry_replayed);
+    assert!(replay_replayed);
+    assert_eq!(stored.status_code, Some(200));
+    assert_eq!(stored.locked_at, None);
+}
+
+#[tokio::test]
+async fn expired_and_abandoned_keys_are_swept() {
+    let app = spawn_app().await;
+    let now = chrono::Utc::now().naive_utc();
+    let mut conn = app.db_pool.get().await.unwrap();
+    for (key, status_code, locked_at, expires_at) in [
+        (
+            "expired",
+            Some(200),
+            None,
+            now - chrono::Duration::minutes(1),
+        ),
+        ("live", Some(200), None, now + chrono::Duration::hours(1)),
+        (
+            "abandoned",
+            None,
+            Some(now - chrono::Duration::minutes(5)),
+            now + chrono::Duration::hours(1),
+        ),
+        ("running", None, Some(now), now + chrono::Duration::hours(1)),
+    ] {
+        diesel::insert_into(key_dsl::idempotency_keys)
+            .values((
+                key_dsl::owner.eq("customer"),
+                key_dsl::idempotency_key.eq(key),
+                key_dsl::fingerprint.eq("fingerprint"),
+                key_dsl::status_code.eq(status_code),
+                key_dsl::locked_at.eq(locked_at),
+                key_dsl::created_at.eq(now - chrono::Duration::hours(2)),
+                key_dsl::expires_at.eq(expires_at),
+            ))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    let purged = idempotency::purge_expired(&app.db_pool, 60).await.unwrap();
+    let mut remaining = key_dsl::idempotency_keys
+        .select(key_dsl::idempotency_key)
+        .load::<String>(&mut conn)
+        .await
+        .unwrap();
+    remaining.sort();
+
+    drop(conn);
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(purged, 2);
+    assert_eq!(remaining, vec!["live".to_string(), "running".to_string()]);
+}
@@ -0,0 +1,292 @@
+use crate::helper::spawn_app;
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use ecommerce::db::drop_database;
+use ecommerce::db::PgPool;
+use ecommerce::jobs::worker::run_next;
+use ecommerce::jobs::{self, Job, JobHandler, JobRegistry, JobStatus};
+use ecommerce::schema::jobs::dsl as job_dsl;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const STALE_AFTER: Duration = Duration::from_secs(300);
+
+#[derive(Serialize, Deserialize)]
+struct CountJob {
+    amount: usize,
+}
+
+impl Job for CountJob {
+    const JOB_TYPE: &'static str = "test.count";
+}
+
+struct CountHandler(Arc<AtomicUsize>);
+
+#[async_trait]
+impl JobHandler<CountJob> for CountHandler {
+    async fn handle(&self, job: CountJob) -> Result<(), String> {
+        self.0.fetch_add(job.amount, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FailingJob;
+
+impl Job for FailingJob {
+    const JOB_TYPE: &'static str = "test.failing";
+    const MAX_ATTEMPTS: i32 = 2;
+}
+
+struct FailingHandler;
+
+#[async_trait]
+impl JobHandler<FailingJob> for FailingHandler {
+    async fn handle(&self, _job: FailingJob) -> Result<(), String> {
+        Err("downstream unavailable".to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SlowJob;
+
+impl Job for SlowJob {
+    const JOB_TYPE: &'static str = "test.slow";
+}
+
+// Runs past its claim: while it works, another worker claims the job again
+struct OvertakenHandler(PgPool);
+
+#[async_trait]
+impl JobHandler<SlowJob> for OvertakenHandler {
+    async fn handle(&self, _job: SlowJob) -> Result<(), String> {
+        let mut conn = self.0.get().await.map_err(|err| err.to_string())?;
+        diesel::update(job_dsl::jobs.filter(job_dsl::status.eq(JobStatus::Running)))
+            .set(job_dsl::locked_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(&mut conn)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+}
+
+// Stands in for a worker that claimed the job long ago and died mid-run
+async fn make_stale(pool: &PgPool, job_id: Uuid, attempts: i32) {
+    let mut conn = pool.get().await.expect("Failed to get db connection");
+    diesel::update(job_dsl::jobs.find(job_id))
+        .set((
+            job_dsl::status.eq(JobStatus::Running),
+            job_dsl::attempts.eq(attempts),
+            job_dsl::locked_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::hours(1)),
+        ))
+        .execute(&mut conn)
+        .await
+        .expect("Failed to mark job stale");
+}
+
+// Skips the backoff so the next `run_next` picks the job up again
+async fn make_runnable(pool: &PgPool, job_id: Uuid) {
+    let mut conn = pool.get().await.expect("Failed to get db connection");
+    diesel::update(job_dsl::jobs.find(job_id))
+        .set(job_dsl::run_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1)))
+        .execute(&mut conn)
+        .await
+        .expect("Failed to reschedule job");
+}
+
+#[tokio::test]
+async fn enqueued_job_is_run_once_and_completed() {
+    let app = spawn_app().await;
+    let counter = Arc::new(AtomicUsize::new(0));
+    let registry = JobRegistry::default().register::<CountJob, _>(CountHandler(counter.clone()));
+
+    let job_id = jobs::enqueue(&app.db_pool, &CountJob { amount: 3 })
+        .await
+        .expect("Failed to enqueue job");
+
+    let ran = run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to run job");
+    let ran_again = run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to poll jobs");
+    let job = jobs::find_job(&app.db_pool, job_id)
+        .await
+        .expect("Failed to fetch job")
+        .expect("Job not found");
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert!(ran);
+    assert!(!ran_again, "Completed jobs must not be claimed again");
+    // Spelled out because `RunQueryDsl::load` is in scope too
+    assert_eq!(AtomicUsize::load(&counter, Ordering::SeqCst), 3);
+    assert_eq!(job.status, JobStatus::Completed);
+    assert_eq!(job.attempts, 1);
+}
+
+#[tokio::test]
+async fn failing_job_is_retried_then_dead_lettered_and_requeued_by_admin() {
+    let app = spawn_app().await;
+    let registry = JobRegistry::default().register::<FailingJob, _>(FailingHandler);
+
+    let job_id = jobs::enqueue(&app.db_pool, &FailingJob)
+        .await
+        .expect("Failed to enqueue job");
+
+    // Step: 1= First failure schedules a retry in the future
+    run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to run job");
+    let retried = jobs::find_job(&app.db_pool, job_id).await.unwrap().unwrap();
+    assert_eq!(retried.status, JobStatus::Pending);
+    assert_eq!(retried.attempts, 1);
+    assert!(retried.run_at > chrono::Utc::now().naive_utc());
+    assert_eq!(
+        retried.last_error.as_deref(),
+        Some("downstream unavailable")
+    );
+    assert!(!run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .unwrap());
+
+    // Step: 2= The last attempt moves it to the dead-letter queue
+    make_runnable(&app.db_pool, job_id).await;
+    run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to run job");
+    let dead = jobs::find_job(&app.db_pool, job_id).await.unwrap().unwrap();
+    assert_eq!(dead.status, JobStatus::Dead);
+    assert_eq!(dead.attempts, 2);
+
+    // Step: 3= Admin inspects the dead-letter queue and requeues the job
+    let admin_login_response = app
+        .login_admin(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let admin_login_response: Value = admin_login_response.json().await.unwrap();
+    let admin_token = admin_login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let list_response = app
+        .api_client
+        .get(format!("{}/api/v1/admin/jobs?status=Dead", &app.address))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to list jobs");
+    let list_status = list_response.status().as_u16();
+    let listed: Value = list_response.json().await.unwrap();
+
+    let requeue_response = app
+        .api_client
+        .post(format!(
+            "{}/api/v1/admin/jobs/{}/requeue",
+            &app.address, job_id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to requeue job");
+    let requeue_status = requeue_response.status().as_u16();
+    let requeued: Value = requeue_response.json().await.unwrap();
+
+    let second_requeue = app
+        .api_client
+        .post(format!(
+            "{}/api/v1/admin/jobs/{}/requeue",
+            &app.address, job_id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to requeue job");
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(list_status, 200);
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["id"], job_id.to_string());
+    assert_eq!(requeue_status, 200);
+    assert_eq!(requeued["status"], "Pending");
+    assert_eq!(requeued["attempts"], 0);
+    assert_eq!(second_requeue.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn stale_jobs_are_reclaimed_only_while_attempts_remain() {
+    let app = spawn_app().await;
+    let counter = Arc::new(AtomicUsize::new(0));
+    let registry = JobRegistry::default().register::<CountJob, _>(CountHandler(counter.clone()));
+
+    let retried_id = jobs::enqueue(&app.db_pool, &CountJob { amount: 1 })
+        .await
+        .expect("Failed to enqueue job");
+    let exhausted_id = jobs::enqueue(&app.db_pool, &CountJob { amount: 10 })
+        .await
+        .expect("Failed to enqueue job");
+    make_stale(&app.db_pool, retried_id, 1).await;
+    make_stale(&app.db_pool, exhausted_id, CountJob::MAX_ATTEMPTS).await;
+
+    let ran = run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to run job");
+    let ran_again = run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to poll jobs");
+    let retried = jobs::find_job(&app.db_pool, retried_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let exhausted = jobs::find_job(&app.db_pool, exhausted_id)
+        .await
+        .unwrap()
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert!(ran);
+    assert!(!ran_again);
+    assert_eq!(AtomicUsize::load(&counter, Ordering::SeqCst), 1);
+    assert_eq!(retried.status, JobStatus::Completed);
+    assert_eq!(retried.attempts, 2);
+    assert_eq!(exhausted.status, JobStatus::Dead);
+    assert_eq!(exhausted.attempts, CountJob::MAX_ATTEMPTS);
+    assert_eq!(exhausted.locked_at, None);
+    assert_eq!(
+        exhausted.last_error.as_deref(),
+        Some("Worker stopped during the last attempt")
+    );
+}
+
+#[tokio::test]
+async fn a_worker_whose_job_was_claimed_again_leaves_it_to_the_new_claim() {
+    let app = spawn_app().await;
+    let registry =
+        JobRegistry::default().register::<SlowJob, _>(OvertakenHandler(app.db_pool.clone()));
+
+    let job_id = jobs::enqueue(&app.db_pool, &SlowJob)
+        .await
+        .expect("Failed to enqueue job");
+
+    let ran = run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to run job");
+    let job = jobs::find_job(&app.db_pool, job_id).await.unwrap().unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert!(ran);
+    assert_eq!(job.status, JobStatus::Running);
+    assert!(job.locked_at.is_some());
+}
@@ -0,0 +1,155 @@
+use crate::helper::{new_order, pay_for_order, spawn_app, TestApp};
+use ecommerce::db::drop_database;
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The session cookie set by a login, which is what marks the caller as an admin
+fn session_cookie(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+async fn connect(app: &TestApp, token: &str, cookie: &str) -> Result<Socket, tungstenite::Error> {
+    let url = format!(
+        "{}/api/v1/admin/orders/live",
+        app.address.replacen("http", "ws", 1)
+    );
+    let mut request = url.into_client_request().unwrap();
+    let headers = request.headers_mut();
+    headers.insert(
+        "Authorization",
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    headers.insert("Cookie", cookie.parse().unwrap());
+    connect_async(request).await.map(|(socket, _)| socket)
+}
+
+// Next JSON text frame; pings are answered by the client library while reading
+async fn next_message(socket: &mut Socket) -> Value {
+    loop {
+        let frame = tokio::time::timeout(READ_TIMEOUT, socket.next())
+            .await
+            .expect("Timed out waiting for a message")
+            .expect("Socket closed")
+            .expect("Failed to read from socket");
+        if let Message::Text(text) = frame {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn admins_receive_filtered_order_events() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= Admin login and subscribing to Pending orders only
+    let login_response = app
+        .login_admin(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let cookie = session_cookie(&login_response);
+    let login_response: Value = login_response.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let mut socket = connect(&app, &token, &cookie)
+        .await
+        .expect("Failed to open live orders socket");
+    socket
+        .send(Message::Text(
+            serde_json::json!({"type": "Subscribe", "statuses": ["Pending"]}).to_string(),
+        ))
+        .await
+        .unwrap();
+    let subscribed = next_message(&mut socket).await;
+
+    // Step: 2= Place an order, ship it, place another; the shipment is filtered out
+    let first = new_order(&app.db_pool, app.test_user.user_id).await;
+    repositories.orders.insert(first.clone()).await.unwrap();
+    let first_placed = next_message(&mut socket).await;
+    pay_for_order(&app.db_pool, app.test_user.user_id, first.id).await;
+    repositories
+        .orders
+        .update_status(first.id, OrderStatus::Shipped)
+        .await
+        .unwrap();
+    let second = new_order(&app.db_pool, app.test_user.user_id).await;
+    repositories.orders.insert(second.clone()).await.unwrap();
+    let second_placed = next_message(&mut socket).await;
+
+    // Step: 3= Malformed messages are answered, not fatal
+    socket
+        .send(Message::Text("{\"type\": \"Unsubscribe\"}".to_string()))
+        .await
+        .unwrap();
+    let error = next_message(&mut socket).await;
+    let _ = socket.close(None).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(subscribed["type"], "Subscribed");
+    assert_eq!(subscribed["statuses"], serde_json::json!(["Pending"]));
+    assert_eq!(first_placed["type"], "OrderPlaced");
+    assert_eq!(first_placed["order_id"], first.id.to_string());
+    assert_eq!(first_placed["status"], "Pending");
+    assert_eq!(second_placed["type"], "OrderPlaced");
+    assert_eq!(second_placed["order_id"], second.id.to_string());
+    assert_eq!(error["type"], "Error");
+}
+
+#[tokio::test]
+async fn customer_tokens_are_rejected() {
+    let app = spawn_app().await;
+
+    // A separate client so the session only knows the customer
+    let client = reqwest::Client::new();
+    let login_response = client
+        .post(format!("{}/api/v1/sessions", &app.address))
+        .json(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .send()
+        .await
+        .expect("Failed to log in");
+    let cookie = session_cookie(&login_response);
+    let login_response: Value = login_response.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let result = connect(&app, &token, &cookie).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    match result {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status().as_u16(), 401),
+        Err(err) => panic!("Unexpected error: {}", err),
+        Ok(_) => panic!("Customer was allowed onto the admin feed"),
+    }
+}
@@ -2,4 +2,20 @@ pub mod admin;
 pub mod customer;
 pub mod health_check;
 pub mod helper;
+pub mod idempotency;
+pub mod jobs;
+pub mod live_orders;
+pub mod metrics;
+pub mod migrations;
+pub mod notifications;
+pub mod openapi;
+pub mod operator;
 pub mod order;
+pub mod order_events;
+pub mod outbox;
+pub mod payments;
+pub mod request_id;
+pub mod returns;
+pub mod seed;
+pub mod versioning;
+pub mod webhooks;
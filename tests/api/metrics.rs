@@ -0,0 +1,38 @@
+use crate::helper::spawn_app;
+use ecommerce::db::drop_database;
+
+#[tokio::test]
+async fn metrics_endpoint_exposes_request_and_login_metrics() {
+    let app = spawn_app().await;
+
+    // Step: 1= Generating some traffic
+    app.api_client
+        .get(format!("{}/health_check", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let body = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let login_response = app.login_customer(body).await;
+    assert!(login_response.status().is_success());
+
+    // Step: 2= Scraping metrics
+    let response = app
+        .api_client
+        .get(format!("{}/metrics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains(
+        r#"ecommerce_http_requests_total{method="GET",route="/health_check",status="200"}"#
+    ));
+    assert!(body.contains("ecommerce_http_request_duration_seconds_bucket"));
+    assert!(body.contains(r#"ecommerce_logins_total{outcome="success",role="customer"}"#));
+    assert!(body.contains("ecommerce_db_pool_max_size 16"));
+    drop_database(&app.database_name, app.test_db_url).await;
+}
@@ -0,0 +1,32 @@
+use ecommerce::config::configuration::Settings;
+use ecommerce::db::{create_database, drop_database};
+use ecommerce::migrate::{migration_status, revert_last_migration, run_migrations};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn migrations_can_be_applied_and_reverted() {
+    let config = Settings::new().expect("Failed to load configurations");
+    let database_name = Uuid::new_v4().to_string();
+    create_database(&database_name, config.database.test_url.clone()).await;
+    let database_url = format!("{}/{}", config.database.test_url, database_name);
+
+    let status = migration_status(&database_url).await.unwrap();
+    assert!(status.applied.is_empty());
+    let total = status.pending.len();
+    assert!(total > 0);
+
+    let applied = run_migrations(&database_url).await.unwrap();
+    assert_eq!(applied.len(), total);
+    let status = migration_status(&database_url).await.unwrap();
+    assert!(status.pending.is_empty());
+
+    // Running again is a no-op
+    assert!(run_migrations(&database_url).await.unwrap().is_empty());
+
+    revert_last_migration(&database_url).await.unwrap();
+    let status = migration_status(&database_url).await.unwrap();
+    assert_eq!(status.pending.len(), 1);
+
+    run_migrations(&database_url).await.unwrap();
+    drop_database(&database_name, config.database.test_url).await;
+}
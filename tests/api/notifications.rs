@@ -0,0 +1,90 @@
+use crate::helper::{pay_for_order, seed_products, spawn_app};
+use diesel_async::RunQueryDsl;
+use ecommerce::db::drop_database;
+use ecommerce::db_models::{Customer, Order};
+use ecommerce::jobs::worker::run_next;
+use ecommerce::jobs::JobRegistry;
+use ecommerce::mailer::notifications::{Notification, NotificationHandler, NotificationSink};
+use ecommerce::mailer::InMemoryMailer;
+use ecommerce::outbox::dispatcher::{dispatch_pending, EventSink};
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const STALE_AFTER: Duration = Duration::from_secs(300);
+
+#[tokio::test]
+async fn lifecycle_events_send_one_email_each() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= Register a customer, place an order and ship it
+    let customer = Customer {
+        id: Uuid::new_v4(),
+        username: "ada".to_string(),
+        password_hash: "not-a-real-hash".to_string(),
+        email: "ada@example.com".to_string(),
+        created_at: None,
+    };
+    repositories
+        .customers
+        .insert(customer.clone())
+        .await
+        .unwrap();
+    let order = Order {
+        id: Uuid::new_v4(),
+        customer_id: customer.id,
+        status: OrderStatus::Pending,
+        created_at: chrono::Utc::now().naive_utc(),
+        product_id: Uuid::parse_str("5fcd7d83-7adf-4d4d-931a-68b9678009db").unwrap(),
+    };
+    repositories.orders.insert(order.clone()).await.unwrap();
+    pay_for_order(&app.db_pool, customer.id, order.id).await;
+    repositories
+        .orders
+        .update_status(order.id, OrderStatus::Shipped)
+        .await
+        .unwrap();
+
+    // Step: 2= Dispatch, simulate a redelivery of every event, then run the email jobs
+    let sinks: Vec<Arc<dyn EventSink>> = vec![Arc::new(NotificationSink::new(app.db_pool.clone()))];
+    dispatch_pending(&app.db_pool, &sinks, 100)
+        .await
+        .expect("Failed to dispatch outbox");
+    let mut conn = app.db_pool.get().await.unwrap();
+    diesel::sql_query("UPDATE outbox_events SET dispatched_at = NULL")
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    drop(conn);
+    dispatch_pending(&app.db_pool, &sinks, 100)
+        .await
+        .expect("Failed to dispatch outbox");
+
+    let mailer = Arc::new(InMemoryMailer::default());
+    let registry = JobRegistry::default().register::<Notification, _>(NotificationHandler::new(
+        repositories.clone(),
+        mailer.clone(),
+    ));
+    while run_next(&app.db_pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to run email job")
+    {}
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    let sent = mailer.sent();
+    assert_eq!(sent.len(), 3);
+    assert!(sent.iter().all(|email| email.to == "ada@example.com"));
+    let subjects: Vec<&str> = sent.iter().map(|email| email.subject.as_str()).collect();
+    assert!(subjects.contains(&"Welcome to ecommerce, ada"));
+    assert!(subjects.contains(&format!("Order {} confirmed", order.id).as_str()));
+    assert!(subjects.contains(&format!("Order {} has shipped", order.id).as_str()));
+    assert!(sent
+        .iter()
+        .filter(|email| email.subject.starts_with("Order"))
+        .all(|email| email.text.contains("Laptop") && email.html.contains("Laptop")));
+}
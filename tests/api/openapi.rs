@@ -0,0 +1,84 @@
+use crate::helper::spawn_app;
+use ecommerce::db::drop_database;
+use ecommerce::openapi::ApiDoc;
+use ecommerce::routes::table::ROUTE_GROUPS;
+use serde_json::Value;
+use utoipa::OpenApi;
+
+// Every route `startup::run_server` mounts, as `(method, full path)`
+fn registered_routes() -> Vec<(&'static str, String)> {
+    ROUTE_GROUPS
+        .iter()
+        .flat_map(|group| group.routes())
+        .collect()
+}
+
+#[test]
+fn every_registered_route_is_documented() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let missing: Vec<String> = registered_routes()
+        .iter()
+        .filter(|(method, path)| spec["paths"][path.as_str()][*method].is_null())
+        .map(|(method, path)| format!("{} {}", method.to_uppercase(), path))
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "Routes missing from the OpenAPI spec: {:?}",
+        missing
+    );
+}
+
+#[test]
+fn every_documented_route_is_registered() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let routes = registered_routes();
+    let unknown: Vec<String> = spec["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, operations)| {
+            operations
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(move |method| (method.clone(), path.clone()))
+        })
+        .filter(|(method, path)| {
+            !routes
+                .iter()
+                .any(|(registered, full)| registered == method && full == path)
+        })
+        .map(|(method, path)| format!("{} {}", method.to_uppercase(), path))
+        .collect();
+    assert!(
+        unknown.is_empty(),
+        "Documented routes that aren't registered: {:?}",
+        unknown
+    );
+}
+
+#[tokio::test]
+async fn openapi_document_and_swagger_ui_are_served() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/openapi.json", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    let spec: Value = response.json().await.unwrap();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(spec["paths"]["/protected/admin/fetch_all_orders"]["get"].is_object());
+    assert!(spec["components"]["securitySchemes"]["bearer_auth"].is_object());
+
+    let response = app
+        .api_client
+        .get(format!("{}/swagger-ui/", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    drop_database(&app.database_name, app.test_db_url).await;
+}
@@ -0,0 +1,105 @@
+use crate::helper::{seed_products, spawn_app};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use ecommerce::db::drop_database;
+use ecommerce::operator::{self, AccountKind};
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use ecommerce::schema::orders::dsl as order;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn created_admin_can_log_in() {
+    let app = spawn_app().await;
+
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    operator::create_admin(&repositories, "ops-admin".to_string(), "s3cret-pass")
+        .await
+        .expect("Failed to create admin");
+    let response = app
+        .login_admin(serde_json::json!({
+            "username": "ops-admin",
+            "password": "s3cret-pass"
+        }))
+        .await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn reset_password_replaces_the_old_one() {
+    let app = spawn_app().await;
+
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    operator::reset_password(
+        &repositories,
+        AccountKind::Customer,
+        &app.test_user.username,
+        "brand-new-pass",
+    )
+    .await
+    .expect("Failed to reset password");
+    let old = app
+        .login_customer(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let new = app
+        .login_customer(serde_json::json!({
+            "username": app.test_user.username,
+            "password": "brand-new-pass"
+        }))
+        .await;
+    let unknown =
+        operator::reset_password(&repositories, AccountKind::Admin, "nobody", "whatever").await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+    assert!(!old.status().is_success());
+    assert!(new.status().is_success());
+    assert!(unknown.is_err());
+}
+
+#[tokio::test]
+async fn export_orders_writes_csv_filtered_by_status() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let product_id = Uuid::parse_str("5fcd7d83-7adf-4d4d-931a-68b9678009db").unwrap();
+    let pending_id = Uuid::new_v4();
+    let shipped_id = Uuid::new_v4();
+    let mut conn = app.db_pool.get().await.unwrap();
+    for (order_id, status) in [
+        (pending_id, OrderStatus::Pending),
+        (shipped_id, OrderStatus::Shipped),
+    ] {
+        diesel::insert_into(order::orders)
+            .values((
+                order::id.eq(order_id),
+                order::customer_id.eq(app.test_user.user_id),
+                order::product_id.eq(product_id),
+                order::created_at.eq(chrono::Utc::now().naive_utc()),
+                order::status.eq(status),
+            ))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+    drop(conn);
+
+    let orders = Repositories::postgres(app.db_pool.clone())
+        .orders
+        .list(Some(OrderStatus::Shipped))
+        .await
+        .unwrap();
+    let mut csv = Vec::new();
+    operator::write_orders_csv(&orders, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "id,customer_id,product_id,status,created_at");
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with(&format!("{},", shipped_id)));
+    assert!(lines[1].contains(",Shipped,"));
+}
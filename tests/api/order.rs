@@ -1,7 +1,13 @@
-use crate::helper::{seed_products, spawn_app};
+use crate::helper::{pay_for_order, seed_products, spawn_app};
 use ecommerce::db::drop_database;
+use ecommerce::db_models::{Order, Product};
+use ecommerce::errors::custom::CustomError;
+use ecommerce::payments::{self, mock::MockPaymentProvider};
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
 use serde_json::{self, Value};
 use std::time::Duration;
+use uuid::Uuid;
 
 #[tokio::test]
 async fn order_creation_get_and_list() {
@@ -36,13 +42,15 @@ async fn order_creation_get_and_list() {
         .expect("Token not found");
 
     // Step: 4= Reteriving new order data and checking status is default set as pending
-    let order_reterive_response = app.get_order(&order_id, token.to_string()).await;
+    let order_reterive_response = app.get_order(order_id, token.to_string()).await;
 
     assert_eq!(order_reterive_response.status().as_u16(), 200);
-    let order_reterive_response_text = order_reterive_response.text().await.unwrap();
-    assert!(order_reterive_response_text
-        .to_lowercase()
-        .contains("pending"));
+    let order_detail: Value = order_reterive_response.json().await.unwrap();
+    assert_eq!(order_detail["id"], order_id);
+    assert_eq!(order_detail["status"], "Pending");
+    assert_eq!(order_detail["product"]["name"], "Laptop");
+    assert_eq!(order_detail["product"]["price"], 50000);
+    assert!(order_detail.get("customer").is_none());
 
     // Step: 5= Reteriving all Orders by the customer
     let orders_all = app.get_all_orders(token.to_string()).await;
@@ -52,3 +60,420 @@ async fn order_creation_get_and_list() {
     assert!(orders_all_response.contains("5fcd7d83-7adf-4d4d-931a-68b9678009db"));
     drop_database(&app.database_name, app.test_db_url).await;
 }
+
+#[tokio::test]
+async fn order_listings_are_paginated_and_filtered() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= Five orders a minute apart, the oldest one shipped
+    // Whole seconds, so the bounds below match what Postgres stores
+    let start = chrono::DateTime::from_timestamp(chrono::Utc::now().timestamp() - 3600, 0)
+        .unwrap()
+        .naive_utc();
+    let mut order_ids = Vec::new();
+    for minute in 0..5 {
+        let order = Order {
+            id: Uuid::new_v4(),
+            customer_id: app.test_user.user_id,
+            status: OrderStatus::Pending,
+            created_at: start + chrono::Duration::minutes(minute),
+            product_id: Uuid::parse_str("5fcd7d83-7adf-4d4d-931a-68b9678009db").unwrap(),
+        };
+        order_ids.push(order.id.to_string());
+        repositories.orders.insert(order).await.unwrap();
+    }
+    let shipped_id = Uuid::parse_str(&order_ids[0]).unwrap();
+    pay_for_order(&app.db_pool, app.test_user.user_id, shipped_id).await;
+    repositories
+        .orders
+        .update_status(shipped_id, OrderStatus::Shipped)
+        .await
+        .unwrap();
+
+    let login_response = app
+        .login_admin(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let login_response: Value = login_response.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let list = |query: String| {
+        let request = app
+            .api_client
+            .get(format!("{}/api/v1/admin/orders?{}", &app.address, query))
+            .bearer_auth(&token);
+        async move { request.send().await.expect("Failed to list orders") }
+    };
+    let ids = |page: &Value| -> Vec<String> {
+        page["orders"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|order| order["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // Step: 2= Walk oldest first two at a time, then step back
+    let first: Value = list("limit=2&sort=oldest".to_string())
+        .await
+        .json()
+        .await
+        .unwrap();
+    let second: Value = list(format!(
+        "limit=2&cursor={}",
+        first["next_cursor"].as_str().unwrap()
+    ))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let third: Value = list(format!(
+        "limit=2&cursor={}",
+        second["next_cursor"].as_str().unwrap()
+    ))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let back: Value = list(format!(
+        "limit=2&cursor={}",
+        third["prev_cursor"].as_str().unwrap()
+    ))
+    .await
+    .json()
+    .await
+    .unwrap();
+
+    // Step: 3= Filters and bad input
+    let pending_newest: Value = list(format!(
+        "status=Pending&customer_id={}",
+        app.test_user.user_id
+    ))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let in_window: Value = list(format!(
+        "created_from={}&created_to={}",
+        (start + chrono::Duration::minutes(1)).format("%Y-%m-%dT%H:%M:%S"),
+        (start + chrono::Duration::minutes(3)).format("%Y-%m-%dT%H:%M:%S"),
+    ))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let detail: Value = app
+        .api_client
+        .get(format!(
+            "{}/api/v1/admin/orders/{}",
+            &app.address, order_ids[0]
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to fetch order")
+        .json()
+        .await
+        .unwrap();
+    let bad_cursor = list("cursor=not-a-cursor".to_string()).await;
+    let wrong_sort = list(format!(
+        "sort=newest&cursor={}",
+        first["next_cursor"].as_str().unwrap()
+    ))
+    .await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(ids(&first), order_ids[0..2]);
+    assert!(first["prev_cursor"].is_null());
+    assert_eq!(ids(&second), order_ids[2..4]);
+    assert_eq!(ids(&third), order_ids[4..5]);
+    assert!(third["next_cursor"].is_null());
+    assert_eq!(ids(&back), order_ids[2..4]);
+
+    let mut pending_expected: Vec<String> = order_ids[1..5].to_vec();
+    pending_expected.reverse();
+    assert_eq!(ids(&pending_newest), pending_expected);
+    assert_eq!(
+        ids(&in_window),
+        vec![order_ids[2].clone(), order_ids[1].clone()]
+    );
+    assert_eq!(
+        first["orders"][0]["customer"]["username"],
+        app.test_user.username.as_str()
+    );
+    assert_eq!(first["orders"][0]["product"]["name"], "Laptop");
+    assert_eq!(detail["status"], "Shipped");
+    assert_eq!(detail["customer"]["id"], app.test_user.user_id.to_string());
+    assert_eq!(bad_cursor.status().as_u16(), 400);
+    assert_eq!(wrong_sort.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn pending_orders_can_be_cancelled_by_customers_and_admins() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= Three orders, the last one already shipped
+    let mut order_ids = Vec::new();
+    for _ in 0..3 {
+        let order = Order {
+            id: Uuid::new_v4(),
+            customer_id: app.test_user.user_id,
+            status: OrderStatus::Pending,
+            created_at: chrono::Utc::now().naive_utc(),
+            product_id: Uuid::parse_str("5fcd7d83-7adf-4d4d-931a-68b9678009db").unwrap(),
+        };
+        order_ids.push(order.id);
+        repositories.orders.insert(order).await.unwrap();
+    }
+    pay_for_order(&app.db_pool, app.test_user.user_id, order_ids[2]).await;
+    repositories
+        .orders
+        .update_status(order_ids[2], OrderStatus::Shipped)
+        .await
+        .unwrap();
+
+    let login_response = app
+        .login_customer(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let login_response: Value = login_response.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let cancel = |path: String, token: &str, reason: &str| {
+        let request = app
+            .api_client
+            .post(format!("{}{}", &app.address, path))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "reason": reason }));
+        async move { request.send().await.expect("Failed to cancel order") }
+    };
+
+    // Step: 2= The customer cancels a pending order, but not a shipped one
+    let cancelled = cancel(
+        format!("/api/v1/orders/{}/cancel", order_ids[0]),
+        &token,
+        "Found it cheaper",
+    )
+    .await;
+    let cancelled_status = cancelled.status().as_u16();
+    let cancelled: Value = cancelled.json().await.unwrap();
+    let shipped = cancel(
+        format!("/api/v1/orders/{}/cancel", order_ids[2]),
+        &token,
+        "Too slow",
+    )
+    .await;
+
+    // Step: 3= An admin cancels another with a reason
+    let admin_login_response = app
+        .login_admin(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let admin_login_response: Value = admin_login_response.json().await.unwrap();
+    let admin_token = admin_login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+    let admin_cancelled: Value = cancel(
+        format!("/api/v1/admin/orders/{}/cancel", order_ids[1]),
+        &admin_token,
+        "Payment flagged",
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let admin_again = cancel(
+        format!("/api/v1/admin/orders/{}/cancel", order_ids[0]),
+        &admin_token,
+        "Payment flagged",
+    )
+    .await;
+    let cancelled_by_patch = app
+        .api_client
+        .patch(format!(
+            "{}/api/v1/admin/orders/{}",
+            &app.address, order_ids[2]
+        ))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "status": "Cancelled" }))
+        .send()
+        .await
+        .expect("Failed to update order");
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(cancelled_status, 200);
+    assert_eq!(cancelled["status"], "Cancelled");
+    assert_eq!(cancelled["cancellation"]["reason"], "Found it cheaper");
+    assert!(cancelled["cancellation"].get("admin_id").is_none());
+    assert_eq!(shipped.status().as_u16(), 409);
+    assert_eq!(admin_cancelled["status"], "Cancelled");
+    assert_eq!(admin_cancelled["cancellation"]["reason"], "Payment flagged");
+    assert!(admin_cancelled["cancellation"]["admin_id"].is_string());
+    assert_eq!(admin_again.status().as_u16(), 409);
+    assert_eq!(cancelled_by_patch.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn orders_keep_the_totals_they_were_placed_with() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let body = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let login_response: Value = app.login_customer(body).await.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    // Step: 1= Placed in a taxed region at today's price
+    let created: Value = app
+        .create_order(
+            serde_json::json!({
+                "product_id": "5fcd7d83-7adf-4d4d-931a-68b9678009db",
+                "region": "us-ca"
+            }),
+            token.clone(),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    let order_id = created["order_id"].as_str().unwrap().to_string();
+    let bad_region = app
+        .create_order(
+            serde_json::json!({
+                "product_id": "5fcd7d83-7adf-4d4d-931a-68b9678009db",
+                "region": "not a region"
+            }),
+            token.clone(),
+        )
+        .await;
+    let unknown_region = app
+        .create_order(
+            serde_json::json!({
+                "product_id": "5fcd7d83-7adf-4d4d-931a-68b9678009db",
+                "region": "fr"
+            }),
+            token.clone(),
+        )
+        .await;
+
+    // Step: 2= The product gets dearer afterwards
+    let mut product = repositories
+        .products
+        .find(Uuid::parse_str("5fcd7d83-7adf-4d4d-931a-68b9678009db").unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    product.price = 60000;
+    repositories.products.update(product).await.unwrap();
+    let detail: Value = app
+        .get_order(&order_id, token.clone())
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Step: 3= The payment is for what the order cost, not what the product costs now
+    let payment = payments::authorize(
+        &app.db_pool,
+        &MockPaymentProvider,
+        app.test_user.user_id,
+        Uuid::parse_str(&order_id).unwrap(),
+        "tok_visa",
+    )
+    .await
+    .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(created["totals"]["subtotal"], 50000);
+    assert_eq!(created["totals"]["tax"], 3625);
+    assert_eq!(created["totals"]["shipping"], 0);
+    assert_eq!(created["totals"]["grand_total"], 53625);
+    assert_eq!(created["totals"]["tax_region"], "US-CA");
+    assert_eq!(bad_region.status().as_u16(), 400);
+    assert_eq!(unknown_region.status().as_u16(), 400);
+    assert_eq!(detail["product"]["price"], 60000);
+    assert_eq!(detail["totals"]["unit_price"], 50000);
+    assert_eq!(detail["totals"]["grand_total"], 53625);
+    assert_eq!(payment.amount, 53625);
+}
+
+#[tokio::test]
+async fn orders_hold_stock_until_they_are_cancelled() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let product = Product {
+        id: Uuid::new_v4(),
+        name: "Last Keyboard".to_string(),
+        is_available: true,
+        price: 4999,
+        sku: Some("KEYBOARD-LAST".to_string()),
+        stock: Some(1),
+    };
+    repositories.products.insert(product.clone()).await.unwrap();
+    let order = || Order {
+        id: Uuid::new_v4(),
+        customer_id: app.test_user.user_id,
+        status: OrderStatus::Pending,
+        created_at: chrono::Utc::now().naive_utc(),
+        product_id: product.id,
+    };
+    let stock = || async {
+        repositories
+            .products
+            .find(product.id)
+            .await
+            .unwrap()
+            .and_then(|product| product.stock)
+    };
+
+    // Step: 1= The only unit goes to the first order
+    let first = order();
+    repositories.orders.insert(first.clone()).await.unwrap();
+    let after_order = stock().await;
+    let sold_out = repositories.orders.insert(order()).await;
+
+    // Step: 2= Cancelling puts it back for the next customer
+    repositories
+        .orders
+        .cancel(first.id, "Ordered twice", None)
+        .await
+        .unwrap();
+    let after_cancel = stock().await;
+    let reordered = repositories.orders.insert(order()).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(after_order, Some(0));
+    assert!(matches!(sold_out, Err(CustomError::ConflictError(_))));
+    assert_eq!(after_cancel, Some(1));
+    assert!(reordered.is_ok());
+}
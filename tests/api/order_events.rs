@@ -0,0 +1,166 @@
+use crate::helper::{new_order, pay_for_order, spawn_app};
+use ecommerce::db::drop_database;
+use ecommerce::db_models::Customer;
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Reads `text/event-stream` frames, skipping heartbeats and the `retry` hint
+struct EventReader {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl EventReader {
+    // Returns the `(id, data)` of the next event carrying data
+    async fn next_event(&mut self) -> (String, Value) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let frame: String = self.buffer.drain(..end + 2).collect();
+                let field = |name: &str| {
+                    frame
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(|value| value.trim().to_string())
+                };
+                if let Some(data) = field("data:") {
+                    let id = field("id:").expect("Event without id");
+                    return (id, serde_json::from_str(&data).unwrap());
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(READ_TIMEOUT, self.response.chunk())
+                .await
+                .expect("Timed out waiting for an event")
+                .expect("Failed to read event stream")
+                .expect("Event stream ended");
+            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+}
+
+async fn open_stream(
+    app: &crate::helper::TestApp,
+    token: &str,
+    last_event_id: Option<&str>,
+) -> reqwest::Response {
+    let mut request = app
+        .api_client
+        .get(format!("{}/api/v1/orders/events", &app.address))
+        .bearer_auth(token);
+    if let Some(last_event_id) = last_event_id {
+        request = request.header("Last-Event-ID", last_event_id);
+    }
+    request.send().await.expect("Failed to open event stream")
+}
+
+#[tokio::test]
+async fn status_changes_stream_to_the_owner_and_replay_after_reconnect() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= Customer login and opening the stream
+    let login_response = app
+        .login_customer(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let login_response: Value = login_response.json().await.unwrap();
+    let token = login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let response = open_stream(&app, &token, None).await;
+    let stream_status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .map(|value| value.to_str().unwrap().to_string());
+    let mut reader = EventReader {
+        response,
+        buffer: String::new(),
+    };
+
+    // Step: 2= Another customer's order ships first, then the caller's
+    let stranger = Customer {
+        id: Uuid::new_v4(),
+        username: "stranger".to_string(),
+        password_hash: "not-a-real-hash".to_string(),
+        email: "stranger@example.com".to_string(),
+        created_at: None,
+    };
+    repositories
+        .customers
+        .insert(stranger.clone())
+        .await
+        .unwrap();
+    let stranger_order = new_order(&app.db_pool, stranger.id).await;
+    repositories
+        .orders
+        .insert(stranger_order.clone())
+        .await
+        .unwrap();
+    pay_for_order(&app.db_pool, stranger.id, stranger_order.id).await;
+    repositories
+        .orders
+        .update_status(stranger_order.id, OrderStatus::Shipped)
+        .await
+        .unwrap();
+    let order = new_order(&app.db_pool, app.test_user.user_id).await;
+    repositories.orders.insert(order.clone()).await.unwrap();
+    pay_for_order(&app.db_pool, app.test_user.user_id, order.id).await;
+    repositories
+        .orders
+        .update_status(order.id, OrderStatus::Shipped)
+        .await
+        .unwrap();
+    let (shipped_id, shipped) = reader.next_event().await;
+
+    // Step: 3= Disconnect, miss a change, reconnect with Last-Event-ID
+    drop(reader);
+    repositories
+        .orders
+        .update_status(order.id, OrderStatus::Delivered)
+        .await
+        .unwrap();
+    let mut reconnected = EventReader {
+        response: open_stream(&app, &token, Some(&shipped_id)).await,
+        buffer: String::new(),
+    };
+    let (delivered_id, delivered) = reconnected.next_event().await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(stream_status, 200);
+    assert_eq!(content_type.as_deref(), Some("text/event-stream"));
+    assert_eq!(shipped["order_id"], order.id.to_string());
+    assert_eq!(shipped["from"], "Pending");
+    assert_eq!(shipped["to"], "Shipped");
+    assert_eq!(delivered["order_id"], order.id.to_string());
+    assert_eq!(delivered["from"], "Shipped");
+    assert_eq!(delivered["to"], "Delivered");
+    assert_ne!(delivered_id, shipped_id);
+}
+
+#[tokio::test]
+async fn streaming_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/orders/events", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,286 @@
+use crate::helper::{new_order, pay_for_order, spawn_app};
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use ecommerce::db::drop_database;
+use ecommerce::db::PgPool;
+use ecommerce::db_models::{Customer, OutboxEvent};
+use ecommerce::outbox::dispatcher::{dispatch_pending, EventSink};
+use ecommerce::outbox::{record, DomainEvent, EventEnvelope};
+use ecommerce::payments::PaymentStatus;
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use ecommerce::schema::outbox_events::dsl as outbox_dsl;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct RecordingSink {
+    events: Mutex<Vec<EventEnvelope>>,
+}
+
+#[async_trait]
+impl EventSink for RecordingSink {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    async fn deliver(&self, event: &EventEnvelope) -> Result<(), String> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+struct FailingSink;
+
+#[async_trait]
+impl EventSink for FailingSink {
+    fn name(&self) -> &'static str {
+        "failing"
+    }
+
+    async fn deliver(&self, _event: &EventEnvelope) -> Result<(), String> {
+        Err("connection refused".to_string())
+    }
+}
+
+async fn outbox_rows(pool: &PgPool) -> Vec<OutboxEvent> {
+    let mut conn = pool.get().await.expect("Failed to get db connection");
+    outbox_dsl::outbox_events
+        .order(outbox_dsl::created_at.asc())
+        .load::<OutboxEvent>(&mut conn)
+        .await
+        .expect("Failed to load outbox events")
+}
+
+#[tokio::test]
+async fn changes_record_events_that_are_dispatched_once() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= Every change writes its event alongside the row
+    let customer_id = Uuid::new_v4();
+    repositories
+        .customers
+        .insert(Customer {
+            id: customer_id,
+            username: "outbox-customer".to_string(),
+            password_hash: "not-a-real-hash".to_string(),
+            email: "outbox@example.com".to_string(),
+            created_at: None,
+        })
+        .await
+        .expect("Failed to insert customer");
+    let order = new_order(&app.db_pool, customer_id).await;
+    repositories
+        .orders
+        .insert(order.clone())
+        .await
+        .expect("Failed to insert order");
+    let payment = pay_for_order(&app.db_pool, customer_id, order.id).await;
+    repositories
+        .orders
+        .update_status(order.id, OrderStatus::Shipped)
+        .await
+        .expect("Failed to update status");
+    // Not a transition, so nothing is recorded
+    repositories
+        .orders
+        .update_status(order.id, OrderStatus::Shipped)
+        .await
+        .expect("Failed to update status");
+
+    // Step: 2= The dispatcher hands them to the sinks oldest first
+    let sink = Arc::new(RecordingSink::default());
+    let sinks: Vec<Arc<dyn EventSink>> = vec![sink.clone()];
+    let handled = dispatch_pending(&app.db_pool, &sinks, 100)
+        .await
+        .expect("Failed to dispatch");
+    let handled_again = dispatch_pending(&app.db_pool, &sinks, 100)
+        .await
+        .expect("Failed to dispatch");
+    let rows = outbox_rows(&app.db_pool).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(handled, 5);
+    assert_eq!(
+        handled_again, 0,
+        "Dispatched events must not be redelivered"
+    );
+    assert!(rows.iter().all(|row| row.dispatched_at.is_some()));
+
+    let delivered: Vec<DomainEvent> = sink
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|envelope| envelope.event.clone())
+        .collect();
+    assert_eq!(
+        delivered,
+        vec![
+            DomainEvent::CustomerRegistered {
+                customer_id,
+                username: "outbox-customer".to_string(),
+                email: "outbox@example.com".to_string(),
+            },
+            DomainEvent::OrderPlaced {
+                order_id: order.id,
+                customer_id,
+                product_id: order.product_id,
+                status: OrderStatus::Pending,
+                created_at: order.created_at,
+            },
+            DomainEvent::PaymentStatusChanged {
+                payment_id: payment.id,
+                order_id: order.id,
+                customer_id,
+                from: None,
+                to: PaymentStatus::Authorized,
+                amount: payment.amount,
+            },
+            DomainEvent::PaymentStatusChanged {
+                payment_id: payment.id,
+                order_id: order.id,
+                customer_id,
+                from: Some(PaymentStatus::Authorized),
+                to: PaymentStatus::Captured,
+                amount: payment.amount,
+            },
+            DomainEvent::OrderStatusChanged {
+                order_id: order.id,
+                customer_id,
+                from: OrderStatus::Pending,
+                to: OrderStatus::Shipped,
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn failed_change_records_no_event() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // The test user already owns this username
+    let result = repositories
+        .customers
+        .insert(Customer {
+            id: Uuid::new_v4(),
+            username: app.test_user.username.clone(),
+            password_hash: "not-a-real-hash".to_string(),
+            email: "duplicate@example.com".to_string(),
+            created_at: None,
+        })
+        .await;
+    let rows = outbox_rows(&app.db_pool).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert!(result.is_err());
+    assert!(rows.is_empty());
+}
+
+#[tokio::test]
+async fn failed_delivery_is_retried_later() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    repositories
+        .orders
+        .insert(new_order(&app.db_pool, app.test_user.user_id).await)
+        .await
+        .expect("Failed to insert order");
+
+    let sinks: Vec<Arc<dyn EventSink>> = vec![Arc::new(FailingSink)];
+    let handled = dispatch_pending(&app.db_pool, &sinks, 100)
+        .await
+        .expect("Failed to dispatch");
+    // Backed off, so an immediate second pass leaves it alone
+    let handled_again = dispatch_pending(&app.db_pool, &sinks, 100)
+        .await
+        .expect("Failed to dispatch");
+    let rows = outbox_rows(&app.db_pool).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(handled, 1);
+    assert_eq!(handled_again, 0);
+    assert_eq!(rows.len(), 1);
+    assert!(rows[0].dispatched_at.is_none());
+    assert_eq!(rows[0].attempts, 1);
+    assert_eq!(
+        rows[0].last_error.as_deref(),
+        Some("failing: connection refused")
+    );
+    assert!(rows[0].next_attempt_at > chrono::Utc::now().naive_utc());
+}
+
+#[tokio::test]
+async fn only_the_same_customers_events_wait_for_each_other() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= An open transaction has recorded an event for the test user
+    let (recorded, held) = tokio::sync::oneshot::channel();
+    let pool = app.db_pool.clone();
+    let customer_id = app.test_user.user_id;
+    let holder = tokio::spawn(async move {
+        let mut conn = pool.get().await.expect("Failed to get db connection");
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                record(
+                    conn,
+                    &DomainEvent::CustomerRegistered {
+                        customer_id,
+                        username: "holder".to_string(),
+                        email: "holder@example.com".to_string(),
+                    },
+                )
+                .await?;
+                let _ = recorded.send(());
+                tokio::time::sleep(Duration::from_secs(3)).await;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .expect("Failed to record event");
+    });
+    held.await.expect("Holder never recorded its event");
+
+    // Step: 2= Another customer's change doesn't wait for it to commit
+    let started = Instant::now();
+    repositories
+        .customers
+        .insert(Customer {
+            id: Uuid::new_v4(),
+            username: "bystander".to_string(),
+            password_hash: "not-a-real-hash".to_string(),
+            email: "bystander@example.com".to_string(),
+            created_at: None,
+        })
+        .await
+        .expect("Failed to insert customer");
+    let elapsed = started.elapsed();
+    holder.await.unwrap();
+    let rows = outbox_rows(&app.db_pool).await;
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert!(elapsed < Duration::from_secs(2), "waited {:?}", elapsed);
+    // The holder numbered its event first but committed last
+    assert_eq!(rows.len(), 2);
+    let holder_row = rows
+        .iter()
+        .find(|row| row.aggregate_id == customer_id)
+        .unwrap();
+    let bystander_row = rows
+        .iter()
+        .find(|row| row.aggregate_id != customer_id)
+        .unwrap();
+    assert!(holder_row.seq < bystander_row.seq);
+}
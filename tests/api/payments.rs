@@ -0,0 +1,471 @@
+use crate::helper::{seed_products, spawn_app, TestApp, PAYMENT_WEBHOOK_SECRET};
+use ecommerce::db::drop_database;
+use ecommerce::db_models::{Order, Product};
+use ecommerce::payments::{self, mock::MockPaymentProvider};
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use ecommerce::webhooks::{signature, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+
+const PRODUCT_ID: &str = "5fcd7d83-7adf-4d4d-931a-68b9678009db";
+
+async fn place_order(app: &TestApp) -> Uuid {
+    let order = Order {
+        id: Uuid::new_v4(),
+        customer_id: app.test_user.user_id,
+        status: OrderStatus::Pending,
+        created_at: chrono::Utc::now().naive_utc(),
+        product_id: Uuid::parse_str(PRODUCT_ID).unwrap(),
+    };
+    Repositories::postgres(app.db_pool.clone())
+        .orders
+        .insert(order.clone())
+        .await
+        .unwrap();
+    order.id
+}
+
+async fn token(response: reqwest::Response) -> String {
+    let body: Value = response.json().await.unwrap();
+    body["token"].as_str().expect("Token not found").to_string()
+}
+
+async fn tokens(app: &TestApp) -> (String, String) {
+    let credentials = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let customer_token = token(app.login_customer(credentials.clone()).await).await;
+    let admin_token = token(app.login_admin(credentials).await).await;
+    tokio::time::sleep(Duration::from_secs(12)).await;
+    (customer_token, admin_token)
+}
+
+async fn pay(app: &TestApp, token: &str, order_id: Uuid, method: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/api/v1/orders/{}/payments",
+            &app.address, order_id
+        ))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "payment_method": method }))
+        .send()
+        .await
+        .expect("Failed to pay for order")
+}
+
+async fn admin_step(
+    app: &TestApp,
+    token: &str,
+    payment_id: &str,
+    step: &str,
+    body: Value,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/api/v1/admin/payments/{}/{}",
+            &app.address, payment_id, step
+        ))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to update payment")
+}
+
+async fn ship(app: &TestApp, token: &str, order_id: Uuid) -> reqwest::Response {
+    app.api_client
+        .patch(format!("{}/api/v1/admin/orders/{}", &app.address, order_id))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "status": "Shipped" }))
+        .send()
+        .await
+        .expect("Failed to update order status")
+}
+
+async fn callback(app: &TestApp, body: &Value, secret: &str, age_secs: i64) -> reqwest::Response {
+    let body = serde_json::to_vec(body).unwrap();
+    let timestamp = chrono::Utc::now().timestamp() - age_secs;
+    app.api_client
+        .post(format!("{}/api/v1/payments/webhook", &app.address))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, signature::sign(secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to send payment callback")
+}
+
+#[tokio::test]
+async fn orders_ship_only_after_payment_is_captured() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let order_id = place_order(&app).await;
+    let (customer_token, admin_token) = tokens(&app).await;
+
+    // Step: 1= Unpaid orders can't ship; a declined card leaves a failed attempt behind
+    let unpaid = ship(&app, &admin_token, order_id).await;
+    let declined = pay(&app, &customer_token, order_id, "tok_decline").await;
+    let authorized = pay(&app, &customer_token, order_id, "tok_visa").await;
+    let authorized_status = authorized.status().as_u16();
+    let authorized: Value = authorized.json().await.unwrap();
+    let payment_id = authorized["id"].as_str().unwrap().to_string();
+    let paid_twice = pay(&app, &customer_token, order_id, "tok_visa").await;
+    let uncaptured = ship(&app, &admin_token, order_id).await;
+
+    // Step: 2= Captured, shipped, then refunded in two parts
+    let captured: Value = admin_step(&app, &admin_token, &payment_id, "capture", Value::Null)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let shipped = ship(&app, &admin_token, order_id).await;
+    let partial: Value = admin_step(
+        &app,
+        &admin_token,
+        &payment_id,
+        "refund",
+        serde_json::json!({ "amount": 10000 }),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let too_much = admin_step(
+        &app,
+        &admin_token,
+        &payment_id,
+        "refund",
+        serde_json::json!({ "amount": 50000 }),
+    )
+    .await;
+    let refunded: Value = admin_step(
+        &app,
+        &admin_token,
+        &payment_id,
+        "refund",
+        serde_json::json!({}),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let attempts: Value = app
+        .api_client
+        .get(format!(
+            "{}/api/v1/orders/{}/payments",
+            &app.address, order_id
+        ))
+        .bearer_auth(&customer_token)
+        .send()
+        .await
+        .expect("Failed to list payments")
+        .json()
+        .await
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(unpaid.status().as_u16(), 409);
+    assert_eq!(declined.status().as_u16(), 402);
+    assert_eq!(authorized_status, 201);
+    assert_eq!(authorized["status"], "Authorized");
+    assert_eq!(authorized["amount"], 50000);
+    assert_eq!(paid_twice.status().as_u16(), 409);
+    assert_eq!(uncaptured.status().as_u16(), 409);
+    assert_eq!(captured["status"], "Captured");
+    assert_eq!(shipped.status().as_u16(), 200);
+    assert_eq!(partial["status"], "PartiallyRefunded");
+    assert_eq!(partial["refunded_amount"], 10000);
+    assert_eq!(too_much.status().as_u16(), 400);
+    assert_eq!(refunded["status"], "Refunded");
+    assert_eq!(refunded["refunded_amount"], 50000);
+    assert_eq!(attempts[0]["status"], "Failed");
+    assert_eq!(attempts[0]["last_error"], "Card declined");
+    assert_eq!(attempts[1]["id"], payment_id.as_str());
+}
+
+async fn cancel(app: &TestApp, token: &str, order_id: Uuid) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/api/v1/orders/{}/cancel",
+            &app.address, order_id
+        ))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "reason": "Changed my mind" }))
+        .send()
+        .await
+        .expect("Failed to cancel order")
+}
+
+#[tokio::test]
+async fn orders_holding_a_payment_are_cancelled_only_once_it_is_settled() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let authorized_order = place_order(&app).await;
+    let captured_order = place_order(&app).await;
+    let (customer_token, admin_token) = tokens(&app).await;
+
+    // Step: 1= An authorization holds the order until it is voided
+    let authorized: Value = pay(&app, &customer_token, authorized_order, "tok_visa")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let authorized_id = authorized["id"].as_str().unwrap().to_string();
+    let while_authorized = cancel(&app, &customer_token, authorized_order).await;
+    admin_step(&app, &admin_token, &authorized_id, "void", Value::Null).await;
+    let after_void = cancel(&app, &customer_token, authorized_order).await;
+    let after_void_status = after_void.status().as_u16();
+    let after_void: Value = after_void.json().await.unwrap();
+
+    // Step: 2= A capture holds it until it is refunded, which cancels the order
+    let captured: Value = pay(&app, &customer_token, captured_order, "tok_visa")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let captured_id = captured["id"].as_str().unwrap().to_string();
+    admin_step(&app, &admin_token, &captured_id, "capture", Value::Null).await;
+    let while_captured = cancel(&app, &customer_token, captured_order).await;
+    admin_step(
+        &app,
+        &admin_token,
+        &captured_id,
+        "refund",
+        serde_json::json!({}),
+    )
+    .await;
+    let refunded_order = Repositories::postgres(app.db_pool.clone())
+        .orders
+        .find(captured_order)
+        .await
+        .unwrap()
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(while_authorized.status().as_u16(), 409);
+    assert_eq!(after_void_status, 200);
+    assert_eq!(after_void["status"], "Cancelled");
+    assert_eq!(while_captured.status().as_u16(), 409);
+    assert_eq!(refunded_order.status, OrderStatus::Cancelled);
+}
+
+#[tokio::test]
+async fn refunding_a_pending_order_in_full_puts_its_unit_back_in_stock() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let product_id = Uuid::parse_str(PRODUCT_ID).unwrap();
+    let laptop = repositories
+        .products
+        .find(product_id)
+        .await
+        .unwrap()
+        .unwrap();
+    repositories
+        .products
+        .update(Product {
+            stock: Some(3),
+            ..laptop
+        })
+        .await
+        .unwrap();
+    let order_id = place_order(&app).await;
+    let (customer_token, admin_token) = tokens(&app).await;
+
+    let paid: Value = pay(&app, &customer_token, order_id, "tok_visa")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let payment_id = paid["id"].as_str().unwrap().to_string();
+    admin_step(&app, &admin_token, &payment_id, "capture", Value::Null).await;
+    let held = repositories.products.find(product_id).await.unwrap();
+    let refunded = admin_step(
+        &app,
+        &admin_token,
+        &payment_id,
+        "refund",
+        serde_json::json!({}),
+    )
+    .await;
+    let refunded_status = refunded.status().as_u16();
+    let order = repositories.orders.find(order_id).await.unwrap().unwrap();
+    let restocked = repositories.products.find(product_id).await.unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(held.and_then(|product| product.stock), Some(2));
+    assert_eq!(refunded_status, 200);
+    assert_eq!(order.status, OrderStatus::Cancelled);
+    assert_eq!(restocked.and_then(|product| product.stock), Some(3));
+}
+
+#[tokio::test]
+async fn voided_authorizations_free_the_order_for_another_payment() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let order_id = place_order(&app).await;
+    let (customer_token, admin_token) = tokens(&app).await;
+
+    let first: Value = pay(&app, &customer_token, order_id, "tok_visa")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let first_id = first["id"].as_str().unwrap().to_string();
+    let voided: Value = admin_step(&app, &admin_token, &first_id, "void", Value::Null)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let capture_voided = admin_step(&app, &admin_token, &first_id, "capture", Value::Null).await;
+    let refund_uncaptured = admin_step(
+        &app,
+        &admin_token,
+        &first_id,
+        "refund",
+        serde_json::json!({}),
+    )
+    .await;
+    let second = pay(&app, &customer_token, order_id, "tok_visa").await;
+    let admin_view: Value = app
+        .api_client
+        .get(format!(
+            "{}/api/v1/admin/orders/{}/payments",
+            &app.address, order_id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to list payments")
+        .json()
+        .await
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(voided["status"], "Voided");
+    assert_eq!(capture_voided.status().as_u16(), 409);
+    assert_eq!(refund_uncaptured.status().as_u16(), 409);
+    assert_eq!(second.status().as_u16(), 201);
+    assert_eq!(admin_view.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn provider_callbacks_are_verified_and_applied_once() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let order_id = place_order(&app).await;
+    let payment = payments::authorize(
+        &app.db_pool,
+        &MockPaymentProvider,
+        app.test_user.user_id,
+        order_id,
+        "tok_visa",
+    )
+    .await
+    .unwrap();
+    let reference = payment.provider_reference.clone().unwrap();
+    let event = |id: &str, event_type: &str| serde_json::json!({ "id": id, "type": event_type, "reference": reference });
+
+    // Step: 1= Forged and replayed callbacks are refused before anything is read
+    let forged = callback(
+        &app,
+        &event("evt_1", "payment.captured"),
+        "not-the-secret",
+        0,
+    )
+    .await;
+    let stale = callback(
+        &app,
+        &event("evt_1", "payment.captured"),
+        PAYMENT_WEBHOOK_SECRET,
+        600,
+    )
+    .await;
+
+    // Step: 2= The capture is applied once, however often it is delivered
+    let captured: Value = callback(
+        &app,
+        &event("evt_1", "payment.captured"),
+        PAYMENT_WEBHOOK_SECRET,
+        0,
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let redelivered: Value = callback(
+        &app,
+        &event("evt_1", "payment.captured"),
+        PAYMENT_WEBHOOK_SECRET,
+        0,
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let after_capture = payments::find_payment(&app.db_pool, payment.id)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Step: 3= A full refund before shipping cancels the order; late events change nothing
+    let refunded: Value = callback(
+        &app,
+        &event("evt_2", "payment.refunded"),
+        PAYMENT_WEBHOOK_SECRET,
+        0,
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let late_void: Value = callback(
+        &app,
+        &event("evt_3", "payment.voided"),
+        PAYMENT_WEBHOOK_SECRET,
+        0,
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let unknown = callback(
+        &app,
+        &serde_json::json!({ "id": "evt_4", "type": "payment.captured", "reference": "mock_unknown" }),
+        PAYMENT_WEBHOOK_SECRET,
+        0,
+    )
+    .await;
+    let after_refund = payments::find_payment(&app.db_pool, payment.id)
+        .await
+        .unwrap()
+        .unwrap();
+    let order = Repositories::postgres(app.db_pool.clone())
+        .orders
+        .find(order_id)
+        .await
+        .unwrap()
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(forged.status().as_u16(), 401);
+    assert_eq!(stale.status().as_u16(), 401);
+    assert_eq!(captured["outcome"], "applied");
+    assert_eq!(redelivered["outcome"], "duplicate");
+    assert_eq!(after_capture.status, payments::PaymentStatus::Captured);
+    assert_eq!(refunded["outcome"], "applied");
+    assert_eq!(late_void["outcome"], "ignored");
+    assert_eq!(unknown.status().as_u16(), 404);
+    assert_eq!(after_refund.status, payments::PaymentStatus::Refunded);
+    assert_eq!(after_refund.refunded_amount, 50000);
+    assert_eq!(order.status, OrderStatus::Cancelled);
+}
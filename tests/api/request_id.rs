@@ -0,0 +1,82 @@
+use crate::helper::spawn_app;
+use ecommerce::db::drop_database;
+use serde_json::Value;
+
+#[tokio::test]
+async fn incoming_request_id_is_echoed_back() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/health_check", &app.address))
+        .header("X-Request-Id", "support-ticket-42")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(
+        response.headers().get("x-request-id").unwrap(),
+        "support-ticket-42"
+    );
+    drop_database(&app.database_name, app.test_db_url).await;
+}
+
+#[tokio::test]
+async fn request_id_is_generated_when_missing_or_invalid() {
+    let app = spawn_app().await;
+
+    for header in [None, Some("not a valid id!")] {
+        let mut request = app.api_client.get(format!("{}/health_check", &app.address));
+        if let Some(header) = header {
+            request = request.header("X-Request-Id", header);
+        }
+        let response = request.send().await.expect("Failed to execute request.");
+
+        let request_id = response
+            .headers()
+            .get("x-request-id")
+            .expect("Missing x-request-id header")
+            .to_str()
+            .unwrap();
+        assert!(uuid::Uuid::parse_str(request_id).is_ok());
+    }
+    drop_database(&app.database_name, app.test_db_url).await;
+}
+
+#[tokio::test]
+async fn error_bodies_include_the_request_id() {
+    let app = spawn_app().await;
+
+    // Step: 1= Invalid registration payload triggers a validation error
+    let response = app
+        .api_client
+        .post(format!("{}/register", &app.address))
+        .header("X-Request-Id", "trace-me")
+        .json(&serde_json::json!({
+            "username": "valid name",
+            "password": "password",
+            "email": "not-an-email"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Step: 2= Asserting the id is both in the header and in the body
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(response.headers().get("x-request-id").unwrap(), "trace-me");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["request_id"], "trace-me");
+    assert!(body["error"].as_str().unwrap().contains("Validation Error"));
+
+    // Step: 3= Errors from the JWT middleware carry the header as well
+    let response = app
+        .api_client
+        .get(format!("{}/protected/view", &app.address))
+        .header("X-Request-Id", "no-token")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers().get("x-request-id").unwrap(), "no-token");
+    drop_database(&app.database_name, app.test_db_url).await;
+}
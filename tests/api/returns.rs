@@ -0,0 +1,460 @@
+use crate::helper::{pay_for_order, seed_products, spawn_app, TestApp};
+use ecommerce::db::drop_database;
+use ecommerce::db_models::{Order, Product};
+use ecommerce::payments::{self, PaymentStatus};
+use ecommerce::repository::details::OrderTotals;
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+
+const PRODUCT_ID: &str = "5fcd7d83-7adf-4d4d-931a-68b9678009db";
+
+async fn place_order(app: &TestApp, repositories: &Repositories, status: OrderStatus) -> Uuid {
+    let order = Order {
+        id: Uuid::new_v4(),
+        customer_id: app.test_user.user_id,
+        status: OrderStatus::Pending,
+        created_at: chrono::Utc::now().naive_utc(),
+        product_id: Uuid::parse_str(PRODUCT_ID).unwrap(),
+    };
+    repositories.orders.insert(order.clone()).await.unwrap();
+    if status != OrderStatus::Pending {
+        pay_for_order(&app.db_pool, app.test_user.user_id, order.id).await;
+        repositories
+            .orders
+            .update_status(order.id, status)
+            .await
+            .unwrap();
+    }
+    order.id
+}
+
+async fn token(response: reqwest::Response) -> String {
+    let body: Value = response.json().await.unwrap();
+    body["token"].as_str().expect("Token not found").to_string()
+}
+
+#[tokio::test]
+async fn delivered_orders_are_returned_inspected_and_refunded() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let laptop = repositories
+        .products
+        .find(Uuid::parse_str(PRODUCT_ID).unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    repositories
+        .products
+        .update(Product {
+            stock: Some(5),
+            ..laptop
+        })
+        .await
+        .unwrap();
+    let delivered = place_order(&app, &repositories, OrderStatus::Delivered).await;
+    let shipped = place_order(&app, &repositories, OrderStatus::Shipped).await;
+
+    let credentials = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let customer_token = token(app.login_customer(credentials.clone()).await).await;
+    let admin_token = token(app.login_admin(credentials).await).await;
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let request_return = |order_id: Uuid| {
+        let request = app
+            .api_client
+            .post(format!(
+                "{}/api/v1/orders/{}/returns",
+                &app.address, order_id
+            ))
+            .bearer_auth(&customer_token)
+            .json(&serde_json::json!({
+                "items": [{ "product_id": PRODUCT_ID, "reason": "Screen arrived cracked" }]
+            }));
+        async move { request.send().await.expect("Failed to request return") }
+    };
+    let act = |return_id: &str, action: &str| {
+        let request = app
+            .api_client
+            .patch(format!(
+                "{}/api/v1/admin/returns/{}",
+                &app.address, return_id
+            ))
+            .bearer_auth(&admin_token)
+            .json(&serde_json::json!({ "action": action, "note": format!("{} step", action) }));
+        async move { request.send().await.expect("Failed to update return") }
+    };
+
+    // Step: 1= The customer asks to return the delivered order, not the shipped one
+    let requested = request_return(delivered).await;
+    let requested_status = requested.status().as_u16();
+    let requested: Value = requested.json().await.unwrap();
+    let not_delivered = request_return(shipped).await;
+    let duplicate = request_return(delivered).await;
+    let return_id = requested["id"].as_str().unwrap().to_string();
+
+    // Step: 2= An admin approves, receives and accepts it; skipping a step is refused
+    let too_early = act(&return_id, "Accept").await;
+    let approved = act(&return_id, "Approve").await;
+    let received = act(&return_id, "Receive").await;
+    let accepted: Value = act(&return_id, "Accept").await.json().await.unwrap();
+
+    // Step: 3= Every step shows up on the order timeline, without the admin behind it
+    let timeline: Value = app
+        .api_client
+        .get(format!(
+            "{}/api/v1/orders/{}/timeline",
+            &app.address, delivered
+        ))
+        .bearer_auth(&customer_token)
+        .send()
+        .await
+        .expect("Failed to fetch timeline")
+        .json()
+        .await
+        .unwrap();
+    let audit_trail: Value = app
+        .api_client
+        .get(format!(
+            "{}/api/v1/admin/orders/{}/timeline",
+            &app.address, delivered
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to fetch audit trail")
+        .json()
+        .await
+        .unwrap();
+
+    let stock = repositories
+        .products
+        .find(Uuid::parse_str(PRODUCT_ID).unwrap())
+        .await
+        .unwrap()
+        .and_then(|product| product.stock);
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(requested_status, 201);
+    assert_eq!(requested["status"], "Requested");
+    assert_eq!(requested["items"][0]["reason"], "Screen arrived cracked");
+    assert_eq!(not_delivered.status().as_u16(), 409);
+    assert_eq!(duplicate.status().as_u16(), 409);
+    assert_eq!(too_early.status().as_u16(), 409);
+    assert_eq!(approved.status().as_u16(), 200);
+    assert_eq!(received.status().as_u16(), 200);
+    assert_eq!(accepted["status"], "Accepted");
+    assert_eq!(accepted["note"], "Accept step");
+    assert_eq!(accepted["refund"]["amount"], 50000);
+    // Two orders took a unit each; the accepted return brought one back
+    assert_eq!(stock, Some(4));
+
+    let kinds: Vec<&str> = timeline
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "OrderPlaced",
+            "PaymentStatusChanged",
+            "PaymentStatusChanged",
+            "OrderStatusChanged",
+            "ReturnRequested",
+            "ReturnStatusChanged",
+            "ReturnStatusChanged",
+            "PaymentStatusChanged",
+            "ReturnStatusChanged",
+            "RefundCreated",
+        ]
+    );
+    assert_eq!(timeline[4]["reason"], "Screen arrived cracked");
+    // The refund went through the payment before the return was accepted
+    assert_eq!(timeline[7]["status"], "Refunded");
+    assert_eq!(timeline[8]["status"], "Accepted");
+    let customer_view = timeline.to_string();
+    assert!(!customer_view.contains("admin_id"));
+    // The test admin has the test user's id
+    assert!(!customer_view.contains(&app.test_user.user_id.to_string()));
+    assert!(!customer_view.contains("Accept step"));
+    // Admins still see who acted and their notes
+    assert_eq!(audit_trail[8]["event_type"], "ReturnStatusChanged");
+    assert_eq!(
+        audit_trail[8]["data"]["admin_id"],
+        app.test_user.user_id.to_string()
+    );
+    assert_eq!(audit_trail[8]["data"]["note"], "Accept step");
+}
+
+#[tokio::test]
+async fn refunds_are_for_what_the_order_cost_not_todays_price() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+
+    // Step: 1= Delivered with a price snapshot, then the product gets dearer
+    let order = Order {
+        id: Uuid::new_v4(),
+        customer_id: app.test_user.user_id,
+        status: OrderStatus::Pending,
+        created_at: chrono::Utc::now().naive_utc(),
+        product_id: Uuid::parse_str(PRODUCT_ID).unwrap(),
+    };
+    let totals = OrderTotals {
+        unit_price: 50000,
+        subtotal: 50000,
+        discount: 5000,
+        shipping: 500,
+        tax: 3263,
+        grand_total: 48763,
+        tax_region: Some("US-CA".to_string()),
+        tax_rate_bps: 725,
+    };
+    repositories
+        .orders
+        .insert_with_totals(order.clone(), totals)
+        .await
+        .unwrap();
+    pay_for_order(&app.db_pool, app.test_user.user_id, order.id).await;
+    repositories
+        .orders
+        .update_status(order.id, OrderStatus::Delivered)
+        .await
+        .unwrap();
+    let laptop = repositories
+        .products
+        .find(order.product_id)
+        .await
+        .unwrap()
+        .unwrap();
+    repositories
+        .products
+        .update(Product {
+            price: 60000,
+            ..laptop
+        })
+        .await
+        .unwrap();
+
+    let credentials = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let customer_token = token(app.login_customer(credentials.clone()).await).await;
+    let admin_token = token(app.login_admin(credentials).await).await;
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    // Step: 2= Returned and accepted
+    let requested: Value = app
+        .api_client
+        .post(format!(
+            "{}/api/v1/orders/{}/returns",
+            &app.address, order.id
+        ))
+        .bearer_auth(&customer_token)
+        .json(&serde_json::json!({
+            "items": [{ "product_id": PRODUCT_ID, "reason": "Too heavy" }]
+        }))
+        .send()
+        .await
+        .expect("Failed to request return")
+        .json()
+        .await
+        .unwrap();
+    let return_id = requested["id"].as_str().unwrap().to_string();
+    let mut accepted = Value::Null;
+    for (action, note) in [
+        ("Approve", Some("Heavier than listed")),
+        ("Receive", None),
+        ("Accept", None),
+    ] {
+        accepted = app
+            .api_client
+            .patch(format!(
+                "{}/api/v1/admin/returns/{}",
+                &app.address, return_id
+            ))
+            .bearer_auth(&admin_token)
+            .json(&serde_json::json!({ "action": action, "note": note }))
+            .send()
+            .await
+            .expect("Failed to update return")
+            .json()
+            .await
+            .unwrap();
+    }
+    let payments = payments::list_for_order(&app.db_pool, order.id)
+        .await
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(accepted["status"], "Accepted");
+    // Steps without a note leave the earlier one in place
+    assert_eq!(accepted["note"], "Heavier than listed");
+    // The discounted goods and their tax, without shipping
+    assert_eq!(accepted["refund"]["amount"], 45000 + 3263);
+    // Given back through the payment, which keeps the shipping
+    assert_eq!(payments[0].status, PaymentStatus::PartiallyRefunded);
+    assert_eq!(payments[0].refunded_amount, 45000 + 3263);
+}
+
+#[tokio::test]
+async fn accepting_a_return_while_the_customer_orders_the_same_product() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let laptop = repositories
+        .products
+        .find(Uuid::parse_str(PRODUCT_ID).unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    repositories
+        .products
+        .update(Product {
+            stock: Some(10),
+            ..laptop
+        })
+        .await
+        .unwrap();
+    let mut delivered = Vec::new();
+    for _ in 0..5 {
+        delivered.push(place_order(&app, &repositories, OrderStatus::Delivered).await);
+    }
+
+    let credentials = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let customer_token = token(app.login_customer(credentials.clone()).await).await;
+    let admin_token = token(app.login_admin(credentials).await).await;
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let act = |return_id: String, action: &'static str| {
+        let request = app
+            .api_client
+            .patch(format!(
+                "{}/api/v1/admin/returns/{}",
+                &app.address, return_id
+            ))
+            .bearer_auth(&admin_token)
+            .json(&serde_json::json!({ "action": action }));
+        async move { request.send().await.expect("Failed to update return") }
+    };
+    let order_again = || {
+        let request = app
+            .api_client
+            .post(format!("{}/api/v1/orders", &app.address))
+            .bearer_auth(&customer_token)
+            .json(&serde_json::json!({ "product_id": PRODUCT_ID }));
+        async move { request.send().await.expect("Failed to place order") }
+    };
+
+    // Step: 1= Every delivered order is returned and waiting for inspection
+    let mut return_ids = Vec::new();
+    for order_id in &delivered {
+        let requested: Value = app
+            .api_client
+            .post(format!(
+                "{}/api/v1/orders/{}/returns",
+                &app.address, order_id
+            ))
+            .bearer_auth(&customer_token)
+            .json(&serde_json::json!({
+                "items": [{ "product_id": PRODUCT_ID, "reason": "Changed my mind" }]
+            }))
+            .send()
+            .await
+            .expect("Failed to request return")
+            .json()
+            .await
+            .unwrap();
+        let return_id = requested["id"].as_str().unwrap().to_string();
+        act(return_id.clone(), "Approve").await;
+        act(return_id.clone(), "Receive").await;
+        return_ids.push(return_id);
+    }
+
+    // Step: 2= Each return is accepted while the same customer orders the same laptop
+    let mut statuses = Vec::new();
+    for return_id in return_ids {
+        let (accepted, ordered) = tokio::join!(act(return_id, "Accept"), order_again());
+        statuses.push((accepted.status().as_u16(), ordered.status().as_u16()));
+    }
+    let stock = repositories
+        .products
+        .find(Uuid::parse_str(PRODUCT_ID).unwrap())
+        .await
+        .unwrap()
+        .and_then(|product| product.stock);
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(statuses, vec![(200, 200); 5]);
+    // Five taken, five brought back, five taken again
+    assert_eq!(stock, Some(5));
+}
+
+#[tokio::test]
+async fn customers_cannot_return_or_inspect_other_orders() {
+    let app = spawn_app().await;
+    let _ = seed_products(app.db_pool.clone()).await;
+
+    let customer_token = token(
+        app.login_customer(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await,
+    )
+    .await;
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let unknown = Uuid::new_v4();
+    let returned = app
+        .api_client
+        .post(format!(
+            "{}/api/v1/orders/{}/returns",
+            &app.address, unknown
+        ))
+        .bearer_auth(&customer_token)
+        .json(&serde_json::json!({
+            "items": [{ "product_id": PRODUCT_ID, "reason": "Not mine" }]
+        }))
+        .send()
+        .await
+        .expect("Failed to request return");
+    let timeline = app
+        .api_client
+        .get(format!(
+            "{}/api/v1/orders/{}/timeline",
+            &app.address, unknown
+        ))
+        .bearer_auth(&customer_token)
+        .send()
+        .await
+        .expect("Failed to fetch timeline");
+    let admin_queue = app
+        .api_client
+        .get(format!("{}/api/v1/admin/returns", &app.address))
+        .bearer_auth(&customer_token)
+        .send()
+        .await
+        .expect("Failed to list returns");
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(returned.status().as_u16(), 404);
+    assert_eq!(timeline.status().as_u16(), 404);
+    assert_eq!(admin_queue.status().as_u16(), 401);
+}
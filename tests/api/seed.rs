@@ -0,0 +1,50 @@
+use crate::helper::spawn_app;
+use ecommerce::db::drop_database;
+use ecommerce::repository::Repositories;
+use ecommerce::routes::products::seed::{parse_fixtures, seed_products, SeedReport};
+use std::path::Path;
+
+#[tokio::test]
+async fn seeding_is_idempotent_and_reports_changes() {
+    let app = spawn_app().await;
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let mut fixtures = parse_fixtures(Path::new("fixtures/products.toml")).unwrap();
+
+    let first = seed_products(repositories.products.as_ref(), &fixtures)
+        .await
+        .unwrap();
+    let second = seed_products(repositories.products.as_ref(), &fixtures)
+        .await
+        .unwrap();
+    fixtures[0].price += 100;
+    let third = seed_products(repositories.products.as_ref(), &fixtures)
+        .await
+        .unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+    let total = fixtures.len();
+    assert_eq!(
+        first,
+        SeedReport {
+            inserted: total,
+            updated: 0,
+            skipped: 0
+        }
+    );
+    assert_eq!(
+        second,
+        SeedReport {
+            inserted: 0,
+            updated: 0,
+            skipped: total
+        }
+    );
+    assert_eq!(
+        third,
+        SeedReport {
+            inserted: 0,
+            updated: 1,
+            skipped: total - 1
+        }
+    );
+}
@@ -0,0 +1,199 @@
+use crate::helper::{pay_for_order, seed_products, spawn_app};
+use ecommerce::db::drop_database;
+use serde_json::{self, Value};
+use std::time::Duration;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn v1_customer_and_order_routes_work() {
+    let app = spawn_app().await;
+
+    // Step: 1= Customer login through the versioned sessions resource
+    let login_response = app
+        .api_client
+        .post(format!("{}/api/v1/sessions", &app.address))
+        .json(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(login_response.status().as_u16(), 200);
+    assert!(login_response.headers().get("deprecation").is_none());
+    let login_response_body: Value = login_response.json().await.unwrap();
+    let token = login_response_body["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    // Step: 2= Updating and viewing the profile via /api/v1/me
+    let update_response = app
+        .api_client
+        .patch(format!("{}/api/v1/me", &app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "username": "Versioned username",
+            "email": "versioned@gmail.com"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(update_response.status().as_u16(), 200);
+
+    let view_response = app
+        .api_client
+        .get(format!("{}/api/v1/me", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(view_response.status().as_u16(), 200);
+    assert!(view_response
+        .text()
+        .await
+        .unwrap()
+        .contains("Versioned username"));
+
+    // Step: 3= Creating, listing and fetching orders
+    let _ = seed_products(app.db_pool.clone()).await;
+    let order_response = app
+        .api_client
+        .post(format!("{}/api/v1/orders", &app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "product_id": "5fcd7d83-7adf-4d4d-931a-68b9678009db",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(order_response.status().as_u16(), 200);
+    let order_response_body: Value = order_response.json().await.unwrap();
+    let order_id = order_response_body["order_id"]
+        .as_str()
+        .expect("Order id not found")
+        .to_string();
+
+    let list_response = app
+        .api_client
+        .get(format!("{}/api/v1/orders", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(list_response.text().await.unwrap().contains(&order_id));
+
+    let get_response = app
+        .api_client
+        .get(format!("{}/api/v1/orders/{}", &app.address, order_id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(get_response.status().as_u16(), 200);
+
+    // Step: 4= Admin updates the status through PATCH /api/v1/admin/orders/{id}
+    let admin_login_response = app
+        .api_client
+        .post(format!("{}/api/v1/admin/sessions", &app.address))
+        .json(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let admin_login_body: Value = admin_login_response.json().await.unwrap();
+    let admin_token = admin_login_body["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    pay_for_order(
+        &app.db_pool,
+        app.test_user.user_id,
+        Uuid::parse_str(&order_id).unwrap(),
+    )
+    .await;
+    let patch_response = app
+        .api_client
+        .patch(format!("{}/api/v1/admin/orders/{}", &app.address, order_id))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({ "status": "Shipped" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(patch_response.status().as_u16(), 200);
+
+    let get_response = app
+        .api_client
+        .get(format!("{}/api/v1/orders/{}", &app.address, order_id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(get_response
+        .text()
+        .await
+        .unwrap()
+        .to_lowercase()
+        .contains("shipped"));
+    drop_database(&app.database_name, app.test_db_url).await;
+}
+
+#[tokio::test]
+async fn legacy_routes_carry_deprecation_headers() {
+    let app = spawn_app().await;
+
+    let body = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password
+    });
+    let login_response = app.login_customer(body).await;
+
+    assert_eq!(login_response.status().as_u16(), 200);
+    assert_eq!(login_response.headers().get("deprecation").unwrap(), "true");
+    assert_eq!(
+        login_response.headers().get("link").unwrap(),
+        r#"</api/v1/sessions>; rel="successor-version""#
+    );
+    let login_response_body: Value = login_response.json().await.unwrap();
+    let token = login_response_body["token"].as_str().unwrap().to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    // Path parameters are substituted into the successor link
+    let order_id = uuid::Uuid::new_v4().to_string();
+    let response = app.get_order(&order_id, token).await;
+    assert_eq!(response.headers().get("deprecation").unwrap(), "true");
+    assert_eq!(
+        response.headers().get("link").unwrap().to_str().unwrap(),
+        format!("</api/v1/orders/{}>; rel=\"successor-version\"", order_id)
+    );
+
+    // The order id is in the body here, so there is no successor URL to point at
+    let admin_login_response: Value = app
+        .login_admin(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let admin_token = admin_login_response["token"].as_str().unwrap().to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+    let status_response = app
+        .update_order_status(
+            serde_json::json!({ "order_id": order_id, "status": "Shipped" }),
+            admin_token,
+        )
+        .await;
+    assert_eq!(
+        status_response.headers().get("deprecation").unwrap(),
+        "true"
+    );
+    assert!(status_response.headers().get("link").is_none());
+    drop_database(&app.database_name, app.test_db_url).await;
+}
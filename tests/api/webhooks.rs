@@ -0,0 +1,192 @@
+use crate::helper::{new_order, pay_for_order, spawn_app};
+use ecommerce::db::drop_database;
+use ecommerce::jobs::worker::run_next;
+use ecommerce::jobs::JobRegistry;
+use ecommerce::outbox::dispatcher::{dispatch_pending, EventSink};
+use ecommerce::repository::Repositories;
+use ecommerce::routes::order::order::OrderStatus;
+use ecommerce::webhooks::{
+    self, signature, DeliverWebhook, DeliverWebhookHandler, DeliveryStatus, WebhookSink,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const SECRET: &str = "whsec_0123456789abcdef";
+const STALE_AFTER: Duration = Duration::from_secs(300);
+
+// Moves recorded events into webhook deliveries, then runs every delivery job that is due
+async fn deliver_all(pool: &ecommerce::db::PgPool) {
+    let sinks: Vec<Arc<dyn EventSink>> = vec![Arc::new(WebhookSink::new(pool.clone()))];
+    dispatch_pending(pool, &sinks, 100)
+        .await
+        .expect("Failed to dispatch outbox");
+    let registry = JobRegistry::default()
+        .register::<DeliverWebhook, _>(DeliverWebhookHandler::new(pool.clone()));
+    while run_next(pool, &registry, STALE_AFTER)
+        .await
+        .expect("Failed to run delivery job")
+    {}
+}
+
+#[tokio::test]
+async fn subscribed_events_are_delivered_signed() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&receiver)
+        .await;
+
+    // Step: 1= Admin registers an endpoint for placed orders only
+    let admin_login_response = app
+        .login_admin(serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password
+        }))
+        .await;
+    let admin_login_response: Value = admin_login_response.json().await.unwrap();
+    let admin_token = admin_login_response["token"]
+        .as_str()
+        .expect("Token not found")
+        .to_string();
+    tokio::time::sleep(Duration::from_secs(12)).await;
+
+    let invalid_response = app
+        .api_client
+        .post(format!("{}/api/v1/admin/webhooks", &app.address))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "url": format!("{}/hooks", receiver.uri()),
+            "secret": "short"
+        }))
+        .send()
+        .await
+        .expect("Failed to register webhook");
+    let register_response = app
+        .api_client
+        .post(format!("{}/api/v1/admin/webhooks", &app.address))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({
+            "url": format!("{}/hooks", receiver.uri()),
+            "secret": SECRET,
+            "event_types": ["OrderPlaced"]
+        }))
+        .send()
+        .await
+        .expect("Failed to register webhook");
+    let register_status = register_response.status().as_u16();
+    let endpoint: Value = register_response.json().await.unwrap();
+    let endpoint_id = endpoint["id"].as_str().expect("Endpoint id not found");
+
+    // Step: 2= Place an order and ship it; only the placement matches the filter
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    let order = new_order(&app.db_pool, app.test_user.user_id).await;
+    repositories.orders.insert(order.clone()).await.unwrap();
+    pay_for_order(&app.db_pool, app.test_user.user_id, order.id).await;
+    repositories
+        .orders
+        .update_status(order.id, OrderStatus::Shipped)
+        .await
+        .unwrap();
+    deliver_all(&app.db_pool).await;
+    // A second dispatch pass must not send anything twice
+    deliver_all(&app.db_pool).await;
+
+    let log_response = app
+        .api_client
+        .get(format!(
+            "{}/api/v1/admin/webhooks/{}/deliveries",
+            &app.address, endpoint_id
+        ))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .expect("Failed to fetch delivery log");
+    let log_status = log_response.status().as_u16();
+    let log: Value = log_response.json().await.unwrap();
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(invalid_response.status().as_u16(), 400);
+    assert_eq!(register_status, 201);
+    assert!(
+        endpoint.get("secret").is_none(),
+        "Secret must not be echoed"
+    );
+
+    let requests = receiver.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    let header = |name: &str| {
+        request
+            .headers
+            .get(name)
+            .unwrap_or_else(|| panic!("Missing {} header", name))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let timestamp: i64 = header(webhooks::TIMESTAMP_HEADER).parse().unwrap();
+    assert!(signature::verify(
+        SECRET,
+        timestamp,
+        &request.body,
+        &header(webhooks::SIGNATURE_HEADER)
+    ));
+    assert_eq!(header(webhooks::EVENT_HEADER), "OrderPlaced");
+    let body: Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["id"], header(webhooks::ID_HEADER));
+    assert_eq!(body["event"]["type"], "OrderPlaced");
+    assert_eq!(body["event"]["data"]["order_id"], order.id.to_string());
+
+    assert_eq!(log_status, 200);
+    assert_eq!(log.as_array().unwrap().len(), 1);
+    assert_eq!(log[0]["status"], "Succeeded");
+    assert_eq!(log[0]["attempts"], 1);
+    assert_eq!(log[0]["response_status"], 200);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_logged_and_retried() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&receiver)
+        .await;
+
+    let endpoint = webhooks::create_endpoint(&app.db_pool, &receiver.uri(), SECRET, vec![])
+        .await
+        .expect("Failed to create endpoint");
+    let repositories = Repositories::postgres(app.db_pool.clone());
+    repositories
+        .orders
+        .insert(new_order(&app.db_pool, app.test_user.user_id).await)
+        .await
+        .unwrap();
+    deliver_all(&app.db_pool).await;
+
+    let deliveries = webhooks::list_deliveries(&app.db_pool, endpoint.id, 10)
+        .await
+        .expect("Failed to list deliveries");
+    let pending_jobs =
+        ecommerce::jobs::list_jobs(&app.db_pool, Some(ecommerce::jobs::JobStatus::Pending), 10)
+            .await
+            .expect("Failed to list jobs");
+
+    drop_database(&app.database_name, app.test_db_url).await;
+
+    assert_eq!(receiver.received_requests().await.unwrap().len(), 1);
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].status, DeliveryStatus::Failed);
+    assert_eq!(deliveries[0].attempts, 1);
+    assert_eq!(deliveries[0].response_status, Some(503));
+    // The delivery job is backed off for another attempt
+    assert_eq!(pending_jobs.len(), 1);
+    assert_eq!(pending_jobs[0].attempts, 1);
+    assert!(pending_jobs[0].run_at > chrono::Utc::now().naive_utc());
+}